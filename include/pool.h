@@ -0,0 +1,45 @@
+/* C bindings for pools of byte buffers, see the `ffi` module of the pool
+ * crate. Built with the `ffi` feature. */
+
+#ifndef POOL_H
+#define POOL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A pool of byte buffers. */
+typedef struct BufferPool BufferPool;
+
+/* A buffer checked out from a `BufferPool`. */
+typedef struct Buffer Buffer;
+
+/* Creates a pool of `count` zeroed buffers of at least `size` bytes each,
+ * or returns NULL. The pool must be freed with `pool_free`. */
+BufferPool *pool_new(size_t count, size_t size);
+
+/* Frees a pool created by `pool_new`. Buffers still checked out are
+ * leaked. */
+void pool_free(BufferPool *pool);
+
+/* Checks out a buffer, or returns NULL if all buffers are in use. */
+Buffer *pool_checkout(BufferPool *pool);
+
+/* Returns a buffer to `pool`, which it must have been checked out from.
+ * Aborts the process otherwise. */
+void pool_checkin(BufferPool *pool, Buffer *buf);
+
+/* Returns a pointer to the first byte of a checked out buffer. */
+uint8_t *pool_buffer_ptr(Buffer *buf);
+
+/* Returns the size of a checked out buffer in bytes. */
+size_t pool_buffer_len(const Buffer *buf);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* POOL_H */
@@ -0,0 +1,231 @@
+//! The `#[pooled]` attribute, re-exported by the `pool` crate with its
+//! `macros` feature. See the documentation there.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Data, DeriveInput, Expr, Fields, Ident, Index, Path};
+use syn::parse::Error;
+
+#[proc_macro_attribute]
+pub fn pooled(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item = syn::parse_macro_input!(item as DeriveInput);
+    let mut opts = Options::default();
+
+    let parser = syn::meta::parser(|meta| opts.parse(meta));
+    syn::parse_macro_input!(args with parser);
+
+    match expand(&mut item, opts) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// Arguments of the attribute
+#[derive(Default)]
+struct Options {
+    pool: Option<Ident>,
+    handle: Option<Ident>,
+    capacity: Option<Expr>,
+    init: Option<Path>,
+}
+
+impl Options {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("pool") {
+            self.pool = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("handle") {
+            self.handle = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("capacity") {
+            self.capacity = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("init") {
+            self.init = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("expected `pool`, `handle`, `capacity` or `init`"));
+        }
+
+        Ok(())
+    }
+}
+
+// How a field is reset, from its `#[reset(...)]` attribute
+enum FieldReset {
+    Reset,
+    Default,
+    With(Path),
+    Skip,
+}
+
+fn expand(item: &mut DeriveInput, opts: Options) -> syn::Result<TokenStream2> {
+    let fields = match item.data {
+        Data::Struct(ref mut data) => &mut data.fields,
+        _ => return Err(Error::new(Span::call_site(), "`#[pooled]` only applies to structs")),
+    };
+
+    let resets = reset_fields(fields)?;
+
+    let name = &item.ident;
+    let vis = &item.vis;
+    let pool = opts.pool.unwrap_or_else(|| Ident::new(&format!("{}Pool", name), name.span()));
+    let handle = opts.handle.unwrap_or_else(|| Ident::new(&format!("{}Handle", name), name.span()));
+    // Without a capacity, the pool starts out empty and grows as needed, so
+    // a checkout never waits for a value that will never come
+    let builder = match opts.capacity {
+        Some(capacity) => quote!(#pool::builder().capacity(#capacity)),
+        None => quote!(#pool::builder().unbounded()),
+    };
+    let init = match opts.init {
+        Some(init) => quote!(#init),
+        None => quote!(<#name as ::std::default::Default>::default),
+    };
+
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(&item.generics, "`#[pooled]` does not support generic structs"));
+    }
+
+    let pool_doc = format!("A pool of `{}`, generated by `#[pooled]`.", name);
+    let handle_doc = format!("A `{}` checked out of a `{}`.", name, pool);
+
+    Ok(quote! {
+        #item
+
+        impl ::pool::Reset for #name {
+            fn reset(&mut self) {
+                #(#resets)*
+            }
+        }
+
+        #[doc = #handle_doc]
+        #vis type #handle = ::pool::Checkout<#name>;
+
+        #[doc = #pool_doc]
+        #vis struct #pool {
+            pool: ::pool::Pool<#name>,
+        }
+
+        impl #pool {
+            /// Creates a pool with the capacity given to `#[pooled]`. Without
+            /// one, the pool starts out empty and grows as values are checked
+            /// out.
+            pub fn new() -> #pool {
+                #pool::build(&#builder)
+            }
+
+            /// Creates a pool of `capacity` values.
+            pub fn with_capacity(capacity: usize) -> #pool {
+                #pool::build(&#pool::builder().capacity(capacity))
+            }
+
+            /// Returns a builder to configure the pool with, see `build`.
+            pub fn builder() -> ::pool::Builder<#name> {
+                ::pool::Builder::new()
+            }
+
+            /// Creates a pool configured by `builder`, initializing its values
+            /// like `new` does.
+            pub fn build(builder: &::pool::Builder<#name>) -> #pool {
+                #pool {
+                    pool: builder.build(#init),
+                }
+            }
+
+            /// See `Pool::checkout`.
+            pub fn checkout(&mut self) -> #handle {
+                self.pool.checkout()
+            }
+
+            /// See `Pool::try_checkout`.
+            pub fn try_checkout(&mut self) -> ::std::option::Option<#handle> {
+                self.pool.try_checkout()
+            }
+
+            /// Returns the pool.
+            pub fn into_inner(self) -> ::pool::Pool<#name> {
+                self.pool
+            }
+        }
+
+        impl ::std::default::Default for #pool {
+            fn default() -> #pool {
+                #pool::new()
+            }
+        }
+
+        impl ::std::ops::Deref for #pool {
+            type Target = ::pool::Pool<#name>;
+
+            fn deref(&self) -> &::pool::Pool<#name> {
+                &self.pool
+            }
+        }
+
+        impl ::std::ops::DerefMut for #pool {
+            fn deref_mut(&mut self) -> &mut ::pool::Pool<#name> {
+                &mut self.pool
+            }
+        }
+    })
+}
+
+// Returns the statement resetting each field, removing the `#[reset]`
+// attributes, which the compiler wouldn't know about
+fn reset_fields(fields: &mut Fields) -> syn::Result<Vec<TokenStream2>> {
+    let mut resets = vec![];
+
+    for (i, field) in fields.iter_mut().enumerate() {
+        let mut reset = FieldReset::Reset;
+        let mut error = None;
+
+        field.attrs.retain(|attr| {
+            if !attr.path().is_ident("reset") {
+                return true;
+            }
+
+            let res = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    reset = FieldReset::Skip;
+                } else if meta.path.is_ident("default") {
+                    reset = FieldReset::Default;
+                } else if meta.path.is_ident("with") {
+                    reset = FieldReset::With(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `skip`, `default` or `with`"));
+                }
+
+                Ok(())
+            });
+
+            if let Err(e) = res {
+                error = Some(e);
+            }
+
+            false
+        });
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let member = match field.ident {
+            Some(ref ident) => quote!(#ident),
+            None => {
+                let idx = Index::from(i);
+                quote!(#idx)
+            }
+        };
+
+        resets.push(match reset {
+            FieldReset::Reset => quote!(::pool::Reset::reset(&mut self.#member);),
+            FieldReset::Default => quote!(self.#member = ::std::default::Default::default();),
+            FieldReset::With(path) => quote!(#path(&mut self.#member);),
+            FieldReset::Skip => quote!(),
+        });
+    }
+
+    Ok(resets)
+}
@@ -0,0 +1,107 @@
+//! An allocator handing out the extra storage of a pool's entries.
+//!
+//! `PoolAllocator` implements the `Allocator` trait of the `allocator-api2`
+//! crate, which is the standard library's unstable `Allocator` on nightly
+//! with its `nightly` feature. Collections such as `allocator_api2`'s `Vec`
+//! and `Box` then draw their memory from the pool:
+//!
+//! ```
+//! extern crate allocator_api2;
+//! extern crate pool;
+//!
+//! use allocator_api2::vec::Vec;
+//! use pool::{Dirty, OnEmpty, Pool};
+//! use pool::allocator::PoolAllocator;
+//!
+//! # fn main() {
+//! let alloc = PoolAllocator::new(Pool::builder()
+//!     .capacity(16)
+//!     .extra(4096)
+//!     .on_empty(OnEmpty::Grow { max: 64 })
+//!     .build(|| Dirty(())));
+//!
+//! let mut vec = Vec::with_capacity_in(1024, &alloc);
+//! vec.extend_from_slice(b"pooled");
+//! # }
+//! ```
+//!
+//! Each allocation takes a whole entry, checked out for as long as the
+//! allocation lives. Allocations larger than the extra storage, or aligned
+//! beyond it, go to the global allocator instead, as do empty ones.
+
+use {Checkout, Entry, Extra, Pool, Reset};
+use allocator_api2::alloc::{AllocError, Allocator, Global};
+use std::alloc::Layout;
+use std::{cmp, mem};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+/// An allocator backed by a pool, see the module documentation.
+pub struct PoolAllocator<T: Reset, E: Extra = u8> {
+    pool: Mutex<Pool<T, E>>,
+    extra: usize, // Bytes in each block
+    align: usize, // Alignment of each block
+}
+
+impl<T: Reset, E: Extra> PoolAllocator<T, E> {
+    /// Returns an allocator handing out the extra storage of `pool`.
+    ///
+    /// Once the pool is out of entries, allocating fails, so it should be
+    /// built to grow, see `OnEmpty::Grow` and `OnEmpty::Transient`. The pool
+    /// must not be a child pool, see `Builder::parent`.
+    pub fn new(pool: Pool<T, E>) -> PoolAllocator<T, E> {
+        // The extra bytes of all entries start at the same offset into
+        // blocks of `align` bytes
+        let offset = pool.inner.lead + mem::size_of::<Entry<T>>();
+
+        PoolAllocator {
+            extra: pool.inner.extra(),
+            align: cmp::min(pool.inner.align, 1 << offset.trailing_zeros()),
+            pool: Mutex::new(pool),
+        }
+    }
+
+    /// Returns the size of the blocks handed out from the pool.
+    pub fn block_size(&self) -> usize {
+        self.extra
+    }
+
+    /// Returns the pool, once no memory is allocated from it.
+    pub fn into_inner(self) -> Pool<T, E> {
+        self.pool.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Whether the layout is served by the pool, the same on allocation and
+    // deallocation
+    fn fits(&self, layout: Layout) -> bool {
+        layout.size() != 0 && layout.size() <= self.extra && layout.align() <= self.align
+    }
+}
+
+unsafe impl<T: Reset, E: Extra> Allocator for PoolAllocator<T, E> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.fits(layout) {
+            return Global.allocate(layout);
+        }
+
+        let checkout = self.pool.lock().unwrap_or_else(|e| e.into_inner())
+            .try_checkout()
+            .ok_or(AllocError)?;
+
+        // Checked out until deallocated
+        let block = checkout.extra_ptr() as *mut u8;
+        checkout.into_raw();
+
+        Ok(NonNull::slice_from_raw_parts(unsafe { NonNull::new_unchecked(block) }, self.extra))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if !self.fits(layout) {
+            return Global.deallocate(ptr, layout);
+        }
+
+        // The extra storage follows the entry
+        let entry = ptr.as_ptr().sub(mem::size_of::<Entry<T>>());
+        drop(Checkout::<T, E>::from_raw(entry as *mut T));
+    }
+}
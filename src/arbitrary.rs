@@ -0,0 +1,197 @@
+//! Property testing support, built on `proptest`.
+//!
+//! `PoolConfig` generates pool configurations, covering the capacity, the
+//! extra storage and its alignment, segment sizes, tiers, idle order and
+//! what happens when the pool is empty. `Op` generates checkouts and
+//! checkins, and `Model` tells which checkouts should succeed, so that a
+//! sequence of operations can be checked against a pool built from the
+//! configuration. Everything shrinks towards small pools and short
+//! sequences.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate proptest;
+//! extern crate pool;
+//!
+//! use pool::Dirty;
+//! use pool::arbitrary::{Model, Op, PoolConfig};
+//!
+//! # fn main() {
+//! proptest!(|(config: PoolConfig, ops: Vec<Op>)| {
+//!     let mut pool = config.builder().build(|| Dirty(0u8));
+//!     let mut model = Model::new(&config);
+//!     let mut held = vec![];
+//!
+//!     for op in ops {
+//!         match op {
+//!             Op::Checkout => {
+//!                 let checkout = pool.try_checkout();
+//!                 prop_assert_eq!(model.checkout(), checkout.is_some());
+//!                 held.extend(checkout);
+//!             }
+//!             Op::Checkin(idx) if !held.is_empty() => {
+//!                 held.swap_remove(idx % held.len());
+//!                 model.checkin();
+//!             }
+//!             Op::Checkin(_) => {}
+//!         }
+//!     }
+//! });
+//! # }
+//! ```
+
+use {Builder, IdleOrder, OnEmpty, Reset};
+use proptest::prelude::*;
+
+/// A pool configuration, generated by `Arbitrary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Initial number of entries, see `Builder::capacity`
+    pub capacity: usize,
+    /// Extra bytes per entry, see `Builder::extra`
+    pub extra: usize,
+    /// Alignment of each entry, see `Builder::align`
+    pub align: usize,
+    /// Alignment of the extra bytes, see `Builder::align_extra`
+    pub extra_align: usize,
+    /// Max segment size in bytes, see `Builder::segment_size`
+    pub segment_size: Option<usize>,
+    /// Size of the hot tier, see `Builder::hot_capacity`
+    pub hot_capacity: Option<usize>,
+    /// See `Builder::idle_order`
+    pub idle_order: IdleOrder,
+    /// See `Builder::on_empty`
+    pub on_empty: OnEmpty,
+}
+
+impl PoolConfig {
+    /// Returns a builder set up with the configuration.
+    pub fn builder<T: Reset>(&self) -> Builder<T> {
+        let mut builder = Builder::new()
+            .capacity(self.capacity)
+            .extra(self.extra)
+            .align(self.align)
+            .align_extra(self.extra_align)
+            .idle_order(self.idle_order)
+            .on_empty(self.on_empty);
+
+        if let Some(size) = self.segment_size {
+            builder = builder.segment_size(size);
+        }
+
+        if let Some(count) = self.hot_capacity {
+            builder = builder.hot_capacity(count);
+        }
+
+        builder
+    }
+}
+
+impl Arbitrary for PoolConfig {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<PoolConfig>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<PoolConfig> {
+        let on_empty = prop_oneof![
+            Just(OnEmpty::Fail),
+            Just(OnEmpty::Block),
+            (0..128usize).prop_map(|max| OnEmpty::Grow { max }),
+            Just(OnEmpty::Transient),
+        ];
+
+        (
+            0..64usize,
+            0..512usize,
+            0..8u32,
+            0..13u32,
+            proptest::option::of(1..16_384usize),
+            proptest::option::of(0..16usize),
+            prop_oneof![Just(IdleOrder::Mru), Just(IdleOrder::Lru)],
+            on_empty,
+        ).prop_map(|(capacity, extra, align, extra_align, segment_size, hot_capacity, idle_order, on_empty)| {
+            PoolConfig {
+                capacity,
+                extra,
+                align: 1 << align,
+                extra_align: 1 << extra_align,
+                segment_size,
+                hot_capacity,
+                idle_order,
+                on_empty,
+            }
+        }).boxed()
+    }
+}
+
+/// An operation on a pool, generated by `Arbitrary`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Check out a value with `Pool::try_checkout`
+    Checkout,
+    /// Check in one of the held values, picked by the index modulo their
+    /// number
+    Checkin(usize),
+}
+
+impl Arbitrary for Op {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Op>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<Op> {
+        prop_oneof![
+            Just(Op::Checkout),
+            any::<usize>().prop_map(Op::Checkin),
+        ].boxed()
+    }
+}
+
+/// A model of which checkouts of a pool succeed, see the module
+/// documentation.
+#[derive(Debug, Clone)]
+pub struct Model {
+    capacity: usize,
+    on_empty: OnEmpty,
+    held: usize,
+}
+
+impl Model {
+    /// Returns a model of a new pool built from `config`.
+    pub fn new(config: &PoolConfig) -> Model {
+        Model {
+            capacity: config.capacity,
+            on_empty: config.on_empty,
+            held: 0,
+        }
+    }
+
+    /// Returns whether `Pool::try_checkout` succeeds, and records the
+    /// checkout if it does.
+    pub fn checkout(&mut self) -> bool {
+        let ok = match self.on_empty {
+            OnEmpty::Fail | OnEmpty::Block => self.held < self.capacity,
+            OnEmpty::Grow { max } => self.held < self.capacity.max(max),
+            OnEmpty::Transient => true,
+        };
+
+        if ok {
+            self.held += 1;
+        }
+
+        ok
+    }
+
+    /// Records a checkin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if nothing is checked out.
+    pub fn checkin(&mut self) {
+        assert!(self.held > 0, "nothing to check in");
+        self.held -= 1;
+    }
+
+    /// Returns the number of values checked out.
+    pub fn held(&self) -> usize {
+        self.held
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::{cmp, mem};
+use sync::{AtomicUsize, Ordering};
+use reset::{Clean, Dirty};
+
+/// Reports the heap memory owned by a value, for pools capped by bytes, see
+/// `Builder::byte_budget`.
+///
+/// The implementations for the stdlib collections are shallow: they report
+/// the buffer of the collection, not what its elements own in turn. Types
+/// holding nested allocations implement it themselves.
+///
+/// ```
+/// use pool::{HeapSize, Reset};
+///
+/// struct Frame {
+///     pixels: Vec<u32>,
+///     label: String,
+/// }
+///
+/// impl HeapSize for Frame {
+///     fn heap_size(&self) -> usize {
+///         self.pixels.heap_size() + self.label.heap_size()
+///     }
+/// }
+/// ```
+pub trait HeapSize {
+    /// Returns the number of heap bytes owned by the value.
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! no_heap {
+    ($($t:ty),*) => {
+        $(
+            impl HeapSize for $t {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+no_heap!(bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl<T: HeapSize, const N: usize> HeapSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(T::heap_size).sum()
+    }
+}
+
+impl<T> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>()
+    }
+}
+
+impl<T> HeapSize for VecDeque<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>()
+    }
+}
+
+impl<T> HeapSize for Box<[T]> {
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(&**self)
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Dirty<T> {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Clean<T> {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size()
+    }
+}
+
+// Measures the heap bytes owned by a value, see `Builder::byte_budget`
+pub(crate) type HeapSizeFn<T> = fn(&T) -> usize;
+
+// Caps the bytes held by a pool: the entries, and the heap memory owned by
+// their values as last measured
+pub(crate) struct Budget<T> {
+    bytes: usize,
+    heap_size: HeapSizeFn<T>,
+    heap: AtomicUsize,     // Heap bytes of the values, as last measured
+    capacity: AtomicUsize, // Mirrors the capacity, readable from any thread
+}
+
+impl<T> Budget<T> {
+    pub fn new(bytes: usize, heap_size: HeapSizeFn<T>) -> Budget<T> {
+        Budget {
+            bytes,
+            heap_size,
+            heap: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
+        }
+    }
+
+    // Must only be called by the owner of the pool.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    // Measures a value, or its absence, in place of the `old` measurement.
+    // Returns the new one.
+    pub fn measure(&self, value: Option<&T>, old: usize) -> usize {
+        let new = value.map_or(0, self.heap_size);
+
+        if new > old {
+            self.heap.fetch_add(new - old, Ordering::Relaxed);
+        } else if new < old {
+            self.heap.fetch_sub(old - new, Ordering::Relaxed);
+        }
+
+        new
+    }
+
+    // Bytes counted against the budget, given the size of an entry
+    pub fn used(&self, entry_size: usize) -> usize {
+        let entries = self.capacity.load(Ordering::Relaxed).saturating_mul(entry_size);
+        entries.saturating_add(self.heap.load(Ordering::Relaxed))
+    }
+
+    pub fn is_over(&self, entry_size: usize) -> bool {
+        self.used(entry_size) > self.bytes
+    }
+
+    // Number of entries that can be added without going over the budget,
+    // assuming their values end up owning as much heap memory as the
+    // current ones do on average
+    pub fn room(&self, entry_size: usize) -> usize {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let heap = self.heap.load(Ordering::Relaxed) / cmp::max(1, capacity);
+
+        self.bytes.saturating_sub(self.used(entry_size)) / (entry_size + heap)
+    }
+}
@@ -0,0 +1,1077 @@
+use {Exhaustion, Extra, HeapSize, Pool, PoolInner, Reset, ResetFn, ResetWith, Wipe};
+use budget::HeapSizeFn;
+use weighted::WeightFn;
+use extra;
+use events::ExhaustionFn;
+use watermark::WatermarkFn;
+use std::{fmt, mem};
+use std::marker::PhantomData;
+use std::time::Duration;
+use sync::Arc;
+
+/// Configures and creates a `Pool`.
+///
+/// ```
+/// use pool::{Pool, Dirty};
+///
+/// let mut pool = Pool::builder()
+///     .capacity(16)
+///     .extra(1_024)
+///     .build(|| Dirty(0u32));
+///
+/// let val = pool.checkout();
+/// assert!(val.extra().len() >= 1_024);
+/// ```
+pub struct Builder<T, E = u8> {
+    pub(crate) count: usize,
+    pub(crate) extra: usize,
+    pub(crate) align: usize,
+    pub(crate) extra_align: usize,
+    pub(crate) regions: Vec<(&'static str, usize, usize)>,
+    pub(crate) prefetch_extra: usize,
+    pub(crate) on_empty: OnEmpty,
+    pub(crate) high_reserve: usize,
+    pub(crate) segment_size: Option<usize>,
+    pub(crate) hot_capacity: Option<usize>,
+    pub(crate) idle_order: IdleOrder,
+    pub(crate) parent: Option<Arc<PoolInner<T>>>,
+    pub(crate) reset: Option<ResetFn<T>>,
+    pub(crate) wipe: Option<Wipe<T>>,
+    pub(crate) weight: Option<WeightFn<T>>,
+    pub(crate) lock_memory: bool,
+    pub(crate) track_idle: bool,
+    pub(crate) huge_pages: bool,
+    pub(crate) page_aligned: bool,
+    pub(crate) track_extra_usage: bool,
+    pub(crate) track_hold_time: bool,
+    pub(crate) low_watermark: Option<(f64, WatermarkFn)>,
+    pub(crate) auto_grow: Option<(f64, Duration, usize)>,
+    pub(crate) auto_shrink: Option<(f64, Duration, usize)>,
+    pub(crate) adaptive: Option<(f64, usize, usize, Duration)>,
+    pub(crate) name: &'static str,
+    pub(crate) on_exhausted: Option<(Duration, ExhaustionFn)>,
+    pub(crate) lease: Option<Duration>,
+    pub(crate) byte_budget: Option<(usize, HeapSizeFn<T>)>,
+    _p: PhantomData<fn() -> (T, E)>,
+}
+
+impl<T: Reset> Builder<T> {
+    /// Returns a new builder with a capacity of zero, no extra bytes and the
+    /// natural alignment of the pooled value.
+    pub fn new() -> Builder<T> {
+        Builder {
+            count: 0,
+            extra: 0,
+            align: 1,
+            extra_align: 1,
+            regions: vec![],
+            prefetch_extra: 0,
+            on_empty: OnEmpty::Block,
+            high_reserve: 0,
+            segment_size: None,
+            hot_capacity: None,
+            idle_order: IdleOrder::Mru,
+            parent: None,
+            reset: None,
+            wipe: None,
+            weight: None,
+            lock_memory: false,
+            track_idle: false,
+            huge_pages: false,
+            page_aligned: false,
+            track_extra_usage: false,
+            track_hold_time: false,
+            low_watermark: None,
+            auto_grow: None,
+            auto_shrink: None,
+            adaptive: None,
+            name: "pool",
+            on_exhausted: None,
+            lease: None,
+            byte_budget: None,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T: Reset, E: Extra> Builder<T, E> {
+    /// Sets the number of entries in the pool.
+    pub fn capacity(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Sets the number of extra bytes available to each entry, or the number
+    /// of extra values for a pool built with `extra_of`.
+    pub fn extra(mut self, extra: usize) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// Makes the extra storage of each entry `len` values of type `U` instead
+    /// of bytes.
+    ///
+    /// The values start out zeroed and are accessed as a `&[U]` through
+    /// `Checkout::extra`, at least `len` of them. Like with bytes, space left
+    /// over by alignment is made available as well.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .extra_of::<u64>(8)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let mut val = pool.checkout();
+    /// assert!(val.extra().len() >= 8);
+    ///
+    /// val.extra_mut()[7] = u64::MAX;
+    /// ```
+    ///
+    /// Zero sized types, such as empty arrays, are rejected at compile time:
+    ///
+    /// ```compile_fail
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let pool = Pool::builder()
+    ///     .extra_of::<[u64; 0]>(4)
+    ///     .build(|| Dirty(0));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a parent pool has been set, the parent must be set after
+    /// choosing the type of the extra storage.
+    pub fn extra_of<U: Extra>(self, len: usize) -> Builder<T, U> {
+        const { assert!(mem::size_of::<U>() != 0, "extra storage must not be zero sized") }
+        assert!(self.parent.is_none(), "parent must be set after `extra_of`");
+
+        Builder {
+            count: self.count,
+            extra: len,
+            align: self.align,
+            extra_align: self.extra_align,
+            regions: self.regions,
+            prefetch_extra: self.prefetch_extra,
+            on_empty: self.on_empty,
+            high_reserve: self.high_reserve,
+            segment_size: self.segment_size,
+            hot_capacity: self.hot_capacity,
+            idle_order: self.idle_order,
+            parent: None,
+            reset: self.reset,
+            wipe: self.wipe,
+            weight: self.weight,
+            lock_memory: self.lock_memory,
+            track_idle: self.track_idle,
+            huge_pages: self.huge_pages,
+            page_aligned: self.page_aligned,
+            track_extra_usage: self.track_extra_usage,
+            track_hold_time: self.track_hold_time,
+            low_watermark: self.low_watermark,
+            auto_grow: self.auto_grow,
+            auto_shrink: self.auto_shrink,
+            adaptive: self.adaptive,
+            name: self.name,
+            on_exhausted: self.on_exhausted,
+            lease: self.lease,
+            byte_budget: self.byte_budget,
+            _p: PhantomData,
+        }
+    }
+
+    /// Sets the minimum alignment of each entry.
+    ///
+    /// Every entry starts on a multiple of `align` and occupies a multiple of
+    /// `align` bytes, so `align(64)` keeps values that are used concurrently
+    /// from different threads off each other's cache lines. Any space left
+    /// over in the aligned block is made available as extra bytes.
+    ///
+    /// Aligning the extra storage, with `align_extra` or `region`, takes
+    /// precedence: all entries are then shifted by the same few bytes so
+    /// that their extra storage lines up, and only occupy a multiple of
+    /// `align` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn align(mut self, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        self.align = align;
+        self
+    }
+
+    /// Sets the minimum alignment of the extra storage of each entry.
+    ///
+    /// For example, `align_extra(64)` starts the extra storage on a cache
+    /// line boundary, so that AVX-512 loads and stores or cache line granular
+    /// DMA can target it directly. Entries occupy a multiple of as much.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .extra(256)
+    ///     .align_extra(64)
+    ///     .build(|| Dirty(()));
+    ///
+    /// let val = pool.checkout();
+    /// assert_eq!(0, val.extra().as_ptr() as usize % 64);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn align_extra(mut self, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        self.extra_align = align;
+        self
+    }
+
+    /// Starts the extra storage of each entry on a page boundary, of at
+    /// least 4 KiB, as required by `O_DIRECT` file I/O and some DMA engines.
+    ///
+    /// Like `align_extra` with the page size, whichever is larger wins, and
+    /// regardless of the order the two are set in. The memory backing the
+    /// pool starts on a page boundary as well. Each entry takes up at least
+    /// a page on top of its extra storage, so this is meant for large extra
+    /// regions.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .extra(16 * 1024)
+    ///     .page_aligned()
+    ///     .build(|| Dirty(()));
+    ///
+    /// let buf = pool.checkout();
+    /// assert_eq!(0, buf.extra().as_ptr() as usize % 4096);
+    /// ```
+    pub fn page_aligned(mut self) -> Self {
+        self.page_aligned = true;
+        self
+    }
+
+    /// Adds a region of `len` bytes, aligned to `align`, to the extra storage
+    /// of each entry, to be accessed by name or index with
+    /// `Checkout::region` and `Checkout::region_at`.
+    ///
+    /// Regions are laid out one after the other, in the order they are
+    /// added, at the start of the extra storage. The extra storage is made
+    /// large enough to hold all of them.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .region("header", 256, 8)
+    ///     .region("payload", 64 * 1024, 4096)
+    ///     .build(|| Dirty(()));
+    ///
+    /// let mut val = pool.checkout();
+    /// assert_eq!(256, val.region("header").unwrap().len());
+    /// assert_eq!(0, val.region("payload").unwrap().as_ptr() as usize % 4096);
+    ///
+    /// for (name, bytes) in val.regions_mut() {
+    ///     bytes[0] = name.len() as u8;
+    /// }
+    ///
+    /// assert_eq!(7, val.region_at(1).unwrap()[0]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two or a region with the same name
+    /// has already been added.
+    pub fn region(mut self, name: &'static str, len: usize, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        assert!(self.regions.iter().all(|&(other, _, _)| other != name), "duplicate region name");
+        self.regions.push((name, len, align));
+        self
+    }
+
+    /// Sets the max byte size of each block of memory backing the pool.
+    ///
+    /// The entries are not allocated as one contiguous block but in fixed
+    /// size segments. A segment holds the initial capacity of the pool unless
+    /// that exceeds `size` bytes, in which case as many segments as needed are
+    /// allocated. Growing the pool adds more segments of the same size, so
+    /// existing entries never move. A segment always holds at least one entry.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn segment_size(mut self, size: usize) -> Self {
+        self.segment_size = Some(size);
+        self
+    }
+
+    /// Sets the number of cache lines at the start of the extra bytes to
+    /// prefetch when a value is checked out.
+    ///
+    /// The value itself is always prefetched. Prefetching the extra bytes as
+    /// well hides the first cache misses when they are accessed right after
+    /// checkout, as is typical for buffers. Defaults to zero.
+    pub fn prefetch_extra(mut self, lines: usize) -> Self {
+        self.prefetch_extra = lines;
+        self
+    }
+
+    /// Sets what a checkout does when every entry is in use, see `OnEmpty`.
+    ///
+    /// Defaults to `OnEmpty::Block`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty, OnEmpty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .on_empty(OnEmpty::Grow { max: 2 })
+    ///     .build(|| Dirty(0));
+    ///
+    /// let a = pool.checkout();
+    /// let b = pool.checkout();
+    /// assert!(pool.try_checkout().is_none());
+    /// ```
+    pub fn on_empty(mut self, on_empty: OnEmpty) -> Self {
+        self.on_empty = on_empty;
+        self
+    }
+
+    /// Holds back `count` entries for high priority checkouts, see
+    /// `Priority`.
+    ///
+    /// Normal checkouts treat the pool as empty once no more than `count`
+    /// entries are available, and `Builder::on_empty` applies to them from
+    /// there. Entries of a parent pool are not held back. Defaults to zero.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty, Priority};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .reserve_high_priority(1)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let bulk: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    /// assert!(pool.try_checkout().is_none());
+    ///
+    /// let health_check = pool.checkout_priority(Priority::High);
+    /// ```
+    pub fn reserve_high_priority(mut self, count: usize) -> Self {
+        self.high_reserve = count;
+        self
+    }
+
+    /// Never fail a checkout because the pool is depleted.
+    ///
+    /// Shorthand for `on_empty(OnEmpty::Grow { max: usize::MAX })`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .unbounded()
+    ///     .build(|| Dirty(0));
+    ///
+    /// let a = pool.checkout();
+    /// let b = pool.checkout();
+    /// ```
+    pub fn unbounded(self) -> Self {
+        self.on_empty(OnEmpty::Grow { max: usize::MAX })
+    }
+
+    /// Splits the idle entries into a hot and a cold tier, keeping at most
+    /// `count` entries in the hot tier.
+    ///
+    /// Checkouts are served from the hot tier first, so under light load the
+    /// same few entries are reused and stay in cache. Checkins go to the hot
+    /// tier while it has room and to the cold tier otherwise. Entries start
+    /// out cold, and `Pool::demote_idle` moves entries that have not been
+    /// used for a while back to the cold tier.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(64)
+    ///     .hot_capacity(4)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    /// ```
+    pub fn hot_capacity(mut self, count: usize) -> Self {
+        self.hot_capacity = Some(count);
+        self
+    }
+
+    /// Sets which idle entry a checkout reuses first, see `IdleOrder`.
+    ///
+    /// Defaults to `IdleOrder::Mru`. Pools with a hot tier, see
+    /// `hot_capacity`, always reuse the most recently used entry first.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty, IdleOrder};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(2)
+    ///     .idle_order(IdleOrder::Lru)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let mut a = pool.checkout();
+    /// let mut b = pool.checkout();
+    /// **a = 1;
+    /// **b = 2;
+    ///
+    /// drop(a);
+    /// drop(b);
+    ///
+    /// assert_eq!(1, **pool.checkout());
+    /// ```
+    pub fn idle_order(mut self, order: IdleOrder) -> Self {
+        self.idle_order = order;
+        self
+    }
+
+    /// Falls back to checking out from `parent` when all entries of the new
+    /// pool are in use.
+    ///
+    /// Values checked out from the parent are returned to the parent, where
+    /// they are available to its other children again. This way each worker
+    /// can keep a small pool of its own, backed by a shared reserve, without
+    /// sizing every pool for the worst case. Parents can have parents of
+    /// their own, they are tried in order.
+    ///
+    /// The parent is kept alive for as long as the new pool is, so
+    /// `Pool::try_into_values` on the parent fails until its children are
+    /// gone.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let reserve = Pool::with_capacity(1, 0, || Dirty("reserve"));
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .parent(&reserve)
+    ///     .build(|| Dirty("local"));
+    ///
+    /// let a = pool.checkout();
+    /// let b = pool.checkout();
+    ///
+    /// assert_eq!("local", **a);
+    /// assert_eq!("reserve", **b);
+    /// assert!(pool.try_checkout().is_none());
+    /// ```
+    pub fn parent(mut self, parent: &Pool<T, E>) -> Self {
+        self.parent = Some(parent.inner.clone());
+        self
+    }
+
+    /// Resets values on checkout with `ResetWith::reset_with`, passing `ctx`,
+    /// instead of with `Reset::reset`.
+    ///
+    /// The context is shared by all values of the pool. Use interior
+    /// mutability, such as an atomic, for parts of it that change at runtime.
+    pub fn reset_context<C>(mut self, ctx: C) -> Self
+            where T: ResetWith<C>,
+                  C: Send + Sync + 'static {
+        self.reset = Some(Arc::new(move |val: &mut T| val.reset_with(&ctx)));
+        self
+    }
+
+    /// Zeroizes values and their extra bytes when they are checked back in.
+    ///
+    /// Use this for values that transiently hold secrets, such as key
+    /// material, so they don't linger in memory while idle. The wipes use
+    /// `zeroize`, which guarantees they are not optimized away.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .extra(32)
+    ///     .zeroize_on_checkin()
+    ///     .build(|| Dirty([0u8; 32]));
+    ///
+    /// let mut key = pool.checkout();
+    /// key.0 = [0xff; 32];
+    /// key.extra_mut()[0] = 0xff;
+    /// drop(key);
+    ///
+    /// let key = pool.checkout();
+    /// assert_eq!([0; 32], key.0);
+    /// assert_eq!(0, key.extra()[0]);
+    /// ```
+    #[cfg(feature = "secure")]
+    pub fn zeroize_on_checkin(mut self) -> Self
+            where T: ::zeroize::Zeroize {
+        use zeroize::Zeroize;
+
+        fn wipe<T: Zeroize>(val: Option<&mut T>, extra: &mut [u8]) {
+            if let Some(val) = val {
+                val.zeroize();
+            }
+
+            extra.zeroize();
+        }
+
+        self.wipe = Some(wipe::<T>);
+        self
+    }
+
+    /// Weighs each value with `f`, for example by the capacity of a buffer,
+    /// so that `Pool::checkout_weighted` can check out enough entries to
+    /// cover a requested weight.
+    ///
+    /// Values are weighed when checked out, so the weight may change as the
+    /// value does. Without a weight function, each entry weighs one.
+    pub fn weight<F>(mut self, f: F) -> Self
+            where F: Fn(&T) -> usize + Send + Sync + 'static {
+        self.weight = Some(Arc::new(f));
+        self
+    }
+
+    /// Records when each entry is checked in, so that idle entries can be
+    /// told apart by how long they have been idle, and when each value is
+    /// created.
+    ///
+    /// See `Checkout::created_at`, `Checkout::last_used` and
+    /// `Pool::for_each_idle`. Required by `Pool::release_idle_memory`. Pools with a hot tier, see
+    /// `hot_capacity`, always track this.
+    pub fn track_idle(mut self) -> Self {
+        self.track_idle = true;
+        self
+    }
+
+    /// Tracks how many of each entry's extra bytes are used, see
+    /// `Pool::extra_usage`.
+    ///
+    /// On every checkin the extra bytes beyond the entry's high-water mark
+    /// are scanned for non-zero bytes, so this is meant for sizing `extra`
+    /// rather than for production use.
+    pub fn track_extra_usage(mut self) -> Self {
+        self.track_extra_usage = true;
+        self
+    }
+
+    /// Records how long each value is held checked out, see
+    /// `Pool::hold_times`.
+    ///
+    /// Telling a pool that is too small from holders that are too slow takes
+    /// this. It costs reading the clock on every checkout and checkin.
+    pub fn track_hold_time(mut self) -> Self {
+        self.track_hold_time = true;
+        self
+    }
+
+    /// Calls `f` when the fraction of entries available for checkout crosses
+    /// `fraction`, in either direction.
+    ///
+    /// `f` is called with `true` when availability drops below `fraction`
+    /// and with `false` when it gets back to or above it. This lets callers
+    /// shed load before the pool is actually empty. The function is called
+    /// on the thread that checked out or checked in the value that crossed
+    /// the watermark, so it should be quick.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// let shedding = Arc::new(AtomicBool::new(false));
+    /// let shed = shedding.clone();
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(10)
+    ///     .low_watermark(0.1, move |below| shed.store(below, Ordering::Relaxed))
+    ///     .build(|| Dirty(0));
+    ///
+    /// let vals: Vec<_> = (0..10).map(|_| pool.checkout()).collect();
+    /// assert!(shedding.load(Ordering::Relaxed));
+    ///
+    /// drop(vals);
+    /// assert!(!shedding.load(Ordering::Relaxed));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1.
+    pub fn low_watermark<F>(mut self, fraction: f64, f: F) -> Self
+            where F: Fn(bool) + Send + Sync + 'static {
+        assert!((0.0..=1.0).contains(&fraction), "watermark must be between 0 and 1");
+        self.low_watermark = Some((fraction, Arc::new(f)));
+        self
+    }
+
+    /// Calls `f` when a checkout finds the pool exhausted, at most once per
+    /// `interval`.
+    ///
+    /// `f` is passed the state of the pool at the time. It is called on the
+    /// thread of the failed checkout, including polls that have to wait, but
+    /// not blocking checkouts. Useful to trip a circuit breaker in the layer
+    /// above the pool.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// let seen = Arc::new(Mutex::new(vec![]));
+    /// let s = seen.clone();
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .on_exhausted(Duration::from_secs(1), move |exhaustion| {
+    ///         s.lock().unwrap().push(*exhaustion);
+    ///     })
+    ///     .build(|| Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    ///
+    /// for _ in 0..10 {
+    ///     assert!(pool.try_checkout().is_none());
+    /// }
+    ///
+    /// let seen = seen.lock().unwrap();
+    /// assert_eq!(1, seen.len());
+    /// assert_eq!(1, seen[0].in_use);
+    /// ```
+    pub fn on_exhausted<F>(mut self, interval: Duration, f: F) -> Self
+            where F: Fn(&Exhaustion) + Send + Sync + 'static {
+        self.on_exhausted = Some((interval, Arc::new(f)));
+        self
+    }
+
+    /// Leases every checkout for `timeout`, so that a value held by a hung
+    /// task is not lost to the pool for good.
+    ///
+    /// Once the pool is out of entries, checkouts revoke the leases that
+    /// expired and add an entry in place of each, with a value created by
+    /// the initializer. The holder of a revoked checkout keeps access to the
+    /// value until it drops it, see `Checkout::is_revoked`, after which the
+    /// value is dropped instead of reused and the entry is retired, bringing
+    /// the capacity back down. Retired entries are taken back in place of
+    /// the next revoked leases, before the pool grows again.
+    /// Blocked checkouts look for expired leases at least once per `timeout`.
+    /// Holders that need longer can extend their lease with
+    /// `Checkout::renew`.
+    ///
+    /// Transient values are not leased. Pools without an initializer ignore
+    /// this setting.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .lease(Duration::from_millis(10))
+    ///     .build(|| Dirty(0));
+    ///
+    /// let hung = pool.checkout();
+    /// assert!(pool.try_checkout().is_none());
+    ///
+    /// thread::sleep(Duration::from_millis(20));
+    ///
+    /// let val = pool.checkout();
+    /// assert!(hung.is_revoked());
+    /// assert_eq!(2, pool.capacity());
+    /// ```
+    pub fn lease(mut self, timeout: Duration) -> Self {
+        self.lease = Some(timeout);
+        self
+    }
+
+    /// Caps the memory held by the pool at `bytes`, counting its entries
+    /// along with the heap memory owned by their values, as reported by
+    /// `HeapSize`.
+    ///
+    /// Values are measured when created and when checked back in. A value
+    /// checked in while the pool is over budget is dropped rather than kept,
+    /// and its entry is filled again on checkout. Growing, through
+    /// `OnEmpty::Grow`, `auto_grow` or `adaptive`, stops short of the budget,
+    /// assuming new values own about as much as the current ones on average.
+    /// The initial capacity and values added with `extend` are not capped,
+    /// see `Pool::byte_usage` for where the pool stands.
+    ///
+    /// Pools without an initializer ignore this setting.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(2)
+    ///     .byte_budget(64 * 1024)
+    ///     .build(|| Dirty(Vec::<u8>::new()));
+    ///
+    /// let mut buf = pool.checkout();
+    /// buf.reserve(1024 * 1024);
+    /// drop(buf);
+    ///
+    /// // Too big to keep around
+    /// assert_eq!(0, pool.checkout().capacity());
+    /// ```
+    pub fn byte_budget(mut self, bytes: usize) -> Self
+            where T: HeapSize {
+        self.byte_budget = Some((bytes, T::heap_size));
+        self
+    }
+
+    /// Grows the pool ahead of exhaustion once the fraction of entries
+    /// available for checkout has stayed below `fraction` for `grace`.
+    ///
+    /// The pool grows by a segment, see `segment_size`, up to `max` entries
+    /// in total. The new values are created right away with the initializer,
+    /// on the checkout that notices the grace period is over. Availability is
+    /// only looked at on checkout.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .segment_size(1)
+    ///     .auto_grow(0.25, Duration::from_secs(0), 8)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+    ///
+    /// // The last checkout left the pool empty, so this one grows it
+    /// let val = pool.try_checkout().unwrap();
+    /// assert_eq!(5, pool.capacity());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1.
+    pub fn auto_grow(mut self, fraction: f64, grace: Duration, max: usize) -> Self {
+        assert!((0.0..=1.0).contains(&fraction), "watermark must be between 0 and 1");
+        self.auto_grow = Some((fraction, grace, max));
+        self
+    }
+
+    /// Retires surplus values once the fraction of entries available for
+    /// checkout has stayed above `fraction` for `period`.
+    ///
+    /// Idle values are dropped, least recently used first, until the pool
+    /// holds no more than `floor` values, counting those checked out. The
+    /// order goes by when values were checked in if `track_idle` is set, and
+    /// is approximate otherwise. Their entries stay in the pool and
+    /// are refilled with the initializer when checked out again, so this
+    /// gives back the memory owned by the values, not the pool's own. Like
+    /// `auto_grow`, availability is only looked at on checkout: use
+    /// `Pool::drain_idle` for pools that sit unused.
+    ///
+    /// ```
+    /// use pool::{Pool, PoolEvent, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(8)
+    ///     .auto_shrink(0.5, Duration::from_secs(0), 2)
+    ///     .build(|| Dirty(Vec::<u8>::with_capacity(1024)));
+    ///
+    /// let events = pool.events();
+    /// let _val = pool.checkout();
+    ///
+    /// // Only the checked out value and one idle value are kept
+    /// assert_eq!(Ok(PoolEvent::Shrank { by: 6 }), events.try_recv());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1.
+    pub fn auto_shrink(mut self, fraction: f64, period: Duration, floor: usize) -> Self {
+        assert!((0.0..=1.0).contains(&fraction), "watermark must be between 0 and 1");
+        self.auto_shrink = Some((fraction, period, floor));
+        self
+    }
+
+    /// Sizes the pool so that at most `target` of it is in use, adjusting
+    /// its size within `min` and `max` entries at the end of each
+    /// `interval`.
+    ///
+    /// The number of entries in use is sampled on every checkout, counting
+    /// the checkout itself, so checkouts that find the pool depleted make it
+    /// grow. At the end of an interval, the pool is sized for the peak of
+    /// that interval: it grows right away with values created by the
+    /// initializer, or retires idle values as with `auto_shrink`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .adaptive(0.5, 4, 64, Duration::from_secs(0))
+    ///     .build(|| Dirty(0));
+    ///
+    /// let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+    ///
+    /// // Grown to keep no more than half of the pool in use
+    /// let val = pool.checkout();
+    /// assert_eq!(10, pool.capacity());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target` is not above 0 and at most 1, or if `min` is
+    /// greater than `max`.
+    pub fn adaptive(mut self, target: f64, min: usize, max: usize, interval: Duration) -> Self {
+        assert!(target > 0.0 && target <= 1.0, "target must be above 0 and at most 1");
+        assert!(min <= max, "min must not be greater than max");
+        self.adaptive = Some((target, min, max, interval));
+        self
+    }
+
+    /// Names the pool, to tell it apart in log messages. Defaults to `pool`.
+    ///
+    /// With the `log` feature, the pool logs a warning when it is exhausted,
+    /// growing and shrinking at the info level, and retired and recycled
+    /// values at the debug level.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Asks the OS to back the pool's memory with huge pages, reducing TLB
+    /// misses for pools with large extra regions.
+    ///
+    /// This is only a hint, it uses transparent huge pages on Linux and does
+    /// nothing elsewhere. Combine it with `Pool::warm_up` to fault in the huge
+    /// pages up front.
+    pub fn huge_pages(mut self) -> Self {
+        self.huge_pages = true;
+        self
+    }
+
+    /// Locks the memory backing the pool into RAM, so that it is never
+    /// swapped to disk.
+    ///
+    /// This uses `mlock` on Unix and `VirtualLock` on Windows. Locking may
+    /// fail, for example because of the process's limit on locked memory, in
+    /// which case the pool works as usual with unlocked memory. Use
+    /// `Pool::is_memory_locked` to find out.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let pool = Pool::builder()
+    ///     .capacity(16)
+    ///     .extra(256)
+    ///     .lock_memory()
+    ///     .build(|| Dirty(()));
+    ///
+    /// if !pool.is_memory_locked() {
+    ///     println!("secrets may be swapped to disk");
+    /// }
+    /// ```
+    pub fn lock_memory(mut self) -> Self {
+        self.lock_memory = true;
+        self
+    }
+
+    /// Creates the pool, initializing each entry with the given function.
+    ///
+    /// The pool keeps the function around to re-initialize entries whose
+    /// value has been removed, see `Pool::drain_idle`.
+    pub fn build<F>(&self, init: F) -> Pool<T, E>
+            where F: Fn() -> T + Send + Sync + 'static {
+        Pool::build(self, Box::new(move |_, _| init()))
+    }
+
+    /// Creates the pool, initializing each entry with the given function.
+    ///
+    /// The function is called with the index of the slot being initialized,
+    /// in order from `0` to `capacity - 1`, so that each value can be
+    /// parameterized by its position in the pool.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .build_with(|idx| Dirty(format!("shard-{}", idx)));
+    ///
+    /// assert!(pool.checkout().starts_with("shard-"));
+    /// ```
+    pub fn build_with<F>(&self, init: F) -> Pool<T, E>
+            where F: Fn(usize) -> T + Send + Sync + 'static {
+        Pool::build(self, Box::new(move |idx, _| init(idx)))
+    }
+
+    /// Creates the pool, initializing each entry with the given function.
+    ///
+    /// Like `build_with`, but the function also receives the extra storage of
+    /// the entry being initialized, so the value can be set up to describe
+    /// its buffer right away.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .extra(64)
+    ///     .build_with_extra(|_, extra| {
+    ///         extra[0] = 0xff;
+    ///         Dirty(extra.len())
+    ///     });
+    ///
+    /// let val = pool.checkout();
+    /// assert_eq!(**val, val.extra().len());
+    /// assert_eq!(0xff, val.extra()[0]);
+    /// ```
+    pub fn build_with_extra<F>(&self, init: F) -> Pool<T, E>
+            where F: Fn(usize, &mut [E]) -> T + Send + Sync + 'static {
+        Pool::build(self, Box::new(move |idx, bytes| init(idx, extra::from_bytes_mut(bytes))))
+    }
+
+    /// Creates the pool, running the initializer on `threads` threads.
+    ///
+    /// Each thread initializes a contiguous range of entries in place. This
+    /// is useful when the values are expensive to create and the pool is
+    /// large. If the initializer panics, every value created so far is
+    /// dropped and the panic is propagated.
+    ///
+    /// Unlike `build`, the initializer may borrow from the caller, so the
+    /// pool cannot keep it. It is only used for the initial values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the builder is set up to create values later on, with
+    /// `on_empty` growing or creating transient values, `lease`, `auto_grow`,
+    /// `auto_shrink`, `adaptive` or `byte_budget`.
+    pub fn build_parallel<F>(&self, threads: usize, init: F) -> Pool<T, E>
+            where F: Fn() -> T + Sync,
+                  T: Send {
+        Pool::build_parallel(self, threads, move |_, _| init())
+    }
+
+    // Pools that don't keep their initializer cannot create values later on,
+    // reject the settings that would need to rather than ignore them
+    pub(crate) fn assert_eager(&self) {
+        let setting = match self.on_empty {
+            OnEmpty::Grow { .. } => Some("on_empty(OnEmpty::Grow)"),
+            OnEmpty::Transient => Some("on_empty(OnEmpty::Transient)"),
+            _ if self.lease.is_some() => Some("lease"),
+            _ if self.auto_grow.is_some() => Some("auto_grow"),
+            _ if self.auto_shrink.is_some() => Some("auto_shrink"),
+            _ if self.adaptive.is_some() => Some("adaptive"),
+            _ if self.byte_budget.is_some() => Some("byte_budget"),
+            _ => None,
+        };
+
+        if let Some(setting) = setting {
+            panic!("`{}` requires a pool that keeps its initializer, see `Builder::build`", setting);
+        }
+    }
+}
+
+/// What a checkout does when every entry of the pool, and of its parents, is
+/// in use. Set with `Builder::on_empty`.
+///
+/// Pools created without an initializer, such as with `Pool::from_vec`,
+/// cannot create values and always fail instead of growing or creating
+/// transient values. `Builder::build_parallel` rejects these settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnEmpty {
+    /// Fail the checkout, `Pool::checkout` panics and `Pool::try_checkout`
+    /// returns `None`.
+    Fail,
+    /// Wait until a value is checked back in, the default.
+    /// `Pool::try_checkout` never waits and returns `None` instead.
+    ///
+    /// The thread looks for a value for a short while before parking, and
+    /// is unparked by the checkin that hands it one. How long it looks
+    /// adapts to how often that pays off.
+    ///
+    /// Only values checked back in to this pool wake the checkout, not those
+    /// checked back in to a parent pool. Blocks forever if nothing else can
+    /// check a value in, for example on a target without threads.
+    #[default]
+    Block,
+    /// Grow the pool by a segment, see `Builder::segment_size`, up to `max`
+    /// entries in total, and fail once there.
+    ///
+    /// The values of the new entries are created with the initializer as they
+    /// are checked out. The new entries are kept and reused afterwards just
+    /// like the initial ones.
+    Grow {
+        /// Max number of entries of the pool
+        max: usize,
+    },
+    /// Create a value outside of the pool, which is dropped instead of
+    /// being reused when checked back in.
+    ///
+    /// Transient values have extra storage like any other, but their memory
+    /// is never locked and they cannot be parked. Initializers that take the
+    /// index of the slot are handed one past any valid index.
+    Transient,
+}
+
+/// Which idle entry a checkout reuses first, see `Builder::idle_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdleOrder {
+    /// Reuse the most recently checked in entry. The same few entries are
+    /// reused under light load, and stay in cache.
+    #[default]
+    Mru,
+    /// Reuse the least recently checked in entry. Every entry gets used
+    /// regularly, which suits resources that expire when left idle, such as
+    /// keep-alive connections.
+    ///
+    /// Checkins and checkouts stay O(1), amortized. The order is only
+    /// approximate while several threads check out from the pool at once,
+    /// through child pools.
+    Lru,
+}
+
+impl<T, E> fmt::Debug for Builder<T, E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Builder")
+            .field("count", &self.count)
+            .field("extra", &self.extra)
+            .field("align", &self.align)
+            .field("extra_align", &self.extra_align)
+            .field("regions", &self.regions)
+            .field("prefetch_extra", &self.prefetch_extra)
+            .field("on_empty", &self.on_empty)
+            .field("high_reserve", &self.high_reserve)
+            .field("segment_size", &self.segment_size)
+            .field("hot_capacity", &self.hot_capacity)
+            .field("idle_order", &self.idle_order)
+            .field("parent", &self.parent.is_some())
+            .field("reset_context", &self.reset.is_some())
+            .field("zeroize_on_checkin", &self.wipe.is_some())
+            .field("weight", &self.weight.is_some())
+            .field("lock_memory", &self.lock_memory)
+            .field("track_idle", &self.track_idle)
+            .field("huge_pages", &self.huge_pages)
+            .field("page_aligned", &self.page_aligned)
+            .field("track_extra_usage", &self.track_extra_usage)
+            .field("track_hold_time", &self.track_hold_time)
+            .field("low_watermark", &self.low_watermark.as_ref().map(|&(fraction, _)| fraction))
+            .field("auto_grow", &self.auto_grow)
+            .field("auto_shrink", &self.auto_shrink)
+            .field("adaptive", &self.adaptive)
+            .field("name", &self.name)
+            .field("on_exhausted", &self.on_exhausted.as_ref().map(|&(interval, _)| interval))
+            .field("lease", &self.lease)
+            .field("byte_budget", &self.byte_budget.map(|(bytes, _)| bytes))
+            .finish()
+    }
+}
+
+impl<T: Reset> Default for Builder<T> {
+    fn default() -> Builder<T> {
+        Builder::new()
+    }
+}
@@ -0,0 +1,230 @@
+use std::{mem, ptr, slice};
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// A bump allocator over a checkout's extra bytes, created by
+/// `Checkout::bump`.
+///
+/// Allocations are carved off the front of the extra bytes one after the
+/// other and are never freed individually. They all borrow the checkout, so
+/// they are gone by the time the value is checked back in, which makes the
+/// whole region available again. Each call to `Checkout::bump` starts over
+/// from the first extra byte.
+///
+/// Only `Copy` values can be allocated, as nothing is ever dropped.
+///
+/// ```
+/// use pool::{Pool, Dirty};
+///
+/// let mut pool = Pool::with_capacity(4, 1024, || Dirty(()));
+/// let mut val = pool.checkout();
+///
+/// let bump = val.bump();
+/// let ids = bump.alloc::<u64>(16).unwrap();
+/// let name = bump.alloc_copy(b"request").unwrap();
+///
+/// ids[0] = 42;
+/// assert_eq!(b"request", name);
+/// ```
+pub struct Bump<'a> {
+    ptr: *mut u8,
+    len: usize,
+    offset: Cell<usize>,
+    _p: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> Bump<'a> {
+    /// Creates a bump allocator handing out the given bytes.
+    pub fn new(bytes: &'a mut [u8]) -> Bump<'a> {
+        Bump {
+            ptr: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            offset: Cell::new(0),
+            _p: PhantomData,
+        }
+    }
+
+    /// Allocates `n` values of type `U` set to their default, or returns
+    /// `None` if there is not enough room left.
+    pub fn alloc<U: Copy + Default>(&self, n: usize) -> Option<&'a mut [U]> {
+        let ptr = self.reserve::<U>(n)?;
+
+        unsafe {
+            for i in 0..n {
+                ptr::write(ptr.add(i), U::default());
+            }
+
+            Some(slice::from_raw_parts_mut(ptr, n))
+        }
+    }
+
+    /// Allocates a copy of `src`, or returns `None` if there is not enough
+    /// room left.
+    pub fn alloc_copy<U: Copy>(&self, src: &[U]) -> Option<&'a mut [U]> {
+        let ptr = self.reserve::<U>(src.len())?;
+
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr, src.len());
+            Some(slice::from_raw_parts_mut(ptr, src.len()))
+        }
+    }
+
+    /// Number of bytes handed out so far, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// Number of bytes left, not accounting for alignment.
+    pub fn remaining(&self) -> usize {
+        self.len - self.offset.get()
+    }
+
+    // Reserves properly aligned room for `n` values of type `U`
+    pub(crate) fn reserve<U>(&self, n: usize) -> Option<*mut U> {
+        let size = mem::size_of::<U>().checked_mul(n)?;
+        let mask = mem::align_of::<U>() - 1;
+
+        // Align the address rather than the offset, the bytes themselves may
+        // be at any alignment
+        let addr = self.ptr as usize + self.offset.get();
+        let start = addr.checked_add(mask)? & !mask;
+        let start = start - self.ptr as usize;
+        let end = start.checked_add(size)?;
+
+        if end > self.len {
+            return None;
+        }
+
+        self.offset.set(end);
+        Some(unsafe { self.ptr.add(start) as *mut U })
+    }
+}
+
+/// An arena over a checkout's extra bytes that can hold values with
+/// destructors, created by `Checkout::arena`.
+///
+/// Works like `Bump`, but the values allocated are dropped, in reverse order,
+/// when the arena is dropped. The arena borrows the checkout, so that always
+/// happens before the value is checked back in. Values must outlive the
+/// checkout borrow, so they cannot point into the arena itself.
+///
+/// ```
+/// use pool::{Pool, Dirty};
+///
+/// let mut pool = Pool::with_capacity(4, 1024, || Dirty(()));
+/// let mut val = pool.checkout();
+///
+/// let arena = val.arena();
+/// let name = arena.alloc(String::from("request")).unwrap();
+/// name.push_str("-1");
+///
+/// assert_eq!("request-1", name);
+/// // `name` is dropped along with the arena
+/// ```
+///
+/// Values borrowing from one another could observe each other dropped, so
+/// that is rejected:
+///
+/// ```compile_fail
+/// use pool::{Pool, Dirty};
+///
+/// let mut pool = Pool::with_capacity(4, 1024, || Dirty(()));
+/// let mut val = pool.checkout();
+///
+/// let arena = val.arena();
+/// let name = arena.alloc(String::from("request")).unwrap();
+/// arena.alloc(vec![&*name]).unwrap();
+/// ```
+pub struct Arena<'a> {
+    bump: Bump<'a>,
+    last: Cell<*mut Dropper>, // Most recent value to drop
+    // Invariant, so that `'a` cannot be shortened to let values borrow from
+    // anything dropped before the arena
+    _p: PhantomData<Cell<&'a ()>>,
+}
+
+// Written in front of each value that needs dropping, linking to the previous
+// one
+struct Dropper {
+    drop: unsafe fn(*mut u8),
+    value: *mut u8,
+    prev: *mut Dropper,
+}
+
+impl<'a> Arena<'a> {
+    /// Creates an arena handing out the given bytes.
+    pub fn new(bytes: &'a mut [u8]) -> Arena<'a> {
+        Arena {
+            bump: Bump::new(bytes),
+            last: Cell::new(ptr::null_mut()),
+            _p: PhantomData,
+        }
+    }
+
+    /// Moves `value` into the arena, or hands it back if there is not enough
+    /// room left.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<U: 'a>(&self, value: U) -> Result<&mut U, U> {
+        unsafe fn drop_value<U>(ptr: *mut u8) {
+            ptr::drop_in_place(ptr as *mut U);
+        }
+
+        let dropper = if mem::needs_drop::<U>() {
+            match self.bump.reserve::<Dropper>(1) {
+                Some(dropper) => dropper,
+                None => return Err(value),
+            }
+        } else {
+            ptr::null_mut()
+        };
+
+        let ptr = match self.bump.reserve::<U>(1) {
+            Some(ptr) => ptr,
+            None => return Err(value),
+        };
+
+        unsafe {
+            ptr::write(ptr, value);
+
+            if !dropper.is_null() {
+                ptr::write(dropper, Dropper {
+                    drop: drop_value::<U>,
+                    value: ptr as *mut u8,
+                    prev: self.last.get(),
+                });
+
+                self.last.set(dropper);
+            }
+
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Number of bytes handed out so far, including bookkeeping and
+    /// alignment padding.
+    pub fn used(&self) -> usize {
+        self.bump.used()
+    }
+
+    /// Number of bytes left, not accounting for alignment.
+    pub fn remaining(&self) -> usize {
+        self.bump.remaining()
+    }
+}
+
+impl<'a> Drop for Arena<'a> {
+    fn drop(&mut self) {
+        let mut dropper = self.last.get();
+
+        while !dropper.is_null() {
+            unsafe {
+                let Dropper { drop, value, prev } = ptr::read(dropper);
+
+                // Unlink first, should the destructor panic the rest leak
+                self.last.set(prev);
+                drop(value);
+                dropper = prev;
+            }
+        }
+    }
+}
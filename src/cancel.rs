@@ -0,0 +1,109 @@
+use std::{fmt, mem};
+use std::sync::{Mutex, MutexGuard};
+use std::task::Waker;
+use sync::{Arc, AtomicUsize, Ordering};
+
+/// Releases threads blocked in `Pool::checkout_cancelable`, for example on
+/// shutdown.
+///
+/// Clones share the same state, cancelling one cancels all of them. Once
+/// cancelled, a token stays cancelled.
+///
+/// ```
+/// use pool::{CancelToken, Dirty, OnEmpty, Pool};
+/// use std::thread;
+///
+/// let mut pool = Pool::builder()
+///     .capacity(1)
+///     .on_empty(OnEmpty::Block)
+///     .build(|| Dirty(0));
+///
+/// let shutdown = CancelToken::new();
+/// let s = shutdown.clone();
+///
+/// let val = pool.checkout();
+///
+/// let handle = thread::spawn(move || {
+///     // Blocks until cancelled, the value is never checked back in
+///     assert!(pool.checkout_cancelable(&s).is_none());
+/// });
+///
+/// shutdown.cancel();
+/// handle.join().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    cancelled: AtomicUsize, // Non-zero once cancelled
+    wakers: Mutex<Vec<Waker>>, // Blocked threads to wake on cancel
+}
+
+impl CancelToken {
+    /// Returns a new token that is not cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken {
+            inner: Arc::new(Inner {
+                cancelled: AtomicUsize::new(0),
+                wakers: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// Cancels the token, releasing the threads blocked on it.
+    pub fn cancel(&self) {
+        let wakers = {
+            let mut wakers = self.lock();
+
+            self.inner.cancelled.store(1, Ordering::Relaxed);
+            mem::take(&mut *wakers)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Returns true if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed) != 0
+    }
+
+    // Leaves a waker to be woken on cancel. Returns false instead if the
+    // token is already cancelled.
+    pub(crate) fn register(&self, waker: &Waker) -> bool {
+        let mut wakers = self.lock();
+
+        if self.is_cancelled() {
+            return false;
+        }
+
+        wakers.push(waker.clone());
+        true
+    }
+
+    pub(crate) fn unregister(&self, waker: &Waker) {
+        self.lock().retain(|other| !other.will_wake(waker));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Waker>> {
+        // Poisoning is irrelevant, wakers are never left half updated
+        self.inner.wakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> CancelToken {
+        CancelToken::new()
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
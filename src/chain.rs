@@ -0,0 +1,209 @@
+use {Checkout, Extra, Pool, Reset};
+#[cfg(feature = "bytes")]
+use bytes::Buf;
+use std::{cmp, io};
+use std::collections::VecDeque;
+use std::io::IoSlice;
+
+/// Checkouts linked into one stream of bytes, for messages larger than the
+/// extra storage of a single entry.
+///
+/// Each segment is a checkout along with the bytes of its extra storage that
+/// hold data. Reading, through `io::Read` or `bytes::Buf` with the `bytes`
+/// feature, carries on from one segment to the next. Segments that have been
+/// read in full are checked back in right away, and any that are left when
+/// the chain is dropped.
+///
+/// ```
+/// use pool::{BufferChain, Dirty, Pool};
+/// use std::io::Read;
+///
+/// let mut pool = Pool::with_capacity(16, 64, || Dirty(()));
+/// let msg = vec![7; 500];
+///
+/// let mut chain = BufferChain::new();
+/// assert_eq!(500, chain.append(&mut pool, &msg));
+/// assert!(chain.segments() > 1);
+///
+/// let mut out = vec![];
+/// chain.read_to_end(&mut out).unwrap();
+/// assert_eq!(msg, out);
+/// assert_eq!(0, pool.in_use());
+/// ```
+pub struct BufferChain<T, E: Extra = u8> {
+    segments: VecDeque<Segment<T, E>>,
+    len: usize,
+}
+
+struct Segment<T, E: Extra> {
+    checkout: Checkout<T, E>,
+    start: usize, // First byte not read yet
+    end: usize,   // End of the data
+}
+
+impl<T, E: Extra> Segment<T, E> {
+    fn data(&self) -> &[u8] {
+        &self.checkout.entry().extra()[self.start..self.end]
+    }
+}
+
+impl<T, E: Extra> BufferChain<T, E> {
+    /// Returns an empty chain.
+    pub fn new() -> BufferChain<T, E> {
+        BufferChain {
+            segments: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    /// Appends a segment holding the first `len` extra bytes of `checkout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than the extra storage.
+    pub fn push(&mut self, checkout: Checkout<T, E>, len: usize) {
+        assert!(len <= checkout.entry().extra().len(), "segment longer than the extra storage");
+
+        self.len += len;
+        self.segments.push_back(Segment { checkout, start: 0, end: len });
+    }
+
+    /// Number of bytes left to read.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there is nothing left to read.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of segments, the checkouts held by the chain.
+    pub fn segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the bytes left to read as one `IoSlice` per segment, in
+    /// order, for vectored writes.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        self.segments.iter().map(|segment| IoSlice::new(segment.data())).collect()
+    }
+
+    /// Returns the bytes left to read in the first segment that has any.
+    pub fn chunk(&self) -> &[u8] {
+        self.segments.iter().map(Segment::data).find(|data| !data.is_empty()).unwrap_or(&[])
+    }
+
+    /// Marks `n` bytes as read, checking in the segments read in full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes are left.
+    pub fn advance(&mut self, mut n: usize) {
+        assert!(n <= self.len, "advanced past the end of the chain");
+        self.len -= n;
+
+        while let Some(segment) = self.segments.front_mut() {
+            let len = segment.end - segment.start;
+
+            if n < len {
+                segment.start += n;
+                return;
+            }
+
+            n -= len;
+            self.segments.pop_front();
+        }
+    }
+
+    /// Checks in every segment.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.len = 0;
+    }
+
+    /// Copies `src` to the end of the chain, filling the room left in the
+    /// last segment first, then checking out more from `pool`. Returns the
+    /// number of bytes copied, short of all of `src` if the pool runs out
+    /// of entries, see `Pool::try_checkout`.
+    pub fn append(&mut self, pool: &mut Pool<T, E>, mut src: &[u8]) -> usize where T: Reset {
+        let total = src.len();
+
+        loop {
+            if let Some(segment) = self.segments.back_mut() {
+                let extra = segment.checkout.entry_mut().extra_mut();
+                let n = cmp::min(src.len(), extra.len() - segment.end);
+
+                extra[segment.end..][..n].copy_from_slice(&src[..n]);
+                segment.end += n;
+                self.len += n;
+                src = &src[n..];
+            }
+
+            if src.is_empty() {
+                return total;
+            }
+
+            match pool.try_checkout() {
+                // A pool without extra storage would never make progress
+                Some(ref checkout) if checkout.entry().extra().is_empty() => return total - src.len(),
+                Some(checkout) => self.push(checkout, 0),
+                None => return total - src.len(),
+            }
+        }
+    }
+}
+
+impl<T, E: Extra> Default for BufferChain<T, E> {
+    fn default() -> BufferChain<T, E> {
+        BufferChain::new()
+    }
+}
+
+impl<T, E: Extra> io::Read for BufferChain<T, E> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+
+        while n < buf.len() && !self.is_empty() {
+            let len = {
+                let chunk = self.chunk();
+                let len = cmp::min(chunk.len(), buf.len() - n);
+
+                buf[n..][..len].copy_from_slice(&chunk[..len]);
+                len
+            };
+
+            self.advance(len);
+            n += len;
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<T, E: Extra> Buf for BufferChain<T, E> {
+    fn remaining(&self) -> usize {
+        self.len
+    }
+
+    fn chunk(&self) -> &[u8] {
+        BufferChain::chunk(self)
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let data = self.segments.iter().map(Segment::data).filter(|data| !data.is_empty());
+        let mut n = 0;
+
+        for (dst, data) in dst.iter_mut().zip(data) {
+            *dst = IoSlice::new(data);
+            n += 1;
+        }
+
+        n
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        BufferChain::advance(self, cnt)
+    }
+}
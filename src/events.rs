@@ -0,0 +1,207 @@
+use std::sync::{Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::time::{Duration, Instant};
+use sync::{Arc, AtomicUsize, Ordering};
+
+// Number of events a receiver may fall behind by before events are dropped
+const BACKLOG: usize = 1024;
+
+/// A change in the state of a pool, received from `Pool::events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolEvent {
+    /// A checkout found every entry in use.
+    Exhausted,
+    /// A value was checked back in after the pool was exhausted.
+    Recovered {
+        /// How long the pool was exhausted for
+        after: Duration,
+    },
+    /// A value was dropped instead of being kept for reuse, a pinned value or
+    /// one retired with `Checkout::retire`.
+    EntryRetired,
+    /// Entries were added to the pool.
+    Grew {
+        /// Number of entries added
+        by: usize,
+    },
+    /// Idle values were removed from the pool.
+    Shrank {
+        /// Number of values removed
+        by: usize,
+    },
+    /// A checkout was held past its lease and revoked, see
+    /// `Builder::lease`.
+    LeaseRevoked,
+}
+
+/// The state of a pool when a checkout found it exhausted, passed to the
+/// function set with `Builder::on_exhausted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhaustion {
+    /// Number of checkouts waiting for a value, blocked or polled
+    pub waiters: usize,
+    /// Number of entries checked out
+    pub in_use: usize,
+    /// Number of entries
+    pub capacity: usize,
+}
+
+pub(crate) type ExhaustionFn = Arc<dyn Fn(&Exhaustion) + Send + Sync>;
+
+// Calls a function on failed checkouts, at most once per interval
+pub(crate) struct ExhaustionHook {
+    interval: Duration,
+    last: Mutex<Option<Instant>>, // Last call
+    callback: ExhaustionFn,
+}
+
+impl ExhaustionHook {
+    pub fn new(interval: Duration, callback: ExhaustionFn) -> ExhaustionHook {
+        ExhaustionHook {
+            interval,
+            last: Mutex::new(None),
+            callback,
+        }
+    }
+
+    // Calls the function with the state returned by `f`, unless it was
+    // called less than an interval ago
+    pub fn call<F>(&self, f: F)
+            where F: FnOnce() -> Exhaustion {
+        {
+            let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+
+            if last.is_some_and(|last| now.duration_since(last) < self.interval) {
+                return;
+            }
+
+            *last = Some(now);
+        }
+
+        (self.callback)(&f());
+    }
+}
+
+// The receivers of a pool's events. Sending is skipped entirely while nobody
+// listens, the log aside.
+pub(crate) struct Events {
+    name: &'static str, // Name of the pool in log messages
+    listeners: AtomicUsize,
+    exhausted: AtomicUsize, // Non-zero while the pool is exhausted and listened
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    senders: Vec<SyncSender<PoolEvent>>,
+    exhausted: Option<Instant>, // Since when the pool is exhausted
+}
+
+impl Events {
+    pub fn new(name: &'static str) -> Events {
+        Events {
+            name,
+            listeners: AtomicUsize::new(0),
+            exhausted: AtomicUsize::new(0),
+            inner: Mutex::new(Inner {
+                senders: vec![],
+                exhausted: None,
+            }),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<PoolEvent> {
+        let (tx, rx) = mpsc::sync_channel(BACKLOG);
+        let mut inner = self.lock();
+
+        inner.senders.push(tx);
+        self.listeners.store(inner.senders.len(), Ordering::Relaxed);
+        rx
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn send(&self, event: PoolEvent) {
+        self.log(event);
+
+        if self.is_listened() {
+            self.lock().send(event, &self.listeners);
+        }
+    }
+
+    // Sends `Exhausted`, unless the pool already was
+    pub fn exhausted(&self) {
+        if self.is_observed() && self.exhausted.load(Ordering::Relaxed) == 0 {
+            let mut inner = self.lock();
+
+            if inner.exhausted.is_none() {
+                inner.exhausted = Some(Instant::now());
+                self.log(PoolEvent::Exhausted);
+                inner.send(PoolEvent::Exhausted, &self.listeners);
+                self.exhausted.store(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Sends `Recovered` if the pool was exhausted. Called on every checkin,
+    // a checkin racing with the pool becoming exhausted leaves it to the next
+    // one.
+    pub fn recovered(&self) {
+        if self.exhausted.load(Ordering::Relaxed) != 0 {
+            let mut inner = self.lock();
+
+            self.exhausted.store(0, Ordering::Relaxed);
+
+            if let Some(since) = inner.exhausted.take() {
+                let event = PoolEvent::Recovered { after: since.elapsed() };
+
+                self.log(event);
+                inner.send(event, &self.listeners);
+            }
+        }
+    }
+
+    fn is_listened(&self) -> bool {
+        self.listeners.load(Ordering::Relaxed) != 0
+    }
+
+    // Whether events go anywhere, to receivers or to the log
+    fn is_observed(&self) -> bool {
+        cfg!(feature = "log") || self.is_listened()
+    }
+
+    #[cfg(feature = "log")]
+    fn log(&self, event: PoolEvent) {
+        match event {
+            PoolEvent::Exhausted => warn!("{}: exhausted", self.name),
+            PoolEvent::Recovered { after } => info!("{}: recovered after {:?}", self.name, after),
+            PoolEvent::EntryRetired => debug!("{}: retired a value", self.name),
+            PoolEvent::Grew { by } => info!("{}: grew by {}", self.name, by),
+            PoolEvent::Shrank { by } => info!("{}: shrank by {}", self.name, by),
+            PoolEvent::LeaseRevoked => warn!("{}: revoked an expired lease", self.name),
+        }
+    }
+
+    #[cfg(not(feature = "log"))]
+    fn log(&self, _: PoolEvent) {
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // Poisoning is irrelevant, senders are never left half updated
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    // Sends the event to every receiver that is still around. Receivers that
+    // fell too far behind miss it.
+    fn send(&mut self, event: PoolEvent, listeners: &AtomicUsize) {
+        self.senders.retain(|tx| {
+            !matches!(tx.try_send(event), Err(mpsc::TrySendError::Disconnected(_)))
+        });
+
+        listeners.store(self.senders.len(), Ordering::Relaxed);
+    }
+}
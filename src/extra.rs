@@ -0,0 +1,49 @@
+use std::{mem, slice};
+
+/// Types the extra storage of a pool's entries can be made of, see
+/// `Builder::extra_of`.
+///
+/// The extra storage starts out zeroed and may be written to as raw bytes,
+/// for example by `Checkout::bump`.
+///
+/// Zero sized types, such as `[u8; 0]`, implement the trait but are rejected
+/// at compile time by `Builder::extra_of` and wherever the extra storage is
+/// viewed as values of the type.
+///
+/// # Safety
+///
+/// The type must have no padding bytes and any bit pattern, including all
+/// zeros, must be a valid value.
+pub unsafe trait Extra: Copy + Send + Sync + 'static {
+}
+
+macro_rules! extra {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl Extra for $ty {
+            }
+        )*
+    }
+}
+
+extra!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+// Covers `N == 0` as well, see the trait documentation
+unsafe impl<E: Extra, const N: usize> Extra for [E; N] {
+}
+
+// Views extra bytes as values of type `E`. The bytes must be aligned for
+// `E`, which the pool ensures by aligning the extra bytes of its entries.
+pub(crate) fn from_bytes<E: Extra>(bytes: &[u8]) -> &[E] {
+    const { assert!(mem::size_of::<E>() != 0, "extra storage must not be zero sized") }
+    debug_assert!(bytes.as_ptr() as usize & (mem::align_of::<E>() - 1) == 0, "extra bytes not aligned");
+
+    unsafe { slice::from_raw_parts(bytes.as_ptr() as *const E, bytes.len() / mem::size_of::<E>()) }
+}
+
+pub(crate) fn from_bytes_mut<E: Extra>(bytes: &mut [u8]) -> &mut [E] {
+    const { assert!(mem::size_of::<E>() != 0, "extra storage must not be zero sized") }
+    debug_assert!(bytes.as_ptr() as usize & (mem::align_of::<E>() - 1) == 0, "extra bytes not aligned");
+
+    unsafe { slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut E, bytes.len() / mem::size_of::<E>()) }
+}
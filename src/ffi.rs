@@ -0,0 +1,131 @@
+//! C bindings for pools of byte buffers.
+//!
+//! Each entry of a pool created with `pool_new` is a buffer of at least the
+//! requested number of bytes, living in the entry's extra bytes. Buffers are
+//! checked out as opaque `Buffer` handles and must be checked back in to the
+//! pool they came from.
+//!
+//! ```c
+//! BufferPool *pool = pool_new(128, 4096);
+//!
+//! Buffer *buf = pool_checkout(pool);
+//! memcpy(pool_buffer_ptr(buf), data, len);
+//! pool_checkin(pool, buf);
+//!
+//! pool_free(pool);
+//! ```
+//!
+//! All functions must be called with the pointers they document. A pool and
+//! its buffers must only be used from one thread at a time. Panics never
+//! unwind into C: `pool_new` and `pool_checkout` return null instead, and
+//! the other functions abort the process.
+//!
+//! The declarations are in `include/pool.h`. To link them into a C program,
+//! build the crate as a static library:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type staticlib
+//! ```
+//!
+//! or depend on it, with the `ffi` feature, from a crate that sets
+//! `crate-type = ["staticlib"]` in its `[lib]` section.
+
+use {Checkout, Dirty, Entry, Pool};
+use std::{panic, process, ptr};
+use std::sync::Arc;
+
+/// A pool of byte buffers.
+pub struct BufferPool {
+    pool: Pool<Dirty<()>>,
+}
+
+/// A buffer checked out from a `BufferPool`.
+pub struct Buffer {
+    _p: [u8; 0],
+}
+
+/// Creates a pool of `count` buffers of at least `size` bytes each.
+///
+/// The buffers are zeroed. The pool must be freed with `pool_free`. Returns
+/// null if the pool could not be created.
+#[no_mangle]
+pub extern "C" fn pool_new(count: usize, size: usize) -> *mut BufferPool {
+    catch(|| {
+        let pool = Pool::with_capacity(count, size, || Dirty(()));
+        Box::into_raw(Box::new(BufferPool { pool }))
+    }).unwrap_or(ptr::null_mut())
+}
+
+/// Frees a pool created by `pool_new`.
+///
+/// Buffers that are still checked out can no longer be checked in, their
+/// memory is leaked.
+///
+/// # Safety
+///
+/// `pool` must have been returned by `pool_new` and not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pool_free(pool: *mut BufferPool) {
+    catch(|| drop(Box::from_raw(pool))).unwrap_or_else(|| process::abort())
+}
+
+/// Checks out a buffer, or returns null if all buffers are in use.
+///
+/// # Safety
+///
+/// `pool` must be a live pool returned by `pool_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pool_checkout(pool: *mut BufferPool) -> *mut Buffer {
+    catch(|| {
+        match (*pool).pool.try_checkout() {
+            Some(checkout) => checkout.into_raw() as *mut Buffer,
+            None => ptr::null_mut(),
+        }
+    }).unwrap_or(ptr::null_mut())
+}
+
+/// Returns a buffer to the pool it was checked out from.
+///
+/// Aborts the process if `buf` was checked out from another pool.
+///
+/// # Safety
+///
+/// `buf` must have been checked out from a live pool and not been checked
+/// in yet.
+#[no_mangle]
+pub unsafe extern "C" fn pool_checkin(pool: *mut BufferPool, buf: *mut Buffer) {
+    let owner = (*(buf as *const Entry<Dirty<()>>)).tracked().map_or(ptr::null(), |track| track.owner);
+
+    if owner != Arc::as_ptr(&(*pool).pool.inner) {
+        // Checking it in anyway would corrupt the other pool's free list
+        process::abort();
+    }
+
+    catch(|| drop(Checkout::<Dirty<()>>::from_raw(buf as *mut Dirty<()>)))
+        .unwrap_or_else(|| process::abort())
+}
+
+/// Returns a pointer to the first byte of the buffer.
+///
+/// # Safety
+///
+/// `buf` must be checked out.
+#[no_mangle]
+pub unsafe extern "C" fn pool_buffer_ptr(buf: *mut Buffer) -> *mut u8 {
+    (*(buf as *mut Entry<Dirty<()>>)).extra_mut().as_mut_ptr()
+}
+
+/// Returns the size of the buffer in bytes.
+///
+/// # Safety
+///
+/// `buf` must be checked out.
+#[no_mangle]
+pub unsafe extern "C" fn pool_buffer_len(buf: *const Buffer) -> usize {
+    (*(buf as *const Entry<Dirty<()>>)).extra().len()
+}
+
+// Runs `f`, returning `None` if it panics instead of unwinding into C
+fn catch<R, F: FnOnce() -> R>(f: F) -> Option<R> {
+    panic::catch_unwind(panic::AssertUnwindSafe(f)).ok()
+}
@@ -0,0 +1,126 @@
+//! Process-wide pools, declared as statics.
+//!
+//! A `GlobalPool` is created in a `const` context and builds its pool on
+//! first use, so that libraries can share a pool, say of buffers, without
+//! threading a `Pool` through every API.
+//!
+//! ```
+//! use pool::{Dirty, Pool};
+//! use pool::global::GlobalPool;
+//!
+//! static BUFFERS: GlobalPool<Dirty<Vec<u8>>> = GlobalPool::new(|| {
+//!     Pool::with_capacity(64, 0, || Dirty(Vec::with_capacity(4096)))
+//! });
+//!
+//! let mut buf = BUFFERS.checkout();
+//! buf.extend_from_slice(b"hello");
+//! ```
+
+use {Checkout, Extra, OnEmpty, Pool, Priority, Reset, Unpark, Wait};
+use std::{fmt, thread};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::task::Waker;
+
+/// A pool built on first use, see the module documentation.
+///
+/// Checkouts go through a mutex, held only while checking out: values are
+/// checked back in without it, from any thread. A checkout that waits for a
+/// value, see `OnEmpty::Block`, waits without the mutex, so other threads
+/// can check out and in meanwhile.
+pub struct GlobalPool<T: Reset, E: Extra = u8> {
+    pool: OnceLock<Mutex<Pool<T, E>>>,
+    build: fn() -> Pool<T, E>,
+}
+
+impl<T: Reset, E: Extra> GlobalPool<T, E> {
+    /// Returns a handle to a pool that is built by `build` when it is first
+    /// used.
+    pub const fn new(build: fn() -> Pool<T, E>) -> GlobalPool<T, E> {
+        GlobalPool {
+            pool: OnceLock::new(),
+            build,
+        }
+    }
+
+    /// See `Pool::checkout`.
+    pub fn checkout(&self) -> Checkout<T, E> {
+        self.checkout_with(true)
+    }
+
+    /// See `Pool::checkout_dirty`.
+    pub fn checkout_dirty(&self) -> Checkout<T, E> {
+        self.checkout_with(false)
+    }
+
+    /// See `Pool::try_checkout`.
+    pub fn try_checkout(&self) -> Option<Checkout<T, E>> {
+        self.lock().try_checkout()
+    }
+
+    /// Calls `f` with the pool, for everything else a `Pool` can do.
+    ///
+    /// Other threads checking out of the pool wait until `f` returns.
+    pub fn with<F, R>(&self, f: F) -> R
+            where F: FnOnce(&mut Pool<T, E>) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Returns whether the pool has been built yet.
+    pub fn is_initialized(&self) -> bool {
+        self.pool.get().is_some()
+    }
+
+    // Checks out under the mutex, and waits for a checkin without it
+    fn checkout_with(&self, reset: bool) -> Checkout<T, E> {
+        let mut waker = None;
+
+        loop {
+            {
+                let mut pool = self.lock();
+
+                if let Some(checkout) = pool.checkout_with(Wait::Never, reset, Priority::Normal) {
+                    if let Some(ref waker) = waker {
+                        pool.inner.unregister(waker);
+                    }
+
+                    return checkout;
+                }
+
+                if pool.inner.on_empty != OnEmpty::Block {
+                    unsafe { pool.inner.depleted() }
+                    drop(pool);
+                    panic!("pool is depleted");
+                }
+
+                let waker = waker.get_or_insert_with(|| Waker::from(Arc::new(Unpark(thread::current()))));
+
+                // Look again after registering, a checkin in between would
+                // not have seen the waker
+                pool.inner.register(waker, Priority::Normal);
+
+                if let Some(checkout) = pool.checkout_with(Wait::Never, reset, Priority::Normal) {
+                    pool.inner.unregister(waker);
+                    return checkout;
+                }
+            }
+
+            // Woken by a checkin, or spuriously, either way look again
+            thread::park();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pool<T, E>> {
+        // A checkout panicking, on an exhausted pool, leaves it intact
+        self.pool.get_or_init(|| Mutex::new((self.build)()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Reset, E: Extra> fmt::Debug for GlobalPool<T, E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("GlobalPool")
+            .field("initialized", &self.is_initialized())
+            .finish()
+    }
+}
@@ -0,0 +1,105 @@
+use {Checkout, Extra, OnEmpty, Pool, Priority, Reset, Wait};
+
+/// Checks out one value from each of several pools, all or nothing.
+///
+/// Implemented for tuples of up to six `&mut Pool`, of any value types. If
+/// any of the pools is out of entries, the values taken from the others are
+/// put back right away rather than held on to, so that competing requests
+/// each holding part of what they need don't starve each other.
+///
+/// ```
+/// use pool::{Dirty, JointCheckout, Pool};
+///
+/// let mut headers = Pool::with_capacity(2, 0, || Dirty(Vec::<u8>::with_capacity(256)));
+/// let mut bodies = Pool::with_capacity(1, 0, || Dirty(Vec::<u8>::with_capacity(4096)));
+///
+/// let (header, body) = (&mut headers, &mut bodies).try_checkout_all().unwrap();
+///
+/// // Out of bodies, the header is not kept
+/// assert!((&mut headers, &mut bodies).try_checkout_all().is_none());
+/// assert_eq!(1, headers.in_use());
+///
+/// drop((header, body));
+/// ```
+pub trait JointCheckout {
+    /// The checkouts, one per pool.
+    type Output;
+
+    /// Checks out a value from each pool, or none at all if any of them is
+    /// out of entries. Never waits.
+    fn try_checkout_all(self) -> Option<Self::Output>;
+
+    /// Checks out a value from each pool, waiting for pools that block when
+    /// empty, see `OnEmpty::Block`.
+    ///
+    /// Nothing is held while waiting: a pool found empty is waited on
+    /// alone, then the others are tried again, until all of them have a
+    /// value to spare. Returns `None` if a pool that doesn't block is out of
+    /// entries.
+    fn checkout_all(self) -> Option<Self::Output>;
+}
+
+// Why a joint checkout came up empty
+enum Missing {
+    Wait(usize), // The pool at the index blocks when empty, wait on it
+    Fail,
+}
+
+// Checks out of the pool at `idx` without waiting
+fn take<T: Reset, E: Extra>(pool: &mut Pool<T, E>, idx: usize) -> Result<Checkout<T, E>, Missing> {
+    match pool.checkout_with(Wait::Never, true, Priority::Normal) {
+        Some(checkout) => Ok(checkout),
+        None if pool.inner.on_empty == OnEmpty::Block => Err(Missing::Wait(idx)),
+        None => {
+            unsafe { pool.inner.depleted() }
+            Err(Missing::Fail)
+        }
+    }
+}
+
+macro_rules! joint {
+    ($($n:tt: $T:ident, $E:ident, $held:ident);+) => {
+        impl<'a, $($T: Reset, $E: Extra),+> JointCheckout for ($(&'a mut Pool<$T, $E>,)+) {
+            type Output = ($(Checkout<$T, $E>,)+);
+
+            fn try_checkout_all(self) -> Option<Self::Output> {
+                // Dropping the checkouts taken so far puts them back
+                Some(($(self.$n.try_checkout()?,)+))
+            }
+
+            fn checkout_all(self) -> Option<Self::Output> {
+                let pools = self;
+                let mut wait = None;
+
+                loop {
+                    $(
+                        let mut $held = None;
+
+                        if wait == Some($n) {
+                            $held = Some(pools.$n.checkout_with(Wait::Blocking, true, Priority::Normal)?);
+                        }
+                    )+
+
+                    let all: Result<Self::Output, Missing> = (|| Ok(($(
+                        match $held.take() {
+                            Some(checkout) => checkout,
+                            None => take(&mut *pools.$n, $n)?,
+                        },
+                    )+)))();
+
+                    match all {
+                        Ok(all) => return Some(all),
+                        Err(Missing::Wait(idx)) => wait = Some(idx),
+                        Err(Missing::Fail) => return None,
+                    }
+                }
+            }
+        }
+    };
+}
+
+joint!(0: A, EA, a; 1: B, EB, b);
+joint!(0: A, EA, a; 1: B, EB, b; 2: C, EC, c);
+joint!(0: A, EA, a; 1: B, EB, b; 2: C, EC, c; 3: D, ED, d);
+joint!(0: A, EA, a; 1: B, EB, b; 2: C, EC, c; 3: D, ED, d; 4: F, EF, f);
+joint!(0: A, EA, a; 1: B, EB, b; 2: C, EC, c; 3: D, ED, d; 4: F, EF, f; 5: G, EG, g);
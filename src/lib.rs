@@ -13,7 +13,7 @@
 //!
 //! let mut pool = Pool::with_capacity(20, 0, || Dirty(Vec::with_capacity(16_384)));
 //!
-//! let mut vec = pool.checkout().unwrap();
+//! let mut vec = pool.checkout();
 //!
 //! // Do some work with the value, this can happen in another thread
 //! thread::spawn(move || {
@@ -25,7 +25,7 @@
 //! }).join();
 //!
 //! // The vec will have been returned to the pool by now
-//! let vec = pool.checkout().unwrap();
+//! let vec = pool.checkout();
 //!
 //! // The pool operates LIFO, so this vec will be the same value that was used
 //! // in the thread above. The value will also be left as it was when it was
@@ -42,6 +42,10 @@
 //! pool of buffers. The metadata could be stored as the `Pool` value and the
 //! byte array can be stored in the padding.
 //!
+//! The extra storage can also be made of another type than bytes, for
+//! example `Pool<Header, u64>` hands out a `&mut [u64]` with each value, see
+//! `Builder::extra_of`.
+//!
 //! ## Threading
 //!
 //! Checking out values from the pool requires a mutable reference to the pool
@@ -49,317 +53,4538 @@
 //! pool is thread safe and lock free, so if the value being pooled is `Sync`
 //! then `Checkout<T>` is `Sync` as well.
 //!
+//! Values only cross threads when that is safe for the pooled type: the pool
+//! and its checkouts can be sent to another thread only if `T` is `Send`.
+//!
+//! ```compile_fail
+//! use pool::{Pool, Dirty};
+//! use std::rc::Rc;
+//! use std::thread;
+//!
+//! let mut pool = Pool::with_capacity(1, 0, || Dirty(Rc::new(0)));
+//! let val = pool.checkout();
+//!
+//! // `Rc` is not `Send`
+//! thread::spawn(move || drop(val));
+//! ```
+//!
 //! The easiest way to have a single pool shared across many threads would be
 //! to wrap `Pool` in a mutex.
-use std::{mem, ops, ptr, usize};
+
+#[cfg(feature = "allocator-api2")]
+extern crate allocator_api2;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate windows_sys;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "macros")]
+extern crate pool_macros;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tower")]
+extern crate tower;
+#[cfg(feature = "secure")]
+extern crate zeroize;
+
+use std::{cmp, fmt, hint, mem, ops, ptr, thread, vec};
+use std::future::Future;
+use std::pin::Pin;
+use std::alloc::{self, Layout};
+use std::borrow::{Borrow, BorrowMut};
 use std::cell::UnsafeCell;
-use std::sync::Arc;
-use std::sync::atomic::{self, AtomicUsize, Ordering};
-pub use reset::{Reset, Dirty};
+use std::hash::{Hash, Hasher};
+use std::io::{IoSlice, IoSliceMut};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use budget::Budget;
+use events::{Events, ExhaustionHook};
+use region::Region;
+use watermark::{Adaptive, AutoGrow, AutoShrink, Watermark};
+use weighted::WeightFn;
+use sync::{fence, Arc, AtomicPtr, AtomicUsize, Ordering, Weak};
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::sync::mpsc::Receiver;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+pub use budget::HeapSize;
+pub use builder::{Builder, IdleOrder, OnEmpty};
+pub use cancel::CancelToken;
+pub use chain::BufferChain;
+pub use events::{Exhaustion, PoolEvent};
+pub use extra::Extra;
+pub use joint::JointCheckout;
+pub use region::RegionsMut;
+pub use bump::{Arena, Bump};
+pub use local::{LocalPool, LocalCheckout};
+pub use reset::{Reset, ResetWith, Clean, Dirty};
+pub use ring::{ExtraRing, RingConsumer, RingProducer};
+pub use scope::{scope, Scope, ScopedPool};
+pub use token::{TokenTable, TokensMut};
+pub use vectored::{io_slices, io_slices_mut};
+pub use weighted::WeightedCheckout;
 
+/// Generates a pool type for a struct, and its `Reset` implementation.
+///
+/// Applied to `struct Name`, generates:
+///
+/// - `NamePool`, wrapping a `Pool<Name>`, which it derefs to. Its values
+///   are created with `Default`, unless `init` names another function.
+///   `NamePool::build` takes a `Builder` for everything else.
+/// - `NameHandle`, an alias of `Checkout<Name>`.
+/// - `Reset for Name`, resetting each field with its own `Reset`
+///   implementation, unless the field is marked `#[reset(default)]` to set
+///   it to its default, `#[reset(with = path)]` to pass it to a function
+///   instead, or `#[reset(skip)]` to leave it as is.
+///
+/// The attribute takes `pool = Ident` and `handle = Ident` to name the
+/// generated types, `capacity = expr` for the capacity of `NamePool::new`,
+/// and `init = path`. Without a capacity, `NamePool::new` starts out empty
+/// and grows as values are checked out, see `Builder::unbounded`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate pool;
+///
+/// #[pooled(capacity = 16)]
+/// #[derive(Default)]
+/// pub struct Request {
+///     headers: Vec<(String, String)>,
+///     #[reset(default)]
+///     status: Option<u16>,
+///     #[reset(skip)]
+///     scratch: Vec<u8>,
+/// }
+///
+/// # fn main() {
+/// let mut requests = RequestPool::new();
+///
+/// let mut req: RequestHandle = requests.checkout();
+/// req.headers.push(("host".to_string(), "example.com".to_string()));
+/// req.status = Some(200);
+/// drop(req);
+///
+/// let req = requests.checkout();
+/// assert!(req.headers.is_empty());
+/// assert_eq!(None, req.status);
+/// assert_eq!(16, requests.capacity());
+/// # }
+/// ```
+#[cfg(feature = "macros")]
+pub use pool_macros::pooled;
+
+mod budget;
+mod builder;
+mod bump;
+mod cancel;
+mod chain;
+mod events;
+mod extra;
+mod joint;
+mod local;
+mod os;
+mod region;
 mod reset;
+mod ring;
+mod scope;
+mod sync;
+mod token;
+mod vectored;
+mod watermark;
+mod weighted;
+
+#[cfg(feature = "allocator-api2")]
+pub mod allocator;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod global;
+pub mod manager;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(all(unix, feature = "shm"))]
+pub mod shm;
+#[cfg(unix)]
+pub mod stack;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 /// A pool of reusable values
-pub struct Pool<T: Reset> {
-    inner: Arc<UnsafeCell<PoolInner<T>>>,
+///
+/// Each entry has extra storage of `E` values, bytes by default, see
+/// `Builder::extra_of`.
+pub struct Pool<T: Reset, E: Extra = u8> {
+    inner: Arc<PoolInner<T>>,
+    _p: PhantomData<E>,
+}
+
+impl<T: Reset> Pool<T> {
+    /// Creates a new pool that can contain up to `capacity` entries as well as
+    /// `extra` extra bytes. Initializes each entry with the given function.
+    pub fn with_capacity<F>(count: usize, extra: usize, init: F) -> Pool<T>
+            where F: Fn() -> T + Send + Sync + 'static {
+        Builder::new()
+            .capacity(count)
+            .extra(extra)
+            .build(init)
+    }
+
+    /// Creates a new pool from already constructed values, with `extra` extra
+    /// bytes for each entry.
+    ///
+    /// The pool has exactly as many entries as there are values. Since there
+    /// is no initializer, entries whose value is removed stay empty until they
+    /// are refilled with `extend`.
+    pub fn from_vec(values: Vec<T>, extra: usize) -> Pool<T> {
+        let mut values = values.into_iter();
+        let inner = PoolInner::new(&Builder::new().extra(extra), None);
+
+        unsafe {
+            inner.grow(values.len(), |_, _| values.next().unwrap());
+        }
+
+        Pool { inner: Arc::new(inner), _p: PhantomData }
+    }
+
+    /// Creates a new pool from the values yielded by an iterator, with `extra`
+    /// extra bytes for each entry.
+    ///
+    /// The pool has exactly as many entries as there are values.
+    pub fn from_iter<I>(values: I, extra: usize) -> Pool<T>
+            where I: IntoIterator<Item = T> {
+        Pool::from_vec(values.into_iter().collect(), extra)
+    }
+
+    /// Returns a `Builder` for configuring a new pool.
+    pub fn builder() -> Builder<T> {
+        Builder::new()
+    }
 }
 
-impl<T: Reset> Pool<T> {
-    /// Creates a new pool that can contain up to `capacity` entries as well as
-    /// `extra` extra bytes. Initializes each entry with the given function.
-    pub fn with_capacity<F>(count: usize, mut extra: usize, init: F) -> Pool<T>
-            where F: Fn() -> T {
+impl<T: Reset, E: Extra> Pool<T, E> {
+    fn build(builder: &Builder<T, E>, init: Init<T>) -> Pool<T, E> {
+        let inner = PoolInner::new(builder, Some(init));
+
+        unsafe {
+            let (init, _) = inner.init.as_ref().unwrap().get();
+            inner.grow(builder.count, &**init);
+        }
+
+        inner.created.store(builder.count, Ordering::Relaxed);
+        Pool { inner: Arc::new(inner), _p: PhantomData }
+    }
+
+    // Creates a pool that only uses `init` for its initial values
+    fn build_eager<F>(builder: &Builder<T, E>, init: F) -> Pool<T, E>
+            where F: FnMut(usize, &mut [u8]) -> T {
+        builder.assert_eager();
+
+        let inner = PoolInner::new(builder, None);
+
+        unsafe {
+            inner.grow(builder.count, init);
+        }
+
+        inner.created.store(builder.count, Ordering::Relaxed);
+        Pool { inner: Arc::new(inner), _p: PhantomData }
+    }
+
+    fn build_parallel<F>(builder: &Builder<T, E>, threads: usize, init: F) -> Pool<T, E>
+            where F: Fn(usize, &mut [u8]) -> T + Sync,
+                  T: Send {
+
+        // Without threads, there is nothing to parallelize
+        if !sync::HAS_THREADS {
+            return Pool::build_eager(builder, init);
+        }
+
+        builder.assert_eager();
+
+        let inner = PoolInner::new(builder, None);
+        let extra = inner.extra();
+        let count = builder.count;
+        let entry_size = inner.entry_size;
+
+        if count == 0 {
+            return Pool { inner: Arc::new(inner), _p: PhantomData };
+        }
+
+        let threads = cmp::max(1, cmp::min(threads, count));
+        let chunk = count.div_ceil(threads);
+        let init = &init;
+        let created = inner.timestamp();
+
+        // Allocate all the memory up front and split it into ranges of
+        // entries that don't cross a segment boundary. Each range is
+        // identified by the index and address of its first entry.
+        let mut ranges = vec![];
+
+        for start in (0..count).step_by(inner.segment_len) {
+            let base = unsafe { inner.alloc_segment() } as usize;
+            let end = cmp::min(start + inner.segment_len, count);
+
+            for offset in (0..end - start).step_by(chunk) {
+                let len = cmp::min(chunk, end - start - offset);
+                ranges.push((start + offset, base + offset * entry_size, len));
+            }
+        }
+
+        let ranges = &ranges;
+
+        // Each thread writes its share of the ranges in place. If an
+        // initializer panics, the guards drop whatever their thread managed
+        // to write before the panic is propagated out of the scope.
+        thread::scope(|scope| {
+            for thread in 0..threads {
+                scope.spawn(move || {
+                    for &(start, base, len) in ranges.iter().skip(thread).step_by(threads) {
+                        let mut guard = InitGuard::<T> {
+                            base,
+                            entry_size,
+                            init: 0,
+                            _p: PhantomData,
+                        };
+
+                        for i in start..start + len {
+                            unsafe {
+                                let ptr = guard.next_ptr();
+
+                                Entry::init(ptr, i, extra, init);
+                                Entry::set_created(ptr, created);
+                            }
+                            guard.init += 1;
+                        }
+
+                        mem::forget(guard);
+                    }
+                });
+            }
+        });
+
+        unsafe {
+            let segments = &mut *inner.segments.get();
+            let last = segments.len() - 1;
+
+            for (i, segment) in segments.iter_mut().enumerate() {
+                segment.len = if i == last {
+                    count - last * inner.segment_len
+                } else {
+                    inner.segment_len
+                };
+            }
+
+            inner.push_list(inner.idle_list(), 0, inner.entry_ptr(count - 1));
+
+            for idx in 0..count {
+                inner.measure(inner.entry_ptr(idx));
+            }
+        }
+
+        inner.created.store(count, Ordering::Relaxed);
+        unsafe { inner.resized(); }
+
+        Pool { inner: Arc::new(inner), _p: PhantomData }
+    }
+
+    // A pool without entries of its own, checking out from `parent`
+    fn child(parent: Arc<PoolInner<T>>) -> Pool<T, E> {
+        let mut builder = Builder::<T>::new().extra_of::<E>(0);
+        builder.parent = Some(parent);
+
+        Pool { inner: Arc::new(PoolInner::new(&builder, None)), _p: PhantomData }
+    }
+
+    /// Checkout a value from the pool. What happens when the pool is
+    /// currently at capacity depends on `Builder::on_empty`, by default this
+    /// waits until a value is checked back in. Use `try_checkout` to never
+    /// wait.
+    ///
+    /// A pool with a parent, see `Builder::parent`, checks out from its
+    /// parent once its own entries are all in use.
+    ///
+    /// The value is reset before it is returned, see `Reset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool is at capacity and `Builder::on_empty` neither
+    /// blocks nor lets it create a value. Use `try_checkout` to handle that
+    /// case.
+    pub fn checkout(&mut self) -> Checkout<T, E> {
+        match self.checkout_with(Wait::Blocking, true, Priority::Normal) {
+            Some(checkout) => checkout,
+            None => {
+                unsafe { self.inner.depleted() }
+                panic!("pool is depleted");
+            }
+        }
+    }
+
+    /// Checkout a value from the pool without resetting it, like `checkout`
+    /// otherwise.
+    ///
+    /// The value contains the state that it had when it was last checked
+    /// in. This saves the cost of the reset for callers that overwrite the
+    /// whole value anyway.
+    ///
+    /// ```
+    /// use pool::{Pool, Clean};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Clean(String::new()));
+    ///
+    /// pool.checkout().push_str("hello");
+    ///
+    /// let val = pool.checkout_dirty();
+    /// assert_eq!("hello", val.0);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool is at capacity and `Builder::on_empty` neither
+    /// blocks nor lets it create a value.
+    pub fn checkout_dirty(&mut self) -> Checkout<T, E> {
+        match self.checkout_with(Wait::Blocking, false, Priority::Normal) {
+            Some(checkout) => checkout,
+            None => {
+                unsafe { self.inner.depleted() }
+                panic!("pool is depleted");
+            }
+        }
+    }
+
+    /// Checkout a value from the pool, like `checkout`, but give up once
+    /// `cancel` is cancelled. Returns `None` instead of panicking if the pool
+    /// is at capacity and does not block.
+    ///
+    /// A checkout that is blocked when the token is cancelled returns `None`
+    /// right away, so threads stuck on an exhausted pool can be released on
+    /// shutdown, see `CancelToken`.
+    pub fn checkout_cancelable(&mut self, cancel: &CancelToken) -> Option<Checkout<T, E>> {
+        let checkout = self.checkout_with(Wait::Until(cancel), true, Priority::Normal);
+
+        if checkout.is_none() && !cancel.is_cancelled() {
+            unsafe { self.inner.depleted() }
+        }
+
+        checkout
+    }
+
+    /// Checkout a value from the pool, without waiting. Returns `None` if the
+    /// pool is currently at capacity, unless `Builder::on_empty` lets it grow
+    /// or create a transient value.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    /// assert!(pool.try_checkout().is_none());
+    /// ```
+    pub fn try_checkout(&mut self) -> Option<Checkout<T, E>> {
+        self.try_checkout_priority(Priority::Normal)
+    }
+
+    /// Checkout a value from the pool, or registers the task to be woken
+    /// once a value is checked back in.
+    ///
+    /// This is the building block for futures and state machines that
+    /// acquire values from the pool without allocating. When the pool is at
+    /// capacity, `Builder::on_empty` still lets it grow or create a transient
+    /// value, otherwise the task waits, whatever the policy.
+    ///
+    /// Each checkin wakes a single waiting task, the one that has been
+    /// waiting the longest, after any waiting with a higher priority, see
+    /// `poll_checkout_priority`. A task that stops polling before it gets a value
+    /// must call `cancel_poll_checkout`, or the wakeup it may have received is
+    /// lost to the other tasks.
+    ///
+    /// ```
+    /// use pool::{Checkout, Dirty, Pool};
+    /// use std::future::Future;
+    /// use std::pin::Pin;
+    /// use std::task::{Context, Poll};
+    ///
+    /// struct Acquire<'a> {
+    ///     pool: &'a mut Pool<Dirty<Vec<u8>>>,
+    /// }
+    ///
+    /// impl<'a> Future for Acquire<'a> {
+    ///     type Output = Checkout<Dirty<Vec<u8>>>;
+    ///
+    ///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    ///         self.pool.poll_checkout(cx)
+    ///     }
+    /// }
+    /// ```
+    pub fn poll_checkout(&mut self, cx: &mut Context<'_>) -> Poll<Checkout<T, E>> {
+        self.poll_checkout_priority(cx, Priority::Normal)
+    }
+
+    /// Stops waiting for a value after `poll_checkout` returned `Pending`
+    /// with the given waker, for example when the future is dropped.
+    ///
+    /// Should the task have been woken already, the wakeup is passed on to
+    /// the next waiting task.
+    pub fn cancel_poll_checkout(&self, waker: &Waker) {
+        self.inner.unregister(waker);
+    }
+
+    /// Checkout a value from the pool with the given priority, like
+    /// `checkout` otherwise.
+    ///
+    /// High priority checkouts may take the entries held back with
+    /// `Builder::reserve_high_priority`. A high priority checkout that waits
+    /// is served before any normal one, see `poll_checkout_priority`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool is at capacity and `Builder::on_empty` neither
+    /// blocks nor lets it create a value.
+    pub fn checkout_priority(&mut self, priority: Priority) -> Checkout<T, E> {
+        match self.checkout_with(Wait::Blocking, true, priority) {
+            Some(checkout) => checkout,
+            None => {
+                unsafe { self.inner.depleted() }
+                panic!("pool is depleted");
+            }
+        }
+    }
+
+    /// Checkout a value from the pool with the given priority, without
+    /// waiting, like `try_checkout` otherwise.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty, Priority};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(2)
+    ///     .reserve_high_priority(1)
+    ///     .build(|| Dirty(0));
+    ///
+    /// let bulk = pool.checkout();
+    /// assert!(pool.try_checkout().is_none());
+    ///
+    /// let admin = pool.try_checkout_priority(Priority::High);
+    /// assert!(admin.is_some());
+    /// ```
+    pub fn try_checkout_priority(&mut self, priority: Priority) -> Option<Checkout<T, E>> {
+        let checkout = self.checkout_with(Wait::Never, true, priority);
+
+        if checkout.is_none() {
+            unsafe { self.inner.depleted() }
+        }
+
+        checkout
+    }
+
+    /// Checkout a value from the pool with the given priority, or registers
+    /// the task to be woken once a value is checked back in, like
+    /// `poll_checkout` otherwise.
+    ///
+    /// Checkins wake the waiting high priority tasks first, in the order they
+    /// started waiting, then the normal ones. The priority of a task is the
+    /// one it first polled with, until it gets a value or cancels.
+    pub fn poll_checkout_priority(&mut self, cx: &mut Context<'_>, priority: Priority) -> Poll<Checkout<T, E>> {
+        if let Some(checkout) = self.checkout_with(Wait::Never, true, priority) {
+            self.inner.unregister(cx.waker());
+            return Poll::Ready(checkout);
+        }
+
+        // Look again after registering, a checkin in between would not have
+        // seen the waker
+        self.inner.register(cx.waker(), priority);
+
+        match self.checkout_with(Wait::Never, true, priority) {
+            Some(checkout) => {
+                self.inner.unregister(cx.waker());
+                Poll::Ready(checkout)
+            }
+            None => {
+                unsafe { self.inner.depleted() }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Checks out entries until their weights add up to at least `weight`,
+    /// see `Builder::weight`. Without a weight function, each entry weighs
+    /// one.
+    ///
+    /// Entries are taken in checkout order. Once one is found that covers
+    /// `weight` on its own, the entries taken before it are put back. All
+    /// or nothing: if the pool runs out first, the entries taken so far are
+    /// put back and `None` is returned. Never waits, holding on to some
+    /// entries while waiting for others could deadlock competing checkouts.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .weight(|buf: &Dirty<Vec<u8>>| buf.capacity())
+    ///     .build(|| Dirty(Vec::with_capacity(1024)));
+    ///
+    /// let bufs = pool.checkout_weighted(3000).unwrap();
+    /// assert_eq!(3, bufs.len());
+    /// assert!(bufs.weight() >= 3000);
+    ///
+    /// assert!(pool.checkout_weighted(2000).is_none());
+    /// drop(bufs);
+    ///
+    /// assert!(pool.checkout_weighted(4000).is_some());
+    /// ```
+    pub fn checkout_weighted(&mut self, weight: usize) -> Option<WeightedCheckout<T, E>> {
+        let mut checkouts = vec![];
+        let mut total = 0;
+
+        while total < weight {
+            // Dropping the checkouts taken so far puts them back
+            let checkout = match self.checkout_with(Wait::Never, true, Priority::Normal) {
+                Some(checkout) => checkout,
+                None => {
+                    unsafe { self.inner.depleted() }
+                    return None;
+                }
+            };
+
+            let w = checkout.weight();
+
+            if w >= weight {
+                checkouts.clear();
+                total = 0;
+            }
+
+            total += w;
+            checkouts.push(checkout);
+        }
+
+        Some(WeightedCheckout { checkouts, weight: total })
+    }
+
+    fn checkout_with(&mut self, wait: Wait<'_>, reset: bool, priority: Priority) -> Option<Checkout<T, E>> {
+        unsafe { self.inner.checkout(wait, priority) }
+            .map(|(ptr, owner)| {
+                Checkout {
+                    entry: ptr,
+                    inner: owner.clone(),
+                    _p: PhantomData,
+                }
+            }).map(|mut checkout| {
+                let stale = checkout.inner.init.as_ref()
+                    .is_some_and(|init| init.epoch() != checkout.entry().epoch());
+
+                if !checkout.entry().init || stale {
+                    // The value was removed from the pool, or created by a
+                    // replaced initializer, create a new one. Should this
+                    // panic, the empty entry is checked back in.
+                    unsafe {
+                        Entry::drop_value(checkout.entry);
+                        checkout.inner.create(checkout.entry);
+                    }
+
+                    checkout.inner.created.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    checkout.inner.recycled.fetch_add(1, Ordering::Relaxed);
+
+                    #[cfg(feature = "log")]
+                    debug!("{}: recycled entry {}", checkout.inner.events.name(), checkout.entry().idx);
+                }
+
+                checkout.entry_mut().uses += 1;
+
+                if reset {
+                    match checkout.inner.reset {
+                        Some(ref reset) => reset(unsafe { (*checkout.entry).data_mut() }),
+                        None => checkout.reset(),
+                    }
+                }
+
+                checkout
+            })
+    }
+
+    /// Removes the values of all entries that are currently idle and returns
+    /// them.
+    ///
+    /// The emptied entries are not lost: they are re-initialized with the
+    /// pool's initializer the next time they are checked out. Pools created
+    /// from existing values have no initializer, their emptied entries are
+    /// refilled by `extend` instead.
+    ///
+    /// Values that are checked out are not affected.
+    pub fn drain_idle(&mut self) -> Vec<T> {
+        let values = unsafe { self.inner.drain_idle() };
+
+        if !values.is_empty() {
+            self.inner.events.send(PoolEvent::Shrank { by: values.len() });
+        }
+
+        values
+    }
+
+    /// Moves values that have sat in the hot tier for longer than `idle` to
+    /// the cold tier, returning how many were moved.
+    ///
+    /// Does nothing unless the pool was built with
+    /// [`Builder::hot_capacity`](struct.Builder.html#method.hot_capacity).
+    pub fn demote_idle(&mut self, idle: Duration) -> usize {
+        match Instant::now().checked_sub(idle) {
+            Some(cutoff) => unsafe { self.inner.demote_idle(cutoff) },
+            None => 0,
+        }
+    }
+
+    /// Parks a checked out value in the pool, returning an id to access it
+    /// by.
+    ///
+    /// The value stays checked out until it is taken back with `unpark`.
+    /// Meanwhile it can be accessed through the pool with `get` and
+    /// `get_mut`. Ids are small and `Copy`, so they can be stored where a
+    /// `Checkout` would be awkward, such as in an event loop's token table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value was checked out from another pool, including the
+    /// pool's parents.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    /// let id = pool.park(val);
+    ///
+    /// **pool.get_mut(id).unwrap() = 5;
+    /// assert_eq!(5, **pool.get(id).unwrap());
+    ///
+    /// let val = pool.unpark(id).unwrap();
+    /// assert!(pool.get(id).is_none());
+    /// ```
+    pub fn park(&mut self, checkout: Checkout<T, E>) -> CheckoutId {
+        assert!(Arc::ptr_eq(&checkout.inner, &self.inner), "value checked out from another pool");
+        assert!(checkout.entry().idx != NIL, "transient values cannot be parked");
+
+        let checkout = mem::ManuallyDrop::new(checkout);
+        let ptr = checkout.entry;
+        let track = unsafe { Entry::track(ptr) };
+
+        let generation = unsafe {
+            (*track).generation = (*track).generation.wrapping_add(1);
+            (*track).generation
+        };
+
+        // The pool keeps the entry, the checkout's handle to it is released
+        drop(unsafe { ptr::read(&checkout.inner) });
+
+        CheckoutId {
+            idx: unsafe { (*ptr).idx },
+            generation,
+        }
+    }
+
+    /// Returns a reference to the parked value with the given id, or `None`
+    /// if it is no longer parked.
+    pub fn get(&self, id: CheckoutId) -> Option<&T> {
+        self.parked(id).map(|ptr| unsafe { (*ptr).data() })
+    }
+
+    /// Returns a mutable reference to the parked value with the given id, or
+    /// `None` if it is no longer parked.
+    pub fn get_mut(&mut self, id: CheckoutId) -> Option<&mut T> {
+        self.parked(id).map(|ptr| unsafe { (*ptr).data_mut() })
+    }
+
+    /// Takes the parked value with the given id back out of the pool, or
+    /// returns `None` if it is no longer parked.
+    ///
+    /// The id is no longer valid afterwards.
+    pub fn unpark(&mut self, id: CheckoutId) -> Option<Checkout<T, E>> {
+        self.parked(id).map(|ptr| {
+            unsafe {
+                let track = Entry::track(ptr);
+                (*track).generation = (*track).generation.wrapping_add(1);
+            }
+
+            Checkout {
+                entry: ptr,
+                inner: self.inner.clone(),
+                _p: PhantomData,
+            }
+        })
+    }
+
+    fn parked(&self, id: CheckoutId) -> Option<*mut Entry<T>> {
+        unsafe {
+            if id.idx >= self.inner.len() {
+                return None;
+            }
+
+            let ptr = self.inner.entry_ptr(id.idx);
+
+            if (*ptr).generation() != id.generation {
+                return None;
+            }
+
+            Some(ptr)
+        }
+    }
+
+    /// Returns a raw pointer to the value of the entry at `idx`, see
+    /// `Checkout::slot`.
+    ///
+    /// This is meant for integrations that keep track of checkouts on their
+    /// own, such as an event loop encoding the slot of a value handed out
+    /// with `Checkout::into_raw` in its registration tokens, to get back to
+    /// the value from the index alone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not below `capacity()`.
+    ///
+    /// # Safety
+    ///
+    /// Entries never move, so the pointer is valid for as long as the pool,
+    /// or anything keeping it alive, is around. The value may only be
+    /// accessed through it while the entry is checked out, and by whoever
+    /// holds the checkout, for example as a raw pointer from `into_raw`. It
+    /// must not be accessed through the checkout meanwhile, unless as
+    /// allowed for `Checkout::data_ptr`. An idle entry's value may be
+    /// checked out at any time, or may not be initialized at all.
+    ///
+    /// ```
+    /// use pool::{Pool, Checkout, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    /// let token = val.slot();
+    /// let raw = val.into_raw();
+    ///
+    /// // Later, given only the token
+    /// let ptr = unsafe { pool.slot_ptr(token) };
+    /// assert_eq!(raw, ptr);
+    /// unsafe { (*ptr).0 = 7; }
+    ///
+    /// drop(unsafe { Checkout::<_>::from_raw(ptr) });
+    /// ```
+    pub unsafe fn slot_ptr(&self, idx: usize) -> *mut T {
+        self.slot_entry(idx) as *mut T
+    }
+
+    /// Returns a raw pointer to the start of the extra storage of the entry
+    /// at `idx`, which holds as many values as `Checkout::extra`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not below `capacity()`.
+    ///
+    /// # Safety
+    ///
+    /// The same rules as for `slot_ptr` apply.
+    pub unsafe fn slot_extra_ptr(&self, idx: usize) -> *mut E {
+        (self.slot_entry(idx) as *mut u8).add(mem::size_of::<Entry<T>>()) as *mut E
+    }
+
+    /// Returns an `iovec` over the extra bytes of each entry, in slot order,
+    /// for registering them as `io_uring` fixed buffers.
+    ///
+    /// A buffer's index in the table is the slot of its entry, see
+    /// `Checkout::buf_index`. Entries never move, so the table stays valid
+    /// for as long as the pool is around. Entries added after the call, as
+    /// the pool grows, are not in it.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 4096, || Dirty(()));
+    /// let iovecs = pool.iovecs();
+    ///
+    /// // Registered with `io_uring_register_buffers`
+    ///
+    /// let buf = pool.checkout();
+    /// let idx = buf.buf_index().unwrap() as usize;
+    /// assert_eq!(buf.extra().as_ptr() as *mut _, iovecs[idx].iov_base);
+    /// ```
+    #[cfg(unix)]
+    pub fn iovecs(&self) -> Vec<libc::iovec> {
+        (0..self.capacity())
+            .map(|idx| unsafe {
+                let ptr = self.inner.entry_ptr(idx);
+
+                libc::iovec {
+                    iov_base: (ptr as *mut u8).add(mem::size_of::<Entry<T>>()) as *mut libc::c_void,
+                    iov_len: (*ptr).extra,
+                }
+            })
+            .collect()
+    }
+
+    unsafe fn slot_entry(&self, idx: usize) -> *mut Entry<T> {
+        assert!(idx < self.inner.len(), "slot {} out of bounds", idx);
+        self.inner.entry_ptr(idx)
+    }
+
+    /// Releases the memory backing the extra bytes of entries that have been
+    /// idle for longer than `idle` back to the OS, returning how many bytes
+    /// were released.
+    ///
+    /// The entries stay in the pool and their address space stays reserved,
+    /// physical memory is faulted back in as the extra bytes are used again.
+    /// Their contents are lost. Only whole pages are released, so this only
+    /// has an effect with large extra regions. Does nothing on platforms
+    /// other than Unix.
+    ///
+    /// Requires `Builder::track_idle`. Entries that have never been checked
+    /// out count as idle for any duration.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(16)
+    ///     .extra(64 * 1024)
+    ///     .track_idle()
+    ///     .build(|| Dirty(()));
+    ///
+    /// // Called periodically from the application's maintenance loop
+    /// pool.release_idle_memory(Duration::from_secs(60));
+    /// ```
+    pub fn release_idle_memory(&mut self, idle: Duration) -> usize {
+        if !self.inner.track_idle {
+            return 0;
+        }
+
+        match Instant::now().checked_sub(idle) {
+            Some(cutoff) => unsafe { self.inner.release_idle(cutoff) },
+            None => 0,
+        }
+    }
+
+    /// Drops the idle values created more than `age` ago, returning how many
+    /// were dropped.
+    ///
+    /// With `recreate`, each value is replaced right away by a new one from
+    /// the pool's initializer. Otherwise the entries are left empty, to be
+    /// filled on their next checkout. Meant to be called from the
+    /// application's own maintenance loop, the pool never does this on its
+    /// own.
+    ///
+    /// Requires `Builder::track_idle`. Does nothing for pools without an
+    /// initializer, such as ones created with `Pool::from_vec` or
+    /// `Builder::build_parallel`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .track_idle()
+    ///     .build(|| Dirty(0));
+    ///
+    /// // Connections don't live for more than an hour
+    /// pool.retire_idle_older_than(Duration::from_secs(3600), true);
+    /// ```
+    pub fn retire_idle_older_than(&mut self, age: Duration, recreate: bool) -> usize {
+        if !self.inner.track_idle || self.inner.init.is_none() {
+            return 0;
+        }
+
+        let retired = match Instant::now().checked_sub(age) {
+            Some(cutoff) => unsafe { self.inner.retire_idle(cutoff, recreate) },
+            None => 0,
+        };
+
+        if retired > 0 && !recreate {
+            self.inner.events.send(PoolEvent::Shrank { by: retired });
+        }
+
+        retired
+    }
+
+    /// Replaces the value of the idle entry at index `idx` with a new one
+    /// from the pool's initializer, leaving the rest of the pool alone.
+    /// Returns false if there is no such entry or it is checked out.
+    ///
+    /// Entry indices are those handed to the initializer, see
+    /// `Builder::build_with`, and `IdleEntry::index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no initializer, such as one created with
+    /// `Pool::from_vec` or `Builder::build_parallel`.
+    pub fn refresh_slot(&mut self, idx: usize) -> bool {
+        self.refresh_where(|entry| entry.index() == idx) != 0
+    }
+
+    /// Replaces the value of each idle entry for which `f` returns true with
+    /// a new one from the pool's initializer, returning how many were
+    /// replaced.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(Some(0)));
+    ///
+    /// // Wedged
+    /// **pool.checkout() = None;
+    ///
+    /// let refreshed = pool.refresh_where(|entry| entry.value().is_some_and(|val| val.is_none()));
+    /// assert_eq!(1, refreshed);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no initializer, such as one created with
+    /// `Pool::from_vec` or `Builder::build_parallel`.
+    pub fn refresh_where<F>(&mut self, mut f: F) -> usize
+            where F: FnMut(IdleEntry<'_, T>) -> bool {
+        assert!(self.inner.init.is_some(), "refreshing requires a pool with an initializer");
+
+        let mut refreshed = 0;
+
+        unsafe {
+            self.inner.for_each_idle(|ptr| {
+                if f(IdleEntry { entry: &*ptr }) {
+                    self.inner.refresh(ptr);
+                    refreshed += 1;
+                }
+            });
+        }
+
+        refreshed
+    }
+
+    /// Replaces the pool's initializer, returning the new epoch of the
+    /// initializer.
+    ///
+    /// Values created from now on come from `init`. Values created by the
+    /// previous initializer are not touched until their next checkout, which
+    /// replaces them with a new value first. This lets a pool pick up new
+    /// configuration under load, without being rebuilt.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty("v1"));
+    /// let old = pool.checkout();
+    ///
+    /// assert_eq!(1, pool.set_init(|| Dirty("v2")));
+    /// assert_eq!("v2", **pool.checkout());
+    ///
+    /// // Checkouts from before the swap keep their value
+    /// assert_eq!("v1", **old);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no initializer, such as one created with
+    /// `Pool::from_vec` or `Builder::build_parallel`.
+    pub fn set_init<F>(&mut self, init: F) -> usize
+            where F: Fn() -> T + Send + Sync + 'static {
+        let current = self.inner.init.as_ref().expect("pool has no initializer to replace");
+        current.replace(Box::new(move |_, _| init()))
+    }
+
+    /// Returns the number of times the pool's initializer was replaced with
+    /// `set_init`.
+    pub fn init_epoch(&self) -> usize {
+        self.inner.init.as_ref().map_or(0, |init| init.epoch())
+    }
+
+    /// Moves the idle values of `other` into this pool, along with the
+    /// contents of their extra storage, and returns how many were moved.
+    ///
+    /// The values fill the entries of this pool whose value was removed, the
+    /// pool grows for the rest. This shifts warm values between pools instead
+    /// of dropping and creating them again. Values still checked out from
+    /// `other` stay with it and are dropped once checked back in. Extra
+    /// storage is copied up to the shorter of the two.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut a = Pool::with_capacity(2, 0, || Dirty(Vec::<u8>::with_capacity(1024)));
+    /// let b = Pool::with_capacity(2, 0, || Dirty(Vec::<u8>::with_capacity(1024)));
+    ///
+    /// assert_eq!(2, a.merge(b));
+    /// assert_eq!(4, a.capacity());
+    /// ```
+    pub fn merge(&mut self, other: Pool<T, E>) -> usize {
+        let mut values = vec![];
+
+        unsafe {
+            // The entries of `other` stay around until it is dropped
+            let count = other.inner.take_idle(usize::MAX, |value, extra| {
+                values.push((value, extra as *const [u8]));
+            });
+
+            self.inner.put(values.into_iter().map(|(value, extra)| (value, &*extra)));
+            count
+        }
+    }
+
+    /// Moves up to `n` idle values into a new pool, along with the contents
+    /// of their extra storage.
+    ///
+    /// The new pool is independent of this one. It has the same entry layout
+    /// and initializer, and otherwise the default configuration. Use it to
+    /// seed short lived pools with warm values, then hand them back with
+    /// `merge`. The emptied entries stay in this pool, as with `drain_idle`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut global = Pool::with_capacity(8, 0, || Dirty(Vec::<u8>::with_capacity(1024)));
+    ///
+    /// let mut job = global.split(2);
+    /// assert_eq!(2, job.capacity());
+    /// assert!(job.checkout().capacity() >= 1024);
+    ///
+    /// global.merge(job);
+    /// ```
+    pub fn split(&mut self, n: usize) -> Pool<T, E> {
+        let extra = self.inner.extra() / mem::size_of::<E>();
+        let builder = Builder::<T>::new()
+            .extra_of::<E>(extra)
+            .align(self.inner.align)
+            .align_extra(self.inner.align);
+
+        let mut inner = PoolInner::new(&builder, None);
+
+        inner.regions = self.inner.regions.clone();
+        inner.init = self.inner.init.as_ref().map(|init| Initializer::new(init.get().0));
+
+        let mut values = vec![];
+
+        unsafe {
+            self.inner.take_idle(n, |value, extra| values.push((value, extra as *const [u8])));
+            inner.put(values.into_iter().map(|(value, extra)| (value, &*extra)));
+        }
+
+        Pool { inner: Arc::new(inner), _p: PhantomData }
+    }
+
+    /// Calls `f` with every idle entry, in the order they would be checked
+    /// out.
+    ///
+    /// Meant for the application's own eviction policies, along with
+    /// `Builder::track_idle` to learn the age of each entry. Checkouts from
+    /// child pools may find the pool empty meanwhile.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .track_idle()
+    ///     .build(|| Dirty(0));
+    ///
+    /// drop(pool.checkout());
+    ///
+    /// let mut used = 0;
+    /// pool.for_each_idle(|entry| {
+    ///     if entry.last_used().is_some() {
+    ///         used += 1;
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(1, used);
+    /// ```
+    pub fn for_each_idle<F>(&mut self, mut f: F)
+            where F: FnMut(IdleEntry<'_, T>) {
+        unsafe {
+            self.inner.for_each_idle(|ptr| f(IdleEntry { entry: &*ptr }));
+        }
+    }
+
+    /// Touches every page of the extra bytes of the idle entries, so that the
+    /// OS backs them with physical memory now rather than on first use.
+    ///
+    /// Fresh pool memory is typically only mapped in as it is written to.
+    /// Call this after building a pool with large extra regions to avoid a
+    /// storm of page faults during the first checkouts. The contents of the
+    /// extra bytes are left as they are.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(16)
+    ///     .extra(64 * 1024)
+    ///     .huge_pages()
+    ///     .build(|| Dirty(()));
+    ///
+    /// pool.warm_up();
+    /// ```
+    pub fn warm_up(&mut self) {
+        unsafe { self.inner.warm_up() }
+    }
+
+    /// Revokes the checkouts held past their lease and adds an entry in
+    /// place of each, see `Builder::lease`. Returns the number of checkouts
+    /// revoked.
+    ///
+    /// Checkouts do this on their own once the pool is out of entries, this
+    /// lets a maintenance task get ahead of them.
+    pub fn reclaim_expired_leases(&mut self) -> usize {
+        match self.inner.lease {
+            Some(_) => unsafe { self.inner.reclaim_leases() },
+            None => 0,
+        }
+    }
+
+    /// Returns true if the pool was built with `Builder::lock_memory` and all
+    /// of its memory was successfully locked into RAM.
+    pub fn is_memory_locked(&self) -> bool {
+        let segments = unsafe { &*self.inner.segments.get() };
+
+        self.inner.lock_memory && segments.iter().all(|segment| segment.locked)
+    }
+
+    /// Returns how the extra bytes of the pool's entries are used, or `None`
+    /// if the pool was not built with `Builder::track_extra_usage` or has no
+    /// entries.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .extra(1024)
+    ///     .track_extra_usage()
+    ///     .build(|| Dirty(()));
+    ///
+    /// pool.checkout().extra_mut()[..100].copy_from_slice(&[1; 100]);
+    ///
+    /// let usage = pool.extra_usage().unwrap();
+    /// assert_eq!(4, usage.entries);
+    /// assert_eq!(100, usage.max);
+    /// assert_eq!(0, usage.min);
+    /// ```
+    pub fn extra_usage(&self) -> Option<ExtraUsage> {
+        if !self.inner.track_extra {
+            return None;
+        }
+
+        let mut used: Vec<usize> = unsafe {
+            (0..self.inner.len())
+                .map(|idx| (*self.inner.entry_ptr(idx)).extra_used())
+                .collect()
+        };
+
+        ExtraUsage::new(&mut used, self.inner.extra())
+    }
+
+    /// Returns the bytes counted against the pool's byte budget, see
+    /// `Builder::byte_budget`, or `None` if it has none.
+    ///
+    /// Values that are checked out count as they were last measured.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .byte_budget(1024 * 1024)
+    ///     .build(|| Dirty(Vec::<u8>::with_capacity(1024)));
+    ///
+    /// assert!(pool.byte_usage().unwrap() >= 4 * 1024);
+    /// ```
+    pub fn byte_usage(&self) -> Option<usize> {
+        self.inner.budget.as_ref().map(|budget| budget.used(self.inner.entry_size))
+    }
+
+    /// Returns the name of the pool, see `Builder::name`.
+    pub fn name(&self) -> &'static str {
+        self.inner.events.name()
+    }
+
+    /// Returns the number of entries of the pool, whether idle or checked
+    /// out.
+    pub fn capacity(&self) -> usize {
+        unsafe { self.inner.capacity() }
+    }
+
+    /// Returns the number of entries currently checked out, including those
+    /// of this pool checked out by its children, but not transient values.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    /// let val = pool.checkout();
+    ///
+    /// assert_eq!(4, pool.capacity());
+    /// assert_eq!(1, pool.in_use());
+    /// ```
+    pub fn in_use(&self) -> usize {
+        self.inner.in_use.load(Ordering::Relaxed)
+    }
+
+    /// Returns a snapshot of the state of the pool.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    ///
+    /// drop(pool.checkout());
+    /// let val = pool.checkout();
+    ///
+    /// let status = pool.status();
+    /// assert_eq!(4, status.capacity);
+    /// assert_eq!(3, status.available);
+    /// assert_eq!(1, status.in_use);
+    /// assert_eq!(4, status.created);
+    /// assert_eq!(2, status.recycled);
+    /// ```
+    pub fn status(&self) -> PoolStatus {
+        // The capacity only changes through the owner, so the checked out
+        // entries are never more than it
+        let capacity = self.capacity();
+        let in_use = cmp::min(self.in_use(), capacity);
+
+        PoolStatus {
+            capacity,
+            available: capacity - in_use,
+            in_use,
+            waiters: self.inner.waiters.load(Ordering::Relaxed),
+            created: self.inner.created.load(Ordering::Relaxed),
+            recycled: self.inner.recycled.load(Ordering::Relaxed),
+            failed_checkouts: self.inner.failed.load(Ordering::Relaxed),
+            waits: self.inner.waits.load(Ordering::Relaxed),
+            wait_time: *self.inner.wait_time.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    /// Returns a receiver of the pool's lifecycle events.
+    ///
+    /// Each call returns a new receiver that gets every event from then on.
+    /// Events are buffered, a receiver that falls behind by more than 1024
+    /// events misses the following ones until it catches up. Dropping the
+    /// receiver unsubscribes it.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty, PoolEvent};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    /// let events = pool.events();
+    ///
+    /// let val = pool.checkout();
+    /// assert!(pool.try_checkout().is_none());
+    /// drop(val);
+    ///
+    /// assert_eq!(PoolEvent::Exhausted, events.try_recv().unwrap());
+    ///
+    /// match events.try_recv().unwrap() {
+    ///     PoolEvent::Recovered { after } => println!("exhausted for {:?}", after),
+    ///     event => panic!("unexpected event {:?}", event),
+    /// }
+    /// ```
+    pub fn events(&self) -> Receiver<PoolEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Returns how long values have been held checked out, or `None` if
+    /// none has been checked back in yet or `Builder::track_hold_time` was
+    /// not set.
+    ///
+    /// Covers every value checked back in since the pool was created,
+    /// including transient values and those checked out by child pools.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(4)
+    ///     .track_hold_time()
+    ///     .build(|| Dirty(()));
+    ///
+    /// drop(pool.checkout());
+    ///
+    /// let times = pool.hold_times().unwrap();
+    /// assert_eq!(1, times.count);
+    /// ```
+    pub fn hold_times(&self) -> Option<HoldTimes> {
+        let buckets: Vec<usize> = self.inner.hold_times.iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+
+        HoldTimes::new(&buckets)
+    }
+
+    /// Returns a handle to the pool that doesn't keep it alive, for values,
+    /// or observers, that need to check out of the pool they belong to
+    /// without keeping its memory around forever.
+    ///
+    /// The pool stays alive until it and all of its checkouts and child
+    /// pools are dropped, after which `WeakPool::upgrade` returns `None`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let pool = Pool::with_capacity(2, 0, || Dirty(0));
+    /// let weak = pool.downgrade();
+    ///
+    /// assert!(weak.upgrade().unwrap().try_checkout().is_some());
+    ///
+    /// drop(pool);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn downgrade(&self) -> WeakPool<T, E> {
+        WeakPool {
+            inner: Arc::downgrade(&self.inner),
+            _p: PhantomData,
+        }
+    }
+
+    /// Consumes the pool, returning every value it holds in slot order.
+    ///
+    /// This only succeeds when no values are checked out, otherwise the pool
+    /// is handed back unchanged.
+    pub fn try_into_values(self) -> Result<Vec<T>, Pool<T, E>> {
+        let mut inner = match Arc::try_unwrap(self.inner) {
+            Ok(inner) => inner,
+            Err(inner) => return Err(Pool { inner, _p: PhantomData }),
+        };
+
+        Ok(inner.take_values())
+    }
+}
+
+impl<T: Reset, E: Extra> Extend<T> for Pool<T, E> {
+    /// Adds the values to the pool as new entries.
+    ///
+    /// The entries are placed in a newly allocated segment, existing entries
+    /// are never moved.
+    fn extend<I>(&mut self, values: I)
+            where I: IntoIterator<Item = T> {
+        let values: Vec<T> = values.into_iter().collect();
+
+        unsafe { self.inner.put(values.into_iter().map(|value| (value, &[][..]))); }
+    }
+}
+
+impl<T: Reset, E: Extra> IntoIterator for Pool<T, E> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    /// Consumes the pool, yielding its idle values, for shutdown code that
+    /// closes them one by one.
+    ///
+    /// Values still checked out are not yielded. They are dropped along with
+    /// the pool once the last of them is checked in, see `try_into_values`
+    /// to make sure there are none.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(3, 0, || Dirty(String::from("conn")));
+    /// let busy = pool.checkout();
+    ///
+    /// let mut closed = 0;
+    ///
+    /// for conn in pool {
+    ///     assert_eq!("conn", *conn);
+    ///     closed += 1;
+    /// }
+    ///
+    /// assert_eq!(2, closed);
+    /// ```
+    fn into_iter(self) -> vec::IntoIter<T> {
+        match self.try_into_values() {
+            Ok(values) => values.into_iter(),
+            Err(mut pool) => pool.drain_idle().into_iter(),
+        }
+    }
+}
+
+// Sending the pool moves its idle values to another thread. Shared access
+// only hands out `&T`, through `get`.
+unsafe impl<T: Send + Reset, E: Extra> Send for Pool<T, E> { }
+unsafe impl<T: Send + Sync + Reset, E: Extra> Sync for Pool<T, E> { }
+
+/// A handle to a pool that doesn't keep it alive, created by
+/// `Pool::downgrade`.
+pub struct WeakPool<T: Reset, E: Extra = u8> {
+    inner: Weak<PoolInner<T>>,
+    _p: PhantomData<E>,
+}
+
+impl<T: Reset, E: Extra> WeakPool<T, E> {
+    /// Returns a pool to check out values of the original pool from, or
+    /// `None` if the pool is gone.
+    ///
+    /// Only the owner of a pool checks out of it directly, so the returned
+    /// pool is a child without entries of its own, see `Builder::parent`,
+    /// which keeps the original pool alive for as long as it is around.
+    pub fn upgrade(&self) -> Option<Pool<T, E>> {
+        self.inner.upgrade().map(Pool::child)
+    }
+}
+
+impl<T: Reset, E: Extra> Clone for WeakPool<T, E> {
+    fn clone(&self) -> WeakPool<T, E> {
+        WeakPool {
+            inner: self.inner.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+// Upgrading only ever hands out new pools, which move values to the thread
+// they are upgraded on.
+unsafe impl<T: Send + Reset, E: Extra> Send for WeakPool<T, E> { }
+unsafe impl<T: Send + Reset, E: Extra> Sync for WeakPool<T, E> { }
+
+/// The priority of a checkout, see `Pool::checkout_priority`.
+///
+/// Waiting high priority checkouts are served before normal ones, and only
+/// they may take the entries held back with `Builder::reserve_high_priority`,
+/// so that health checks and the like are not starved by bulk work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// The priority of `Pool::checkout` and friends.
+    #[default]
+    Normal,
+    /// Served first.
+    High,
+}
+
+/// Identifies a value parked in a pool, see `Pool::park`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CheckoutId {
+    idx: usize,
+    generation: usize,
+}
+
+/// Distribution of the high-water marks of the extra bytes used by a pool's
+/// entries, returned by `Pool::extra_usage`.
+///
+/// An entry's high-water mark is the offset just past the last non-zero extra
+/// byte seen when it was checked in. Bytes only ever set to zero count as
+/// unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtraUsage {
+    /// Number of entries
+    pub entries: usize,
+    /// Number of extra bytes available to each entry
+    pub available: usize,
+    /// Smallest high-water mark
+    pub min: usize,
+    /// Median high-water mark
+    pub p50: usize,
+    /// 90th percentile high-water mark
+    pub p90: usize,
+    /// 99th percentile high-water mark
+    pub p99: usize,
+    /// Largest high-water mark
+    pub max: usize,
+    /// Mean high-water mark, rounded down
+    pub mean: usize,
+}
+
+impl ExtraUsage {
+    fn new(used: &mut [usize], available: usize) -> Option<ExtraUsage> {
+        if used.is_empty() {
+            return None;
+        }
+
+        used.sort_unstable();
+
+        let n = used.len();
+        let percentile = |p: usize| used[(n - 1) * p / 100];
+
+        Some(ExtraUsage {
+            entries: n,
+            available,
+            min: used[0],
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: used[n - 1],
+            mean: used.iter().sum::<usize>() / n,
+        })
+    }
+}
+
+/// An idle entry of a pool, handed out by `Pool::for_each_idle`.
+pub struct IdleEntry<'a, T> {
+    entry: &'a Entry<T>,
+}
+
+impl<'a, T> IdleEntry<'a, T> {
+    /// Returns the index of the entry in the pool.
+    pub fn index(&self) -> usize {
+        self.entry.idx
+    }
+
+    /// Returns the value held by the entry, or `None` if it is empty and
+    /// waits to be filled on checkout.
+    pub fn value(&self) -> Option<&'a T> {
+        if self.entry.init {
+            Some(self.entry.data())
+        } else {
+            None
+        }
+    }
+
+    /// Returns when the value was created, if the pool was built with
+    /// `Builder::track_idle`.
+    pub fn created_at(&self) -> Option<Instant> {
+        self.entry.created().get()
+    }
+
+    /// Returns when the entry was last checked in, if the pool was built
+    /// with `Builder::track_idle`. `None` if it was never checked out.
+    pub fn last_used(&self) -> Option<Instant> {
+        self.entry.checked_in().get()
+    }
+}
+
+impl<'a, T> fmt::Debug for IdleEntry<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("IdleEntry")
+            .field("index", &self.index())
+            .field("created_at", &self.created_at())
+            .field("last_used", &self.last_used())
+            .finish()
+    }
+}
+
+/// A snapshot of the state of a pool, returned by `Pool::status`.
+///
+/// The figures are read one after the other while checkins may go on
+/// concurrently, but `available` and `in_use` always add up to `capacity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Number of entries
+    pub capacity: usize,
+    /// Number of entries not checked out
+    pub available: usize,
+    /// Number of entries checked out, including by child pools, but not
+    /// transient values
+    pub in_use: usize,
+    /// Number of checkouts waiting for a value, blocked or polled
+    pub waiters: usize,
+    /// Number of values created by the initializer, including transient
+    /// values and values created again after `Pool::drain_idle`
+    pub created: usize,
+    /// Number of checkouts that reused a value
+    pub recycled: usize,
+    /// Number of checkouts that found the pool depleted, including polls
+    /// that had to wait
+    pub failed_checkouts: usize,
+    /// Number of times a checkout waited for a value, blocked or polled
+    pub waits: usize,
+    /// Total time spent waiting. Polled checkouts count until they are
+    /// woken.
+    pub wait_time: Duration,
+}
+
+/// How long values have been held checked out, returned by
+/// `Pool::hold_times`.
+///
+/// Durations are counted in buckets by powers of two, and each figure is the
+/// upper bound of its bucket. They overestimate the actual durations by up to
+/// a factor of two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoldTimes {
+    /// Number of checkouts checked back in
+    pub count: usize,
+    /// Median hold time
+    pub p50: Duration,
+    /// 90th percentile hold time
+    pub p90: Duration,
+    /// 99th percentile hold time
+    pub p99: Duration,
+    /// Longest hold time
+    pub max: Duration,
+}
+
+impl HoldTimes {
+    fn new(buckets: &[usize]) -> Option<HoldTimes> {
+        let count: usize = buckets.iter().sum();
+
+        if count == 0 {
+            return None;
+        }
+
+        // Upper bound of the bucket holding the checkout of the given rank
+        let at_rank = |rank: usize| {
+            let mut seen = 0;
+
+            let bucket = buckets.iter().position(|&n| {
+                seen += n;
+                seen > rank
+            }).unwrap();
+
+            match bucket {
+                0 => Duration::from_nanos(0),
+                bucket => Duration::from_nanos(u64::MAX >> (u64::BITS - bucket as u32)),
+            }
+        };
+
+        let percentile = |p: usize| at_rank((count - 1) * p / 100);
+
+        Some(HoldTimes {
+            count,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: at_rank(count - 1),
+        })
+    }
+}
+
+/// A handle to a checked out value. When dropped out of scope, the value will
+/// be returned to the pool.
+///
+/// Entries never move, not even when the pool grows, so the addresses of the
+/// value and its extra storage are stable for as long as it is checked out.
+/// See `into_pin` for pinning the value.
+pub struct Checkout<T, E: Extra = u8> {
+    entry: *mut Entry<T>,
+    inner: Arc<PoolInner<T>>,
+    _p: PhantomData<E>,
+}
+
+impl<T, E: Extra> Checkout<T, E> {
+    /// Read access to the extra storage
+    pub fn extra(&self) -> &[E] {
+        extra::from_bytes(self.entry().extra())
+    }
+
+    /// Write access to the extra storage
+    pub fn extra_mut(&mut self) -> &mut [E] {
+        extra::from_bytes_mut(self.entry_mut().extra_mut())
+    }
+
+    /// Returns a bump allocator over the extra storage, starting from the
+    /// first byte. See `Bump`.
+    pub fn bump(&mut self) -> Bump<'_> {
+        Bump::new(self.entry_mut().extra_mut())
+    }
+
+    /// Returns an arena over the extra storage, starting from the first byte.
+    /// See `Arena`.
+    pub fn arena(&mut self) -> Arena<'_> {
+        Arena::new(self.entry_mut().extra_mut())
+    }
+
+    /// Read access to the region of the extra storage with the given name,
+    /// see `Builder::region`.
+    pub fn region(&self, name: &str) -> Option<&[u8]> {
+        let region = self.inner.regions.iter().find(|region| region.name == name)?;
+        Some(&self.entry().extra()[region.offset..][..region.len])
+    }
+
+    /// Write access to the region of the extra storage with the given name.
+    pub fn region_mut(&mut self, name: &str) -> Option<&mut [u8]> {
+        let region = *self.inner.regions.iter().find(|region| region.name == name)?;
+        Some(&mut self.entry_mut().extra_mut()[region.offset..][..region.len])
+    }
+
+    /// Read access to the region of the extra storage with the given index,
+    /// in the order the regions were added to the builder.
+    pub fn region_at(&self, idx: usize) -> Option<&[u8]> {
+        let region = self.inner.regions.get(idx)?;
+        Some(&self.entry().extra()[region.offset..][..region.len])
+    }
+
+    /// Write access to the region of the extra storage with the given index.
+    pub fn region_at_mut(&mut self, idx: usize) -> Option<&mut [u8]> {
+        let region = *self.inner.regions.get(idx)?;
+        Some(&mut self.entry_mut().extra_mut()[region.offset..][..region.len])
+    }
+
+    /// Write access to all regions of the extra storage at once.
+    pub fn regions_mut(&mut self) -> RegionsMut<'_> {
+        let extra: *mut [u8] = self.entry_mut().extra_mut();
+        RegionsMut::new(&self.inner.regions, unsafe { &mut *extra })
+    }
+
+    /// Returns a raw pointer to the checked out value.
+    ///
+    /// The pointer is valid for as long as the value is checked out, see
+    /// `into_raw` for handing it to code that outlives the checkout. It may
+    /// be written through as long as the checkout is not used meanwhile.
+    pub fn data_ptr(&self) -> *mut T {
+        // Raw all the way, so the pointer stays usable alongside the checkout
+        self.entry as *mut T
+    }
+
+    /// Returns a raw pointer to the start of the extra storage, which holds
+    /// `extra().len()` values.
+    ///
+    /// The same rules as for `data_ptr` apply.
+    pub fn extra_ptr(&self) -> *mut E {
+        unsafe { (self.entry as *mut u8).add(mem::size_of::<Entry<T>>()) as *mut E }
+    }
+
+    /// Returns the extra storage as an `IoSlice`, for vectored writes. See
+    /// `io_slices` for several checkouts at once.
+    pub fn io_slice(&self) -> IoSlice<'_> {
+        IoSlice::new(self.entry().extra())
+    }
+
+    /// Returns the extra storage as an `IoSliceMut`, for vectored reads.
+    pub fn io_slice_mut(&mut self) -> IoSliceMut<'_> {
+        IoSliceMut::new(self.entry_mut().extra_mut())
+    }
+
+    /// Returns whether both checkouts refer to the same entry.
+    ///
+    /// Two checkouts alive at the same time never do, but a value checked
+    /// out again after being checked in, or unparked, does. Transient
+    /// values each have an entry of their own.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(2, 0, || Dirty(0));
+    ///
+    /// let a = pool.checkout();
+    /// let b = pool.checkout();
+    /// assert!(a.same_slot(&a));
+    /// assert!(!a.same_slot(&b));
+    /// ```
+    pub fn same_slot(&self, other: &Checkout<T, E>) -> bool {
+        ptr::eq(self.entry, other.entry)
+    }
+
+    /// Returns the index of the value's extra bytes in the table returned by
+    /// `Pool::iovecs`, for `io_uring` fixed buffer operations such as
+    /// `IORING_OP_READ_FIXED`.
+    ///
+    /// Returns `None` for transient values, and for slots too large for
+    /// `io_uring` buffer indices.
+    #[cfg(unix)]
+    pub fn buf_index(&self) -> Option<u16> {
+        match self.slot() {
+            slot if slot <= u16::MAX as usize => Some(slot as u16),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the value's entry within the pool it belongs
+    /// to, the same as `IdleEntry::index`, for use as a compact token in
+    /// logs or side tables.
+    ///
+    /// Values checked out through a child pool have the index of their
+    /// entry in the parent. Transient values have no entry in the pool and
+    /// return `usize::MAX`.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    ///
+    /// let val = pool.checkout();
+    /// assert!(val.slot() < 4);
+    /// ```
+    pub fn slot(&self) -> usize {
+        match self.entry().idx {
+            NIL => usize::MAX,
+            idx => idx,
+        }
+    }
+
+    /// Consumes the checkout, returning a raw pointer to the value.
+    ///
+    /// The value stays checked out, and its address stable, until the
+    /// pointer is turned back into a checkout with `from_raw`. This is meant
+    /// for handing pooled values to foreign code that holds on to them.
+    ///
+    /// ```
+    /// use pool::{Pool, Checkout, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    ///
+    /// let ptr = pool.checkout().into_raw();
+    /// unsafe { (*ptr).0 = 7; }
+    /// assert!(pool.try_checkout().is_none());
+    ///
+    /// drop(unsafe { Checkout::<_>::from_raw(ptr) });
+    /// assert_eq!(7, **pool.checkout());
+    /// ```
+    pub fn into_raw(self) -> *mut T {
+        let checkout = mem::ManuallyDrop::new(self);
+        let entry = checkout.entry;
+
+        // The entry keeps the checkout's reference to the pool
+        unsafe { (*Entry::track(entry)).owner = Arc::into_raw(ptr::read(&checkout.inner)); }
+
+        entry as *mut T
+    }
+
+    /// Turns a pointer returned by `into_raw` back into a checkout.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `into_raw` on a checkout with the
+    /// same type of extra storage, and must only be turned back once.
+    pub unsafe fn from_raw(ptr: *mut T) -> Checkout<T, E> {
+        let entry = ptr as *mut Entry<T>;
+        let owner = mem::replace(&mut (*Entry::track(entry)).owner, ptr::null());
+
+        Checkout {
+            entry,
+            inner: Arc::from_raw(owner),
+            _p: PhantomData,
+        }
+    }
+
+    /// Pins the checked out value, for values such as futures that must not
+    /// move once in use.
+    ///
+    /// A pinned value is never moved again: instead of being reused, it is
+    /// dropped in place when checked back in, and its entry is filled with a
+    /// new value by the pool's initializer on its next checkout. The extra
+    /// storage is no longer accessible through the pinned checkout.
+    ///
+    /// A pinned checkout of a future is a future itself, like `Pin<Box<_>>`,
+    /// for futures that are not `Unpin`, see `impl Future for Checkout`.
+    ///
+    /// ```
+    /// use pool::{Pool, Reset};
+    /// use std::marker::PhantomPinned;
+    /// use std::pin::Pin;
+    ///
+    /// struct Machine {
+    ///     state: u32,
+    ///     _pin: PhantomPinned,
+    /// }
+    ///
+    /// impl Machine {
+    ///     fn step(self: Pin<&mut Self>) -> u32 {
+    ///         let this = unsafe { self.get_unchecked_mut() };
+    ///         this.state += 1;
+    ///         this.state
+    ///     }
+    /// }
+    ///
+    /// impl Reset for Machine {
+    ///     fn reset(&mut self) {}
+    /// }
+    ///
+    /// let mut pool = Pool::with_capacity(4, 0, || Machine { state: 0, _pin: PhantomPinned });
+    /// let mut machine = pool.checkout().into_pin();
+    ///
+    /// assert_eq!(1, machine.as_mut().step());
+    /// assert_eq!(2, machine.as_mut().step());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no initializer, such as one created with
+    /// `Pool::from_vec` or `Builder::build_parallel`.
+    pub fn into_pin(self) -> Pin<Checkout<T, E>> {
+        assert!(self.inner.init.is_some(), "pinning requires a pool with an initializer");
+
+        unsafe {
+            (*self.entry).retire = true;
+            Pin::new_unchecked(self)
+        }
+    }
+
+    /// Drops the value and returns its entry to the pool, to be filled with a
+    /// new value by the pool's initializer on its next checkout.
+    ///
+    /// Use this to take a value that turned out to be broken, such as a
+    /// closed connection, out of rotation. The value is not reset and no
+    /// later checkout sees it.
+    ///
+    /// The value is dropped as soon as the checkout is, so a value owning a
+    /// file descriptor, such as a `File`, `TcpStream` or `OwnedFd`, has it
+    /// closed right away rather than handed out again.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(vec![1, 2, 3]));
+    ///
+    /// let mut conn = pool.checkout();
+    /// conn.clear();
+    /// conn.retire();
+    ///
+    /// assert_eq!(vec![1, 2, 3], **pool.checkout());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no initializer, such as one created with
+    /// `Pool::from_vec` or `Builder::build_parallel`.
+    pub fn retire(self) {
+        assert!(self.inner.init.is_some(), "retiring requires a pool with an initializer");
+
+        unsafe { (*self.entry).retire = true; }
+    }
+
+    /// Returns true if the lease of the checkout expired and was revoked,
+    /// see `Builder::lease`.
+    ///
+    /// The value is still the checkout's to use, but it is dropped instead
+    /// of reused once the checkout is dropped.
+    pub fn is_revoked(&self) -> bool {
+        self.entry().lease() == REVOKED
+    }
+
+    /// Extends the lease of the checkout to expire `timeout` from now, see
+    /// `Builder::lease`. Returns false if the lease was revoked already.
+    ///
+    /// Lets long operations keep their value without sizing the lease of
+    /// every checkout for the longest one. Transient values are not leased,
+    /// renewing them does nothing.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    /// use std::time::Duration;
+    ///
+    /// let mut pool = Pool::builder()
+    ///     .capacity(1)
+    ///     .lease(Duration::from_secs(1))
+    ///     .build(|| Dirty(0));
+    ///
+    /// let mut val = pool.checkout();
+    /// assert!(val.renew(Duration::from_secs(60)));
+    /// assert!(val.lease_remaining().unwrap() > Duration::from_secs(1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool does not lease checkouts.
+    pub fn renew(&mut self, timeout: Duration) -> bool {
+        assert!(self.inner.lease.is_some(), "renewing requires a pool with leases");
+
+        let deadline = self.inner.lease_clock(timeout);
+        // Not leased if never checked out while the pool leased checkouts
+        let renewed = self.entry().tracked().map_or(Err(0), |track| {
+            track.lease.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |lease| match lease {
+                0 | REVOKED => None,
+                _ => Some(deadline),
+            })
+        });
+
+        renewed != Err(REVOKED)
+    }
+
+    /// Returns the time left until the lease of the checkout expires, zero
+    /// once expired or revoked, see `Builder::lease`.
+    ///
+    /// Returns `None` if the checkout is not leased.
+    pub fn lease_remaining(&self) -> Option<Duration> {
+        match self.entry().lease() {
+            0 => None,
+            REVOKED => Some(Duration::from_secs(0)),
+            deadline => {
+                let now = self.inner.lease_clock(Duration::from_secs(0));
+                Some(Duration::from_millis(deadline.saturating_sub(now) as u64))
+            }
+        }
+    }
+
+    /// Turns the checkout into a handle that can be cloned to share read
+    /// access to the value.
+    ///
+    /// The value is returned to the pool once the last clone is dropped.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    ///
+    /// let a = pool.checkout().share();
+    /// let b = a.clone();
+    ///
+    /// drop(a);
+    /// assert!(pool.try_checkout().is_none());
+    ///
+    /// drop(b);
+    /// assert!(pool.try_checkout().is_some());
+    /// ```
+    pub fn share(self) -> SharedCheckout<T, E> {
+        let checkout = mem::ManuallyDrop::new(self);
+
+        unsafe { (*Entry::track(checkout.entry)).shared.store(1, Ordering::Relaxed); }
+
+        SharedCheckout {
+            entry: checkout.entry,
+            inner: unsafe { ptr::read(&checkout.inner) },
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns how many times the value was checked out before this
+    /// checkout.
+    ///
+    /// The count starts over whenever the pool creates a new value for the
+    /// entry, so it tells how long the value has been in use.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    ///
+    /// assert_eq!(0, pool.checkout().reuse_count());
+    /// assert_eq!(1, pool.checkout().reuse_count());
+    /// ```
+    pub fn reuse_count(&self) -> usize {
+        self.entry().uses - 1
+    }
+
+    /// Returns the weight of the value, as given by `Builder::weight`, or
+    /// one if the pool has no weight function.
+    pub fn weight(&self) -> usize {
+        self.inner.weight.as_ref().map_or(1, |weight| weight(self.entry().data()))
+    }
+
+    /// Returns when the value was created, if the pool was built with
+    /// `Builder::track_idle`.
+    pub fn created_at(&self) -> Option<Instant> {
+        self.entry().created().get()
+    }
+
+    /// Returns when the entry was last checked in, before this checkout, if
+    /// the pool was built with `Builder::track_idle`. `None` if it was never
+    /// checked out before.
+    pub fn last_used(&self) -> Option<Instant> {
+        self.entry().checked_in().get()
+    }
+
+    fn entry(&self) -> &Entry<T> {
+        unsafe { &*self.entry }
+    }
+
+    fn entry_mut(&mut self) -> &mut Entry<T> {
+        unsafe { &mut *self.entry }
+    }
+}
+
+impl<T: Reset, E: Extra> Checkout<T, E> {
+    /// Checks the value in to `target` instead of the pool it came from,
+    /// for example to demote a buffer from a hot pool to a cold one.
+    ///
+    /// Entries cannot move between pools, so the value and the extra bytes
+    /// are swapped with those of an entry checked out of `target`, which is
+    /// then checked back in, and the checkout takes its old value back to its
+    /// own pool. The entry is checked out like `Pool::try_checkout` does, so
+    /// entries held back by `Builder::reserve_high_priority` are left alone, and
+    /// `target` may grow as set by `Builder::on_empty`.
+    ///
+    /// The checkout is returned unchanged if `target` has no entry to give,
+    /// or if its entries have a different amount or alignment of extra
+    /// storage.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut hot = Pool::with_capacity(1, 0, || Dirty(vec![0u8; 16]));
+    /// let mut cold = Pool::with_capacity(1, 0, || Dirty(vec![]));
+    ///
+    /// let mut buf = hot.checkout();
+    /// buf[0] = 1;
+    /// assert!(buf.transfer(&mut cold).is_ok());
+    ///
+    /// assert_eq!(1, cold.checkout()[0]);
+    /// assert!(hot.checkout().is_empty());
+    /// ```
+    pub fn transfer(self, target: &mut Pool<T, E>) -> Result<(), Checkout<T, E>> {
+        if !self.inner.same_layout(&target.inner) {
+            return Err(self);
+        }
+
+        let other = match unsafe { target.inner.checkout(Wait::Never, Priority::Normal) } {
+            Some((ptr, owner)) => Checkout::<T, E> {
+                entry: ptr,
+                inner: owner.clone(),
+                _p: PhantomData,
+            },
+            None => return Err(self),
+        };
+
+        // A transient value would be dropped on checkin, along with this one.
+        // The entry may also come from a parent of `target`, laid out
+        // differently.
+        if other.entry().idx == NIL || !self.inner.same_layout(&other.inner) {
+            return Err(self);
+        }
+
+        unsafe {
+            Entry::swap_values(self.entry, other.entry);
+        }
+
+        // Checked in like any other checkout of `target`
+        drop(other);
+        Ok(())
+    }
+
+    /// Returns a pool to check out more values from the pool this value was
+    /// checked out from, for code that was only handed the checkout.
+    ///
+    /// Only the owner of a pool checks out of it directly, so the returned
+    /// pool is a child without entries of its own, see `Builder::parent`. It
+    /// takes the idle entries of this value's pool, and of its parents, and
+    /// keeps them alive for as long as it is around.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(2, 0, || Dirty(0));
+    /// let val = pool.checkout();
+    ///
+    /// let mut other = val.pool();
+    /// assert!(other.try_checkout().is_some());
+    /// assert_eq!(0, other.capacity());
+    /// ```
+    ///
+    /// Values that cannot be sent to another thread cannot be checked out
+    /// there either, even through a checkout shared with that thread:
+    ///
+    /// ```compile_fail
+    /// use pool::{Pool, Dirty};
+    /// use std::sync::MutexGuard;
+    /// use std::thread;
+    ///
+    /// let mut pool: Pool<Dirty<Option<MutexGuard<'static, i32>>>> =
+    ///     Pool::with_capacity(2, 0, || Dirty(None));
+    /// let val = pool.checkout();
+    ///
+    /// thread::scope(|scope| {
+    ///     scope.spawn(|| drop(val.pool().try_checkout()));
+    /// });
+    /// ```
+    pub fn pool(&self) -> Pool<T, E>
+            where T: Send {
+        Pool::child(self.inner.clone())
+    }
+}
+
+impl<T, E: Extra> ops::Deref for Checkout<T, E> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.entry().data()
+    }
+}
+
+impl<T, E: Extra> ops::DerefMut for Checkout<T, E> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.entry_mut().data_mut()
+    }
+}
+
+// Compared and hashed by value, like `Box`
+
+impl<T: PartialEq, E: Extra> PartialEq for Checkout<T, E> {
+    fn eq(&self, other: &Checkout<T, E>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, E: Extra> Eq for Checkout<T, E> { }
+
+impl<T: PartialOrd, E: Extra> PartialOrd for Checkout<T, E> {
+    fn partial_cmp(&self, other: &Checkout<T, E>) -> Option<cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, E: Extra> Ord for Checkout<T, E> {
+    fn cmp(&self, other: &Checkout<T, E>) -> cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, E: Extra> Hash for Checkout<T, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: fmt::Display, E: Extra> fmt::Display for Checkout<T, E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(fmt)
+    }
+}
+
+impl<T: fmt::Debug, E: Extra> fmt::Debug for Checkout<T, E> {
+    /// Shows the slot of the value, see `slot`, and how long it has been
+    /// checked out if the pool was built with `Builder::track_hold_time`,
+    /// along with the value.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Checkout")
+            .field("slot", &self.slot())
+            .field("held_for", &self.entry().checked_out().get().map(|at| at.elapsed()))
+            .field("value", &**self)
+            .finish()
+    }
+}
+
+// The value lives in the pool, not in the checkout, like with `Box`
+impl<T, E: Extra> Unpin for Checkout<T, E> { }
+
+impl<T: Future + Unpin, E: Extra> Future for Checkout<T, E> {
+    type Output = T::Output;
+
+    /// Polls the value, for pools of futures that are reused rather than
+    /// allocated each time. Futures that are not `Unpin` are polled through
+    /// a pinned checkout, see `into_pin`.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T::Output> {
+        Pin::new(&mut **self).poll(cx)
+    }
+}
+
+// For pools of files, sockets and the like
+
+#[cfg(unix)]
+impl<T: AsRawFd, E: Extra> AsRawFd for Checkout<T, E> {
+    fn as_raw_fd(&self) -> RawFd {
+        (**self).as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl<T: AsFd, E: Extra> AsFd for Checkout<T, E> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        (**self).as_fd()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, E: Extra> serde::Serialize for Checkout<T, E> {
+    /// Serializes the value, as if it wasn't pooled.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, E: Extra> AsRef<T> for Checkout<T, E> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T, E: Extra> AsMut<T> for Checkout<T, E> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T, E: Extra> Borrow<T> for Checkout<T, E> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, E: Extra> BorrowMut<T> for Checkout<T, E> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T, E: Extra> Drop for Checkout<T, E> {
+    fn drop(&mut self) {
+        self.inner.checkin(self.entry);
+    }
+}
+
+// The value is checked back in from whichever thread drops the checkout, and
+// a shared checkout only hands out `&T`.
+unsafe impl<T: Send, E: Extra> Send for Checkout<T, E> { }
+unsafe impl<T: Sync, E: Extra> Sync for Checkout<T, E> { }
+
+/// A shared handle to a checked out value, created by `Checkout::share`.
+///
+/// Cloning the handle is cheap and only gives read access to the value. The
+/// value is returned to the pool when the last clone is dropped.
+pub struct SharedCheckout<T, E: Extra = u8> {
+    entry: *mut Entry<T>,
+    inner: Arc<PoolInner<T>>,
+    _p: PhantomData<E>,
+}
+
+impl<T, E: Extra> SharedCheckout<T, E> {
+    /// Read access to the extra storage
+    pub fn extra(&self) -> &[E] {
+        extra::from_bytes(self.entry().extra())
+    }
+
+    /// Returns whether both handles refer to the same entry, as clones of
+    /// one another do.
+    pub fn same_slot(&self, other: &SharedCheckout<T, E>) -> bool {
+        ptr::eq(self.entry, other.entry)
+    }
+
+    /// Turns the handle back into an exclusive checkout if it is the only
+    /// one left, otherwise returns it unchanged.
+    ///
+    /// ```
+    /// use pool::{Pool, Dirty};
+    ///
+    /// let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    ///
+    /// let a = pool.checkout().share();
+    /// let b = a.clone();
+    ///
+    /// let a = a.try_upgrade().err().unwrap();
+    /// drop(b);
+    ///
+    /// let mut val = a.try_upgrade().ok().unwrap();
+    /// **val = 1;
+    /// ```
+    pub fn try_upgrade(self) -> Result<Checkout<T, E>, SharedCheckout<T, E>> {
+        // With a single handle left, nobody else can clone it concurrently
+        if self.handles().compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return Err(self);
+        }
+
+        let shared = mem::ManuallyDrop::new(self);
+
+        Ok(Checkout {
+            entry: shared.entry,
+            inner: unsafe { ptr::read(&shared.inner) },
+            _p: PhantomData,
+        })
+    }
+
+    fn entry(&self) -> &Entry<T> {
+        unsafe { &*self.entry }
+    }
+
+    // Number of handles to the entry, tracked from `Checkout::share` on
+    fn handles(&self) -> &AtomicUsize {
+        &self.entry().tracked().unwrap().shared
+    }
+}
+
+impl<T, E: Extra> Clone for SharedCheckout<T, E> {
+    fn clone(&self) -> SharedCheckout<T, E> {
+        self.handles().fetch_add(1, Ordering::Relaxed);
+
+        SharedCheckout {
+            entry: self.entry,
+            inner: self.inner.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T, E: Extra> ops::Deref for SharedCheckout<T, E> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.entry().data()
+    }
+}
+
+impl<T, E: Extra> Drop for SharedCheckout<T, E> {
+    fn drop(&mut self) {
+        if self.handles().fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // Make sure all reads through other handles are done before the
+        // entry is handed out again
+        fence(Ordering::Acquire);
+        self.inner.checkin(self.entry);
+    }
+}
+
+// Any clone may be the one to check the value back in, and clones hand out
+// `&T` to their threads concurrently.
+unsafe impl<T: Send + Sync, E: Extra> Send for SharedCheckout<T, E> { }
+unsafe impl<T: Send + Sync, E: Extra> Sync for SharedCheckout<T, E> { }
+
+struct PoolInner<T> {
+    // Hot, written by every checkout and checkin. Each gets its own cache line
+    // so that writes don't invalidate the read-mostly fields below.
+    next: CachePadded<AtomicUsize>,  // Index of next available entry (hot tier)
+    cold: CachePadded<AtomicUsize>,  // Index of next available cold entry
+    recent: CachePadded<AtomicUsize>, // Index of the last entry checked in, in LRU order
+    spare: AtomicUsize,     // Index of the first entry set aside after its lease was revoked
+    spare_len: AtomicUsize, // Number of entries set aside, not counted in the capacity
+    hot_len: CachePadded<AtomicUsize>, // Number of entries in the hot tier
+    elimination: CachePadded<[AtomicUsize; ELIMINATION_SLOTS]>, // Entries handed off directly
+    waiters: CachePadded<AtomicUsize>, // Number of checkouts waiting on an empty pool
+    in_use: CachePadded<AtomicUsize>, // Number of entries checked out, transients aside
+    hold_times: Vec<AtomicUsize>, // Checkouts by bit length of their duration in ns, if tracked
+    created: AtomicUsize,  // Number of values created by the initializer
+    recycled: AtomicUsize, // Number of checkouts that reused a value
+    failed: AtomicUsize,   // Number of checkouts that found the pool depleted
+    events: Events,        // Receivers of lifecycle events
+    low_watermark: Option<Watermark>, // Reports availability dropping low
+    auto_grow: Option<AutoGrow>, // Grows ahead of exhaustion
+    auto_shrink: Option<AutoShrink>, // Retires surplus values
+    adaptive: Option<Adaptive>, // Sizes the pool for a target utilization
+    on_exhausted: Option<ExhaustionHook>, // Called on failed checkouts
+    lease: Option<Duration>, // How long checkouts are leased for
+    clock: Instant, // Start of the lease clock
+    budget: Option<Budget<T>>, // Caps the bytes held by the pool
+
+    // Waiting checkouts queue a waker, to be woken one per checkin. Blocked
+    // ones spin for a while first, then park their thread behind a waker
+    // that unparks it.
+    wakers: Mutex<VecDeque<Waiter>>, // High priority ones first
+    spins: UnsafeCell<usize>, // Times a blocked checkout looks before parking
+    waits: AtomicUsize, // Number of checkouts that waited for a value
+    wait_time: Mutex<Duration>, // Total time spent waiting
+
+    // Only accessed by the owner of the `Pool`, or once the pool is gone.
+    // Checkins never touch the segments, they work from the entry pointer.
+    segments: UnsafeCell<Vec<Segment>>,
+    tables: UnsafeCell<Vec<Vec<*mut u8>>>, // Every segment table so far, the last is current
+    vacant: UnsafeCell<Vec<usize>>, // Emptied entries kept off the free list
+
+    // Pointers to the first entry of each segment, used to find an entry by
+    // its index. When full, it is replaced by a larger copy rather than
+    // reallocated, so it can be read while the owner adds segments.
+    table: AtomicPtr<*mut u8>,
+
+    init: Option<Initializer<T>>, // Creates values for empty entries
+
+    // Cold, only read after construction
+    parent: Option<Arc<PoolInner<T>>>, // Checked out from when out of entries
+    reset: Option<ResetFn<T>>, // Resets values with the pool's context
+    wipe: Option<Wipe<T>>,     // Wipes values on checkin
+    weight: Option<WeightFn<T>>, // Weighs values for weighted checkouts
+    lock_memory: bool,  // Lock segments into RAM
+    track_idle: bool,   // Record when entries are checked in
+    huge_pages: bool,   // Ask for segments to be backed by huge pages
+    track_extra: bool,  // Track how much of the extra bytes are used
+    on_empty: OnEmpty,  // What checkouts do when out of entries
+    high_reserve: usize, // Entries only high priority checkouts may take
+    hot_capacity: usize, // Max number of idle entries in the hot tier
+    lru: bool,          // Reuse the least recently used entry first
+    align: usize,       // Alignment of each entry
+    entry_size: usize,  // Byte size of each entry
+    segment_len: usize, // Number of entries per segment
+    lead: usize,        // Bytes in front of the first entry of a segment
+    regions: Vec<Region>, // Named regions of the extra bytes
+    prefetch: usize,    // Bytes to prefetch on checkout
+    _p: PhantomData<T>,
+}
+
+// The inner state is shared between the pool, its checkouts and its child
+// pools, which may all live on different threads. Each value is only ever
+// accessed by whoever checked it out, or by the owner of the pool while it is
+// idle. Values move between threads, but are never shared through the inner
+// state itself.
+unsafe impl<T: Send> Send for PoolInner<T> { }
+unsafe impl<T: Send> Sync for PoolInner<T> { }
+
+// A fixed size block of memory holding a contiguous range of entries
+struct Segment {
+    memory: Box<[u8]>,  // Ownership of raw memory
+    ptr: *mut u8,       // Pointer to first entry
+    len: usize,         // Number of entries written
+    locked: bool,       // Whether the memory is locked into RAM
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        if self.locked {
+            os::unlock(self.memory.as_ptr(), self.memory.len());
+        }
+    }
+}
+
+// A task or thread waiting for a value
+struct Waiter {
+    waker: Waker,
+    since: Instant, // When the task started waiting
+    priority: Priority,
+}
+
+// How a checkout waits for an entry, when the pool blocks
+#[derive(Clone, Copy)]
+enum Wait<'a> {
+    Never,
+    Blocking,
+    Until(&'a CancelToken), // Gives up once cancelled
+}
+
+// Wakes a thread parked in a blocking checkout
+struct Unpark(thread::Thread);
+
+impl Wake for Unpark {
+    fn wake(self: std::sync::Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Initializes the value of an entry from its index and extra bytes
+type Init<T> = Box<dyn Fn(usize, &mut [u8]) -> T + Send + Sync>;
+
+// The initializer of a pool. The owner may replace it while child pools use
+// it to fill the entries they check out.
+struct Initializer<T> {
+    current: Mutex<Arc<Init<T>>>,
+    epoch: AtomicUsize, // Number of times the initializer was replaced
+}
+
+impl<T> Initializer<T> {
+    fn new(init: Arc<Init<T>>) -> Initializer<T> {
+        Initializer {
+            current: Mutex::new(init),
+            epoch: AtomicUsize::new(0),
+        }
+    }
+
+    // Returns the current initializer along with its epoch
+    fn get(&self) -> (Arc<Init<T>>, usize) {
+        let current = self.lock();
+        (current.clone(), self.epoch.load(Ordering::Relaxed))
+    }
+
+    fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    // Returns the new epoch
+    fn replace(&self, init: Init<T>) -> usize {
+        let mut current = self.lock();
+
+        *current = Arc::new(init);
+        self.epoch.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Arc<Init<T>>> {
+        // Poisoning is irrelevant, the initializer is replaced in one go
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Wipes a value and its extra bytes on checkin, see
+// `Builder::zeroize_on_checkin`
+pub(crate) type Wipe<T> = fn(Option<&mut T>, &mut [u8]);
+
+// Resets a value on checkout in place of `Reset::reset`, see
+// `Builder::reset_context`
+pub(crate) type ResetFn<T> = Arc<dyn Fn(&mut T) + Send + Sync>;
+
+// Size of a cache line on the architectures we care about
+const CACHE_LINE: usize = 64;
+
+// Aligns and pads a value to the size of a cache line
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> ops::Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+// Max size of the pool
+const MAX: usize = usize::MAX >> 1;
+
+// Default max byte size of a segment
+const SEGMENT_SIZE: usize = 1 << 20;
+
+// Number of low bits of a free list head holding the index of the first
+// entry. The remaining high bits hold a tag that is bumped by every pop, so
+// that a checkout holding on to a stale head can't mistake it for the current
+// one after the entry was popped and pushed back by others in the meantime.
+#[cfg(target_pointer_width = "64")]
+const INDEX_BITS: u32 = 40;
+#[cfg(not(target_pointer_width = "64"))]
+const INDEX_BITS: u32 = 24;
+
+// Terminates the free list. Also bounds the number of entries in the pool.
+const NIL: usize = (1 << INDEX_BITS) - 1;
+
+// Number of slots in the elimination array. A checkin that loses the race for
+// the free list head parks its entry in one of these slots for a short while
+// so that a concurrent checkout can take it without touching the head.
+const ELIMINATION_SLOTS: usize = 4;
+
+// Number of spins a checkin waits on an elimination slot before withdrawing
+// the entry and going back to the free list.
+const ELIMINATION_SPINS: usize = 64;
+
+// Bounds of the number of times a blocked checkout looks for an entry
+// before parking. Spinning doubles up to the max each time it pays off, and
+// halves down to the min each time the thread has to park.
+const MIN_WAIT_SPINS: usize = 16;
+const MAX_WAIT_SPINS: usize = 1 << 10;
+
+// Marks the lease of a checkout as revoked. Zero marks an entry without a
+// lease, other values are deadlines on the lease clock, see `lease_clock`.
+const REVOKED: usize = usize::MAX;
+
+// Marks an elimination slot as not holding an entry
+const EMPTY: usize = usize::MAX;
+
+// Number of hold time buckets, one per bit length of a duration in
+// nanoseconds
+const HOLD_BUCKETS: usize = 65;
+
+impl<T> PoolInner<T> {
+    fn new<E: Extra>(builder: &Builder<T, E>, init: Option<Init<T>>) -> PoolInner<T> {
+        // The required alignment for the entry. The start of the entry must
+        // align with this number. It is at least what `Entry<T>` requires but
+        // may be raised by the user, for example to a cache line.
+        let align = cmp::max(builder.align, mem::align_of::<Entry<T>>());
+
+        // The extra bytes must be aligned for the type of the extra storage,
+        // and may be aligned further by the user. Entries are aligned to at
+        // least as much, so the extra bytes of all entries line up once the
+        // first entry of a segment is placed so that its extra bytes do.
+        let (regions, regions_len) = region::layout(&builder.regions);
+        let regions_align = builder.regions.iter().map(|&(_, _, align)| align).max();
+
+        let extra_align = cmp::max(builder.extra_align, mem::align_of::<E>());
+        let extra_align = cmp::max(extra_align, regions_align.unwrap_or(1));
+        let extra_align = match builder.page_aligned {
+            true => cmp::max(extra_align, cmp::max(os::page_size(), 4096)),
+            false => extra_align,
+        };
+        let align = cmp::max(align, extra_align);
+        let lead = mem::size_of::<Entry<T>>().wrapping_neg() & (extra_align - 1);
+
+        assert!(align.is_power_of_two(), "something weird is up with the requested alignment");
+
+        let mask = align - 1;
+
+        // The extra storage, including the padding needed to align it
+        let extra = mem::size_of::<E>().checked_mul(builder.extra);
+        let extra = cmp::max(extra.expect("requested extra storage too big"), regions_len);
+
+        // Calculate the size of each entry. Since the extra bytes are
+        // immediately after the entry, just add the sizes and round up to the
+        // alignment. Any padding this adds is handed out as extra bytes.
+        let entry_size = (mem::size_of::<Entry<T>>() + extra + mask) & !mask;
+
+        // This should always be true, but let's check it anyway
+        assert!(entry_size & mask == 0, "entry size is not aligned");
+
+        let extra = entry_size - mem::size_of::<Entry<T>>();
+
+        // Segments hold the initial capacity, unless that would make them
+        // larger than the max segment size. An empty pool uses the largest
+        // segments.
+        let max_len = cmp::max(1, builder.segment_size.unwrap_or(SEGMENT_SIZE) / entry_size);
+        let segment_len = match builder.count {
+            0 => max_len,
+            count => cmp::min(count, max_len),
+        };
+
+        PoolInner {
+            next: CachePadded(AtomicUsize::new(NIL)),
+            cold: CachePadded(AtomicUsize::new(NIL)),
+            recent: CachePadded(AtomicUsize::new(NIL)),
+            spare: AtomicUsize::new(NIL),
+            spare_len: AtomicUsize::new(0),
+            hot_len: CachePadded(AtomicUsize::new(0)),
+            elimination: CachePadded([
+                AtomicUsize::new(EMPTY),
+                AtomicUsize::new(EMPTY),
+                AtomicUsize::new(EMPTY),
+                AtomicUsize::new(EMPTY),
+            ]),
+            segments: UnsafeCell::new(vec![]),
+            tables: UnsafeCell::new(vec![]),
+            table: AtomicPtr::new(ptr::null_mut()),
+            vacant: UnsafeCell::new(vec![]),
+            waiters: CachePadded(AtomicUsize::new(0)),
+            in_use: CachePadded(AtomicUsize::new(0)),
+            created: AtomicUsize::new(0),
+            recycled: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            events: Events::new(builder.name),
+            on_exhausted: builder.on_exhausted.as_ref()
+                .map(|(interval, callback)| ExhaustionHook::new(*interval, callback.clone())),
+            lease: builder.lease.filter(|_| init.is_some()),
+            clock: Instant::now(),
+            // Dropped values are created again on checkout
+            budget: builder.byte_budget
+                .filter(|_| init.is_some())
+                .map(|(bytes, heap_size)| Budget::new(bytes, heap_size)),
+            low_watermark: builder.low_watermark.as_ref()
+                .map(|(fraction, callback)| Watermark::new(*fraction, callback.clone())),
+            // Growing needs values to be created
+            auto_grow: builder.auto_grow
+                .filter(|_| init.is_some())
+                .map(|(fraction, grace, max)| AutoGrow::new(fraction, grace, max)),
+            // Retired values are created again on checkout
+            auto_shrink: builder.auto_shrink
+                .filter(|_| init.is_some())
+                .map(|(fraction, period, floor)| AutoShrink::new(fraction, period, floor)),
+            adaptive: builder.adaptive
+                .filter(|_| init.is_some())
+                .map(|(target, min, max, interval)| Adaptive::new(target, min, max, interval)),
+            hold_times: match builder.track_hold_time {
+                true => (0..HOLD_BUCKETS).map(|_| AtomicUsize::new(0)).collect(),
+                false => vec![],
+            },
+            wakers: Mutex::new(VecDeque::new()),
+            waits: AtomicUsize::new(0),
+            wait_time: Mutex::new(Duration::from_secs(0)),
+            spins: UnsafeCell::new(MAX_WAIT_SPINS),
+            // Growing and transient values need values to be created
+            on_empty: match builder.on_empty {
+                OnEmpty::Grow { .. } | OnEmpty::Transient if init.is_none() => OnEmpty::Fail,
+                on_empty => on_empty,
+            },
+            high_reserve: builder.high_reserve,
+            hot_capacity: builder.hot_capacity.unwrap_or(usize::MAX),
+            lru: builder.idle_order == IdleOrder::Lru && builder.hot_capacity.is_none(),
+            init: init.map(|init| Initializer::new(Arc::new(init))),
+            parent: builder.parent.clone(),
+            reset: builder.reset.clone(),
+            wipe: builder.wipe,
+            weight: builder.weight.clone(),
+            lock_memory: builder.lock_memory,
+            track_idle: builder.track_idle || builder.hot_capacity.is_some(),
+
+            huge_pages: builder.huge_pages,
+            track_extra: builder.track_extra_usage,
+            align,
+            entry_size,
+            segment_len,
+            lead,
+            regions,
+            // Cover the value and the requested leading cache lines of the
+            // extra bytes
+            prefetch: mem::size_of::<Entry<T>>() +
+                cmp::min(extra, builder.prefetch_extra * CACHE_LINE),
+            _p: PhantomData,
+        }
+    }
+
+    // Appends `count` new entries, initialized with the given function, and
+    // makes them available for checkout.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn grow<F>(&self, count: usize, mut init: F)
+            where F: FnMut(usize, &mut [u8]) -> T {
+        let extra = self.extra();
+
+        self.grow_with(count, |ptr, idx| Entry::init(ptr, idx, extra, &mut init));
+    }
+
+    // Appends `count` new empty entries and makes them available for
+    // checkout. Their values are created by the initializer when they are
+    // first checked out.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn grow_empty(&self, count: usize) {
+        let extra = self.extra();
+
+        self.grow_with(count, |ptr, idx| Entry::init_empty(ptr, idx, extra));
+    }
+
+    // Appends `count` new entries, written by the given function, and pushes
+    // them onto the free list. The free space in the last segment is used
+    // first, then new segments are allocated as needed.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn grow_with<F>(&self, count: usize, mut write: F)
+            where F: FnMut(*mut Entry<T>, usize) {
+
+        if count == 0 {
+            return;
+        }
+
+        let start = self.len();
+
+        // Check that the capacity is not too large
+        assert!(start.checked_add(count).is_some_and(|len| len < NIL), "requested pool size too big");
+
+        let created = self.timestamp();
+        let epoch = self.init.as_ref().map_or(0, |init| init.epoch());
+
+        for idx in start..start + count {
+            if self.segment_room() == 0 {
+                self.alloc_segment();
+            }
+
+            let segment = self.last_segment();
+            let ptr = segment.ptr.add(segment.len * self.entry_size) as *mut Entry<T>;
+
+            write(ptr, idx);
+            self.last_segment().len += 1;
+
+            if (*ptr).init {
+                Entry::set_created(ptr, created);
+                Entry::set_epoch(ptr, epoch);
+                self.measure(ptr);
+            }
+        }
+
+        // New entries go to the cold tier until they have proven useful
+        self.push_list(self.idle_list(), start, self.entry_ptr(start + count - 1));
+        self.resized();
+    }
+
+    // The current time, if entries record when they are used
+    fn timestamp(&self) -> Stamp {
+        if self.track_idle {
+            Stamp::now()
+        } else {
+            Stamp::NONE
+        }
+    }
+
+    // Lets the watermark know the capacity changed
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn resized(&self) {
+        if let Some(ref watermark) = self.low_watermark {
+            watermark.set_capacity(self.capacity(), self.in_use.load(Ordering::Relaxed));
+        }
+
+        if let Some(ref budget) = self.budget {
+            budget.set_capacity(self.capacity());
+        }
+    }
+
+    // Allocates a new segment and returns a pointer to its first entry. The
+    // entries still need to be written.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn alloc_segment(&self) -> *mut u8 {
+        let segments = &mut *self.segments.get();
+
+        // Ensure that the total memory needed is possible. It must be
+        // representable by an `isize` value in order for pointer offset to
+        // work.
+        let size = self.entry_size.checked_mul(self.segment_len).and_then(|size| size.checked_add(self.lead));
+        assert!(size.is_some_and(|size| size < MAX), "requested pool capacity too big");
+
+        // Allocate the memory, it is zeroed out for safety
+        let (memory, ptr) = alloc(self.entry_size * self.segment_len + self.lead, self.align);
+        let ptr = ptr.add(self.lead);
+
+        if self.huge_pages {
+            os::advise_huge(memory.as_ptr(), memory.len());
+        }
+
+        // Locking is best effort, `Pool::is_memory_locked` tells whether it
+        // worked out
+        let locked = self.lock_memory && os::lock(memory.as_ptr(), memory.len());
+
+        segments.push(Segment {
+            memory,
+            ptr,
+            len: 0,
+            locked,
+        });
+
+        let tables = &mut *self.tables.get();
+        let n = segments.len() - 1;
+
+        if tables.last().map_or(0, Vec::len) > n {
+            // Nobody reads the slot before the segment's entries are pushed
+            // onto a free list.
+            *self.table.load(Ordering::Relaxed).add(n) = ptr;
+        } else {
+            // Readers may still hold the current table, so it is kept around
+            // until the pool is dropped.
+            let mut table: Vec<*mut u8> = segments.iter().map(|segment| segment.ptr).collect();
+            table.resize(cmp::max(4, 2 * segments.len()), ptr::null_mut());
+
+            self.table.store(table.as_mut_ptr(), Ordering::Release);
+            tables.push(table);
+        }
+
+        ptr
+    }
+
+    // Number of entries that fit in the last segment
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn segment_room(&self) -> usize {
+        let segments = &*self.segments.get();
+
+        segments.last()
+            .map(|segment| self.segment_len - segment.len)
+            .unwrap_or(0)
+    }
+
+    // Must only be called by the owner of the pool.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn last_segment(&self) -> &mut Segment {
+        let segments = &mut *self.segments.get();
+        segments.last_mut().unwrap()
+    }
+
+    // Total number of entries
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn len(&self) -> usize {
+        let segments = &*self.segments.get();
+
+        segments.last()
+            .map(|segment| (segments.len() - 1) * self.segment_len + segment.len)
+            .unwrap_or(0)
+    }
+
+    // Number of entries in use or available, leaving out those set aside
+    // after their lease was revoked
+    unsafe fn capacity(&self) -> usize {
+        self.len() - self.spare_len.load(Ordering::Relaxed)
+    }
+
+    // Returns an available entry along with the pool it belongs to, which is
+    // either this one or one of its parents. Waits for an entry as told by
+    // `wait` if the pool blocks when empty.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn checkout(self: &Arc<Self>, wait: Wait<'_>, priority: Priority) -> Option<(*mut Entry<T>, &Arc<PoolInner<T>>)> {
+        if let Some(ref auto_grow) = self.auto_grow {
+            if auto_grow.should_grow(self.capacity(), self.in_use.load(Ordering::Relaxed)) {
+                self.grow_ahead(auto_grow.max);
+            }
+        }
+
+        if let Some(ref auto_shrink) = self.auto_shrink {
+            if auto_shrink.should_shrink(self.capacity(), self.in_use.load(Ordering::Relaxed)) {
+                self.shrink_to(auto_shrink.floor);
+            }
+        }
+
+        if let Some(ref adaptive) = self.adaptive {
+            if let Some(size) = adaptive.sample(self.in_use.load(Ordering::Relaxed) + 1) {
+                self.resize_to(size);
+            }
+        }
+
+        let popped = match self.pop_for(priority) {
+            // Out of entries, make up for those held past their lease
+            None if self.lease.is_some() && self.reclaim_leases() > 0 => self.pop_for(priority),
+            popped => popped,
+        };
+
+        let (ptr, owner) = match popped {
+            Some(ptr) => (ptr, self),
+            None => match self.pop_parent() {
+                Some(found) => found,
+                None => match self.on_empty {
+                    OnEmpty::Fail => return None,
+                    OnEmpty::Block => match wait {
+                        Wait::Never => return None,
+                        Wait::Blocking => self.wait(priority, None)?,
+                        Wait::Until(cancel) => self.wait(priority, Some(cancel))?,
+                    },
+                    OnEmpty::Grow { max } => {
+                        let len = self.capacity();
+
+                        if len >= max {
+                            return None;
+                        }
+
+                        // Grow the pool by a segment, or whatever fits in the
+                        // last one. The new entries are reused like any other
+                        // once checked back in.
+                        let count = match self.segment_room() {
+                            0 => self.segment_len,
+                            room => room,
+                        };
+
+                        let count = cmp::min(count, max - len);
+                        let count = cmp::min(count, self.room());
+
+                        if count == 0 {
+                            return None;
+                        }
+
+                        self.grow_empty(count);
+                        self.events.send(PoolEvent::Grew { by: count });
+                        (self.pop()?, self)
+                    }
+                    OnEmpty::Transient => (self.alloc_transient(), self),
+                },
+            },
+        };
+
+        if (*ptr).idx != NIL {
+            let in_use = owner.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+
+            if let Some(ref watermark) = owner.low_watermark {
+                watermark.update(in_use);
+            }
+
+            if let Some(lease) = owner.lease {
+                (*Entry::track(ptr)).lease.store(owner.lease_clock(lease), Ordering::Relaxed);
+            }
+        }
+
+        if !owner.hold_times.is_empty() {
+            (*Entry::track(ptr)).checked_out = Stamp::now();
+        }
+
+        // The caller is about to touch the entry, so start pulling it (and
+        // possibly the start of the extra bytes) into the cache now.
+        prefetch(ptr as *const u8, owner.prefetch);
+
+        Some((ptr, owner))
+    }
+
+    // Adds a segment's worth of initialized entries, or whatever fits in the
+    // last segment, without exceeding `max` entries
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn grow_ahead(&self, max: usize) {
+        let count = match self.segment_room() {
+            0 => self.segment_len,
+            room => room,
+        };
+        let count = cmp::min(count, max.saturating_sub(self.capacity()));
+        let count = cmp::min(count, self.room());
+
+        if count == 0 {
+            return;
+        }
+
+        self.grow(count, &**self.init.as_ref().unwrap().get().0);
+        self.created.fetch_add(count, Ordering::Relaxed);
+        self.events.send(PoolEvent::Grew { by: count });
+    }
+
+    // Grows the pool to `size` initialized entries, or retires idle values
+    // until at most `size` are left
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn resize_to(&self, size: usize) {
+        let len = self.capacity();
+
+        if size > len {
+            let count = cmp::min(size - len, self.room());
+
+            if count > 0 {
+                self.grow(count, &**self.init.as_ref().unwrap().get().0);
+                self.created.fetch_add(count, Ordering::Relaxed);
+                self.events.send(PoolEvent::Grew { by: count });
+            }
+        } else if size < len {
+            self.shrink_to(size);
+        }
+    }
+
+    // Drops idle values, least recently used first, until at most `floor`
+    // are left, counting the checked out ones. The emptied entries stay on
+    // the free list, to be filled on checkout.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn shrink_to(&self, floor: usize) {
+        let keep = floor.saturating_sub(self.in_use.load(Ordering::Relaxed));
+        let mut retired = 0;
+
+        self.with_idle_lru(|idle| {
+            let full: Vec<_> = idle.iter().filter(|&&ptr| (*ptr).init).collect();
+            retired = full.len().saturating_sub(keep);
+
+            for &&ptr in &full[..retired] {
+                Entry::drop_value(ptr);
+                self.measure(ptr);
+            }
+        });
+
+        if retired > 0 {
+            self.events.send(PoolEvent::Shrank { by: retired });
+        }
+    }
+
+    // Blocks until an entry is available, from this pool or a parent
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn wait(self: &Arc<Self>, priority: Priority, cancel: Option<&CancelToken>) -> Option<(*mut Entry<T>, &Arc<PoolInner<T>>)> {
+        self.events.exhausted();
+
+        // Checkins usually follow shortly, look again for a while before
+        // paying for parking the thread
+        let spins = &mut *self.spins.get();
+
+        for _ in 0..*spins {
+            hint::spin_loop();
+
+            if let Some(found) = self.pop_waiting(priority) {
+                *spins = cmp::min(*spins * 2, MAX_WAIT_SPINS);
+                return Some(found);
+            }
+        }
+
+        *spins = cmp::max(*spins / 2, MIN_WAIT_SPINS);
+
+        let waker = Waker::from(std::sync::Arc::new(Unpark(thread::current())));
+
+        // Cancelling wakes the thread like a checkin would
+        let cancelled = || cancel.is_some_and(|cancel| cancel.is_cancelled());
+        let registered = cancel.is_none_or(|cancel| cancel.register(&waker));
+
+        let found = loop {
+            // Look again after registering, a checkin in between would not
+            // have seen the waker
+            self.register(&waker, priority);
+
+            if let Some(found) = self.pop_waiting(priority) {
+                break Some(found);
+            }
+
+            if !registered || cancelled() {
+                break None;
+            }
+
+            match self.lease {
+                // Wake up to revoke the leases that expire meanwhile
+                Some(lease) => {
+                    thread::park_timeout(lease);
+                    self.reclaim_leases();
+                }
+                None => thread::park(),
+            }
+
+            // Woken, or not. Should another checkout have taken the entry,
+            // registering again goes to the back of the queue.
+            if let Some(found) = self.pop_waiting(priority) {
+                break Some(found);
+            }
+        };
+
+        self.unregister(&waker);
+
+        if let Some(cancel) = cancel {
+            cancel.unregister(&waker);
+        }
+
+        found
+    }
+
+    // Revokes the leases that expired and adds an empty entry in place of
+    // each, taking back the entries of revoked checkouts that were set aside
+    // before growing the pool. Returns the number of leases revoked.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn reclaim_leases(&self) -> usize {
+        let now = self.lease_clock(Duration::from_secs(0));
+        let mut revoked = 0;
+
+        for idx in 0..self.len() {
+            // Never checked out, so never leased
+            let lease = match (*self.entry_ptr(idx)).tracked() {
+                Some(track) => &track.lease,
+                None => continue,
+            };
+
+            let deadline = lease.load(Ordering::Relaxed);
+
+            if deadline == 0 || deadline == REVOKED || deadline > now {
+                continue;
+            }
+
+            // Fails if the checkout is being dropped
+            if lease.compare_exchange(deadline, REVOKED, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                revoked += 1;
+                self.events.send(PoolEvent::LeaseRevoked);
+            }
+        }
+
+        let mut grow = revoked;
+
+        while grow > 0 {
+            let ptr = match self.pop_list(&self.spare) {
+                Some(ptr) => ptr,
+                None => break,
+            };
+
+            self.spare_len.fetch_sub(1, Ordering::Relaxed);
+            self.push_list(self.idle_list(), (*ptr).idx, ptr);
+            grow -= 1;
+        }
+
+        if grow > 0 {
+            self.grow_empty(grow);
+            self.events.send(PoolEvent::Grew { by: grow });
+        } else if revoked > 0 {
+            self.resized();
+        }
+
+        revoked
+    }
+
+    // The time `after` from now on the lease clock, in milliseconds plus one
+    // so that it is never zero
+    fn lease_clock(&self, after: Duration) -> usize {
+        let ms = (self.clock.elapsed() + after).as_millis() + 1;
+        cmp::min(ms, (REVOKED - 1) as u128) as usize
+    }
+
+    // Pops an entry for a waiting checkout, from this pool or a parent
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn pop_waiting(self: &Arc<Self>, priority: Priority) -> Option<(*mut Entry<T>, &Arc<PoolInner<T>>)> {
+        match self.pop_for(priority) {
+            Some(ptr) => Some((ptr, self)),
+            None => self.pop_parent(),
+        }
+    }
+
+    // Adds the time since `since` to the total wait time
+    fn waited(&self, since: Instant) {
+        let mut wait_time = self.wait_time.lock().unwrap_or_else(|e| e.into_inner());
+        *wait_time += since.elapsed();
+    }
+
+    // Leaves a waker to be woken by the next checkin, behind those of the
+    // same or a higher priority. The caller must look for an entry again
+    // afterwards.
+    fn register(&self, waker: &Waker, priority: Priority) {
+        let mut wakers = self.lock_wakers();
+
+        // A task polling again replaces its previous waker
+        match wakers.iter_mut().find(|waiter| waiter.waker.will_wake(waker)) {
+            Some(waiter) => waiter.waker.clone_from(waker),
+            None => {
+                let pos = wakers.iter().position(|waiter| waiter.priority < priority).unwrap_or(wakers.len());
+
+                wakers.insert(pos, Waiter {
+                    waker: waker.clone(),
+                    since: Instant::now(),
+                    priority,
+                });
+
+                self.waiters.fetch_add(1, Ordering::Relaxed);
+                self.waits.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fence(Ordering::SeqCst);
+    }
+
+    // Takes the waker back out of the queue, once the task no longer waits.
+    // If it was already woken, the wakeup is passed on to the next task.
+    fn unregister(&self, waker: &Waker) {
+        if self.waiters.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let mut wakers = self.lock_wakers();
+
+        match wakers.iter().position(|waiter| waiter.waker.will_wake(waker)) {
+            Some(pos) => {
+                let waiter = wakers.remove(pos).unwrap();
+
+                self.waiters.fetch_sub(1, Ordering::Relaxed);
+                drop(wakers);
+                self.waited(waiter.since);
+            }
+            None if self.has_idle() => self.wake_one(wakers),
+            None => {}
+        }
+    }
+
+    // Records a checkout that found the pool depleted
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn depleted(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        self.events.exhausted();
+
+        if let Some(ref hook) = self.on_exhausted {
+            hook.call(|| Exhaustion {
+                waiters: self.waiters.load(Ordering::Relaxed),
+                in_use: self.in_use.load(Ordering::Relaxed),
+                capacity: self.capacity(),
+            });
+        }
+    }
+
+    // Wakes a checkout waiting on the pool, after an entry was pushed
+    fn notify(&self) {
+        fence(Ordering::SeqCst);
+
+        if self.waiters.load(Ordering::Relaxed) != 0 {
+            self.wake_one(self.lock_wakers());
+        }
+    }
+
+    // Wakes the task with the highest priority that has been waiting the
+    // longest, outside of the lock
+    fn wake_one(&self, mut wakers: MutexGuard<'_, VecDeque<Waiter>>) {
+        let waiter = wakers.pop_front();
+
+        if waiter.is_some() {
+            self.waiters.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        drop(wakers);
+
+        // The task is done waiting, though it may lose the value to another
+        // checkout and wait again
+        if let Some(waiter) = waiter {
+            self.waited(waiter.since);
+            waiter.waker.wake();
+        }
+    }
+
+    // Whether the free lists hold an entry, which may be gone by the time
+    // the caller acts on it
+    fn has_idle(&self) -> bool {
+        [&self.next, &self.cold, &self.recent].iter().any(|list| list.load(Ordering::Relaxed) & NIL != NIL)
+    }
+
+    fn lock_wakers(&self) -> MutexGuard<'_, VecDeque<Waiter>> {
+        // Poisoning is irrelevant, wakers are never left half updated
+        self.wakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Allocates an entry outside of the segments, for a transient value. It
+    // is marked by an index of `NIL` and freed on checkin.
+    unsafe fn alloc_transient(&self) -> *mut Entry<T> {
+        let layout = self.transient_layout();
+        let memory = alloc::alloc_zeroed(layout);
+
+        if memory.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        // Placed like the first entry of a segment, aligning the extra bytes
+        let ptr = memory.add(self.lead) as *mut Entry<T>;
+        Entry::init_empty(ptr, NIL, self.extra());
+        ptr
+    }
+
+    unsafe fn free_transient(&self, ptr: *mut Entry<T>) {
+        Entry::release(ptr);
+        alloc::dealloc((ptr as *mut u8).sub(self.lead), self.transient_layout());
+    }
+
+    fn transient_layout(&self) -> Layout {
+        Layout::from_size_align(self.entry_size + self.lead, self.align).unwrap()
+    }
+
+    // Pops an entry off the closest parent that has one available. Parents
+    // are shared by their children, so this may race with checkouts from
+    // other pools.
+    unsafe fn pop_parent(&self) -> Option<(*mut Entry<T>, &Arc<PoolInner<T>>)> {
+        let mut parent = self.parent.as_ref();
+
+        while let Some(pool) = parent {
+            if let Some(ptr) = pool.pop() {
+                return Some((ptr, pool));
+            }
+
+            parent = pool.parent.as_ref();
+        }
+
+        None
+    }
+
+    // Pops an entry, unless only the entries held back for high priority
+    // checkouts are left and the checkout is not one of them
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn pop_for(&self, priority: Priority) -> Option<*mut Entry<T>> {
+        if priority < Priority::High && self.high_reserve > 0 {
+            let available = self.capacity().saturating_sub(self.in_use.load(Ordering::Relaxed));
+
+            if available <= self.high_reserve {
+                return None;
+            }
+        }
+
+        self.pop()
+    }
+
+    unsafe fn pop(&self) -> Option<*mut Entry<T>> {
+        if self.lru {
+            // Checkins never hand entries off directly, the free list has the
+            // oldest ones
+            return match self.pop_list(&self.next) {
+                Some(ptr) => Some(ptr),
+                None if self.refill() => self.pop_list(&self.next),
+                None => None,
+            };
+        }
+
+        if !self.is_tiered() {
+            return self.pop_hot();
+        }
+
+        // Prefer the hot tier, spill to the cold one under pressure
+        match self.pop_hot() {
+            Some(ptr) => {
+                self.hot_len.fetch_sub(1, Ordering::Relaxed);
+                Some(ptr)
+            }
+            None => self.pop_list(&self.cold),
+        }
+    }
+
+    // Pops an entry off the hot free list, or takes one being handed off by a
+    // concurrent checkin
+    unsafe fn pop_hot(&self) -> Option<*mut Entry<T>> {
+        let mut head = self.next.load(Ordering::Acquire);
+
+        loop {
+            let idx = head & NIL;
+
+            if idx == NIL {
+                // The free list is depleted, but a checkin may be in the
+                // middle of handing off an entry.
+                return self.eliminate_checkout();
+            }
+
+            let nxt = (*self.entry_ptr(idx)).next.load(Ordering::Relaxed);
+
+            // The entry was acquired along with the head, which is tagged, so
+            // success means nothing was pushed or popped meanwhile
+            let actual = match self.next.compare_exchange_weak(head, pop_head(head, nxt), Ordering::Relaxed, Ordering::Acquire) {
+                Ok(_) => return Some(self.entry_ptr(idx)),
+                Err(actual) => actual,
+            };
+
+            // The head is contended, try taking an entry being handed off by
+            // a checkin instead of racing for it again. A spurious failure
+            // just retries.
+            if actual != head {
+                if let Some(ptr) = self.eliminate_checkout() {
+                    return Some(ptr);
+                }
+            }
+
+            head = actual;
+        }
+    }
+
+    // Pops an entry off the given free list
+    unsafe fn pop_list(&self, list: &AtomicUsize) -> Option<*mut Entry<T>> {
+        let mut head = list.load(Ordering::Acquire);
+
+        while head & NIL != NIL {
+            let idx = head & NIL;
+            let nxt = (*self.entry_ptr(idx)).next.load(Ordering::Relaxed);
+
+            // See `pop_hot`
+            match list.compare_exchange_weak(head, pop_head(head, nxt), Ordering::Relaxed, Ordering::Acquire) {
+                Ok(_) => return Some(self.entry_ptr(idx)),
+                Err(actual) => head = actual,
+            }
+        }
+
+        None
+    }
+
+    // Moves the entries checked in since the last refill onto the free list,
+    // oldest first, behind those still on it. Returns false if there were
+    // none.
+    //
+    // Each entry is moved once, so this is O(1) per checkin, amortized.
+    unsafe fn refill(&self) -> bool {
+        let mut idx = take_list(&self.recent);
+
+        if idx == NIL {
+            return false;
+        }
+
+        // The list is most recent first, reverse it
+        let last = self.entry_ptr(idx);
+        let mut first = NIL;
+
+        while idx != NIL {
+            let ptr = self.entry_ptr(idx);
+            let nxt = (*ptr).next.load(Ordering::Relaxed);
+
+            (*ptr).next.store(first, Ordering::Relaxed);
+            first = idx;
+            idx = nxt;
+        }
+
+        // Append it to whatever is left on the free list, which is usually
+        // nothing
+        let rest = take_list(&self.next);
+
+        if rest != NIL {
+            let mut tail = self.entry_ptr(rest);
+
+            loop {
+                let nxt = (*tail).next.load(Ordering::Relaxed);
+
+                if nxt == NIL {
+                    break;
+                }
+
+                tail = self.entry_ptr(nxt);
+            }
+
+            (*tail).next.store(first, Ordering::Relaxed);
+            first = rest;
+        }
+
+        self.push_list(&self.next, first, last);
+
+        // A checkout may have found the lists empty meanwhile
+        self.notify();
+        true
+    }
+
+    // Whether idle entries are split between a hot and a cold tier
+    fn is_tiered(&self) -> bool {
+        self.hot_capacity != usize::MAX
+    }
+
+    // The free list that entries not known to be hot go to
+    fn idle_list(&self) -> &AtomicUsize {
+        if self.is_tiered() {
+            &self.cold
+        } else {
+            &self.next
+        }
+    }
+
+    // Moves the entries of the hot tier that have been idle since before
+    // `cutoff` to the cold tier. Returns the number of entries moved.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn demote_idle(&self, cutoff: Instant) -> usize {
+        if !self.is_tiered() {
+            return 0;
+        }
+
+        let mut demoted = 0;
+        let mut idx = take_list(&self.next);
+
+        while idx != NIL {
+            let ptr = self.entry_ptr(idx);
+            let nxt = (*ptr).next.load(Ordering::Relaxed);
+
+            if (*ptr).checked_in() < Stamp::at(cutoff) {
+                self.push_list(&self.cold, idx, ptr);
+                demoted += 1;
+            } else {
+                self.push_list(&self.next, idx, ptr);
+            }
+
+            idx = nxt;
+        }
+
+        self.hot_len.fetch_sub(demoted, Ordering::Relaxed);
+        demoted
+    }
+
+    // Calls `f` with every idle entry, leaving the free lists as they were.
+    // The lists are taken off the pool meanwhile, so concurrent checkouts
+    // from child pools may find the pool empty.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn for_each_idle<F>(&self, mut f: F)
+            where F: FnMut(*mut Entry<T>) {
+        for &list in &[&self.next, &self.cold, &self.recent] {
+            let first = take_list(list);
+            let mut idx = first;
+            let mut last = ptr::null_mut();
+
+            while idx != NIL {
+                last = self.entry_ptr(idx);
+                f(last);
+                idx = (*last).next.load(Ordering::Relaxed);
+            }
+
+            if first != NIL {
+                self.push_list(list, first, last);
+            }
+        }
+    }
+
+    // Calls `f` with the idle entries, least recently used first, leaving the
+    // free lists as they were, like `for_each_idle`.
+    //
+    // Entries are ordered by when they were checked in if that is tracked,
+    // by their position on the free lists otherwise: cold before hot, and
+    // the most recently used entries first on a stack.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn with_idle_lru<F>(&self, f: F)
+            where F: FnOnce(&[*mut Entry<T>]) {
+        let lists = [&self.next, &self.cold, &self.recent];
+        let mut taken = vec![];
+
+        for &list in &lists {
+            let mut idx = take_list(list);
+            let mut entries = vec![];
+
+            while idx != NIL {
+                let ptr = self.entry_ptr(idx);
+                entries.push(ptr);
+                idx = (*ptr).next.load(Ordering::Relaxed);
+            }
+
+            taken.push(entries);
+        }
+
+        let mut idle: Vec<_> = taken[1].iter().rev().cloned().collect();
+
+        if self.lru {
+            // The free list holds the oldest entries, oldest first
+            idle.extend(taken[0].iter().cloned());
+        } else {
+            idle.extend(taken[0].iter().rev().cloned());
+        }
 
-        let mut inner = PoolInner::with_capacity(count, extra);
+        idle.extend(taken[2].iter().rev().cloned());
 
-        // Get the actual number of extra bytes
-        extra = inner.entry_size - mem::size_of::<Entry<T>>();
+        if self.track_idle {
+            // Never checked in first. The sort is stable, ties keep their
+            // order.
+            idle.sort_by_key(|&ptr| (*ptr).checked_in());
+        }
 
-        // Initialize the entries
-        for i in 0..count {
-            unsafe {
-                ptr::write(inner.entry_mut(i), Entry {
-                    data: init(),
-                    next: i + 1,
-                    extra: extra,
-                });
+        f(&idle);
+
+        // The entries are still linked as they were
+        for (&list, entries) in lists.iter().zip(&taken) {
+            if let (Some(&first), Some(&last)) = (entries.first(), entries.last()) {
+                self.push_list(list, (*first).idx, last);
             }
-            inner.init += 1;
         }
-
-        Pool { inner: Arc::new(UnsafeCell::new(inner)) }
     }
 
-    /// Checkout a value from the pool. Returns `None` if the pool is currently
-    /// at capacity.
-    ///
-    /// The value returned from the pool has not been reset and contains the
-    /// state that it previously had when it was last released.
-    pub fn checkout(&mut self) -> Option<Checkout<T>> {
-        self.inner_mut().checkout()
-            .map(|ptr| {
-                Checkout {
-                    entry: ptr,
-                    inner: self.inner.clone(),
-                }
-            }).map(|mut checkout| {
-                checkout.reset();
-                checkout
-            })
+    // Writes to every page of the extra bytes of idle entries. Only the idle
+    // entries' own bytes are touched, checked out entries may share a page.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn warm_up(&self) {
+        let page = os::page_size();
+
+        self.for_each_idle(|ptr| {
+            let extra = (*ptr).extra_mut();
+            let start = extra.as_mut_ptr() as usize;
+            let end = start + extra.len();
+            let mut p = start;
+
+            while p < end {
+                // Write the byte back, the extra bytes are always initialized
+                let byte = p as *mut u8;
+                ptr::write_volatile(byte, ptr::read_volatile(byte));
+
+                // Move on to the start of the next page
+                p = (p & !(page - 1)) + page;
+            }
+        });
     }
 
-    fn inner_mut(&self) -> &mut PoolInner<T> {
-        unsafe { mem::transmute(self.inner.get()) }
+    // Hands the memory backing the extra bytes of entries that have been idle
+    // since before `cutoff` back to the OS. Returns the number of bytes
+    // released.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn release_idle(&self, cutoff: Instant) -> usize {
+        let mut released = 0;
+
+        let cutoff = Stamp::at(cutoff);
+
+        self.for_each_idle(|ptr| {
+            if (*ptr).checked_in() < cutoff {
+                let extra = (*ptr).extra_mut();
+                released += os::release(extra.as_mut_ptr(), extra.len());
+            }
+        });
+
+        released
     }
-}
 
-unsafe impl<T: Send + Reset> Send for Pool<T> { }
+    // Drops the values of idle entries created before `cutoff`, creating new
+    // ones with the initializer if `recreate` is set. Returns the number of
+    // values dropped.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn retire_idle(&self, cutoff: Instant, recreate: bool) -> usize {
+        let mut retired = 0;
 
-/// A handle to a checked out value. When dropped out of scope, the value will
-/// be returned to the pool.
-pub struct Checkout<T> {
-    entry: *mut Entry<T>,
-    inner: Arc<UnsafeCell<PoolInner<T>>>,
-}
+        let cutoff = Stamp::at(cutoff);
+
+        self.for_each_idle(|ptr| {
+            if !(*ptr).init || (*ptr).created() >= cutoff {
+                return;
+            }
+
+            retired += 1;
 
-impl<T> Checkout<T> {
-    /// Read access to the raw bytes
-    pub fn extra(&self) -> &[u8] {
-        self.entry().extra()
+            if recreate {
+                self.refresh(ptr);
+            } else {
+                Entry::drop_value(ptr);
+                self.measure(ptr);
+            }
+        });
+
+        retired
     }
 
-    /// Write access to the extra bytes
-    pub fn extra_mut(&mut self) -> &mut [u8] {
-        self.entry_mut().extra_mut()
+    // Replaces the value of an idle entry, or fills an empty one, with a new
+    // value from the initializer
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn refresh(&self, ptr: *mut Entry<T>) {
+        Entry::drop_value(ptr);
+        self.create(ptr);
+        self.created.fetch_add(1, Ordering::Relaxed);
     }
 
-    fn entry(&self) -> &Entry<T> {
-        unsafe { mem::transmute(self.entry) }
+    // Fills an empty entry with a value from the current initializer
+    unsafe fn create(&self, ptr: *mut Entry<T>) {
+        let (init, epoch) = self.init.as_ref().unwrap().get();
+
+        self.fill(ptr, &**init);
+        Entry::set_epoch(ptr, epoch);
     }
 
-    fn entry_mut(&mut self) -> &mut Entry<T> {
-        unsafe { mem::transmute(self.entry) }
+    // Measures the heap memory owned by the entry's value, if the pool has
+    // a byte budget. Transient values don't count.
+    unsafe fn measure(&self, ptr: *mut Entry<T>) {
+        if let Some(ref budget) = self.budget {
+            if (*ptr).idx != NIL {
+                let value = if (*ptr).init { Some((*ptr).data()) } else { None };
+                let track = Entry::track(ptr);
+                (*track).heap = budget.measure(value, (*track).heap);
+            }
+        }
     }
 
-    fn inner(&self) -> &mut PoolInner<T> {
-        unsafe { mem::transmute(self.inner.get()) }
+    // Number of entries the pool may grow by, within the byte budget
+    fn room(&self) -> usize {
+        self.budget.as_ref().map_or(usize::MAX, |budget| budget.room(self.entry_size))
     }
-}
 
-impl<T> ops::Deref for Checkout<T> {
-    type Target = T;
+    // Creates a value for an empty entry that is checked out, using the
+    // pool's initializer.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn fill<F>(&self, ptr: *mut Entry<T>, init: F)
+            where F: FnOnce(usize, &mut [u8]) -> T {
+        let idx = (*ptr).idx;
+        let value = init(idx, (*ptr).extra_mut());
 
-    fn deref(&self) -> &T {
-        &self.entry().data
+        (*ptr).data = MaybeUninit::new(value);
+        (*ptr).init = true;
+        (*ptr).uses = 0;
+        Entry::set_created(ptr, self.timestamp());
+        self.measure(ptr);
     }
-}
 
-impl<T> ops::DerefMut for Checkout<T> {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.entry_mut().data
+    // Adds the values to the pool along with the extra bytes to copy into
+    // their entry, refilling emptied entries first and then growing the pool
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn put<'a, I>(&self, values: I)
+            where I: ExactSizeIterator<Item = (T, &'a [u8])> {
+        fn copy_extra(dst: &mut [u8], src: &[u8]) {
+            let len = cmp::min(dst.len(), src.len());
+            dst[..len].copy_from_slice(&src[..len]);
+        }
+
+        let mut values = values;
+
+        while let Some(idx) = self.vacant().pop() {
+            match values.next() {
+                Some((value, extra)) => {
+                    let ptr = self.entry_ptr(idx);
+
+                    self.fill(ptr, |_, bytes| {
+                        copy_extra(bytes, extra);
+                        value
+                    });
+                    self.push_list(self.idle_list(), idx, ptr);
+                }
+                None => {
+                    self.vacant().push(idx);
+                    return;
+                }
+            }
+        }
+
+        let count = values.len();
+
+        if count > 0 {
+            self.grow(count, |_, bytes| {
+                let (value, extra) = values.next().unwrap();
+
+                copy_extra(bytes, extra);
+                value
+            });
+            self.events.send(PoolEvent::Grew { by: count });
+        }
     }
-}
 
-impl<T> Drop for Checkout<T> {
-    fn drop(&mut self) {
-        self.inner().checkin(self.entry);
+    // Removes the values of all entries on the free list.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn drain_idle(&self) -> Vec<T> {
+        let mut values = vec![];
+
+        self.take_idle(usize::MAX, |value, _| values.push(value));
+        values
     }
-}
 
-unsafe impl<T: Send> Send for Checkout<T> { }
-unsafe impl<T: Sync> Sync for Checkout<T> { }
+    // Removes the values of up to `limit` entries on the free list, handing
+    // each to `f` along with the entry's extra bytes. Returns the number of
+    // values removed.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn take_idle<F>(&self, limit: usize, mut f: F) -> usize
+            where F: FnMut(T, &mut [u8]) {
+        let mut taken = 0;
 
-struct PoolInner<T> {
-    #[allow(dead_code)]
-    memory: Box<[u8]>,  // Ownership of raw memory
-    next: AtomicUsize,  // Offset to next available value
-    ptr: *mut Entry<T>, // Pointer to first entry
-    init: usize,        // Number of initialized entries
-    count: usize,       // Total number of entries
-    entry_size: usize,  // Byte size of each entry
-}
+        // Take the whole free lists at once. Concurrent checkins simply start
+        // new lists.
+        for &list in &[&self.next, &self.cold, &self.recent] {
+            let mut idx = take_list(list);
+            let mut kept = vec![];  // Entries that keep their value
+            let mut emptied = vec![];
+            let mut len = 0;
 
-// Max size of the pool
-const MAX: usize = usize::MAX >> 1;
+            while idx != NIL {
+                let ptr = self.entry_ptr(idx);
+                let next = (*ptr).next.load(Ordering::Relaxed);
 
-impl<T> PoolInner<T> {
-    fn with_capacity(count: usize, mut extra: usize) -> PoolInner<T> {
-        // The required alignment for the entry. The start of the entry must
-        // align with this number
-        let align = mem::align_of::<Entry<T>>();
+                len += 1;
 
-        // Check that the capacity is not too large
-        assert!(count < MAX, "requested pool size too big");
-        assert!(align > 0, "something weird is up with the requested alignment");
+                match if taken < limit { Entry::take(ptr) } else { None } {
+                    Some(value) => {
+                        taken += 1;
+                        self.measure(ptr);
+                        f(value, (*ptr).extra_mut());
+                        emptied.push(idx);
+                    }
+                    // Already empty entries only exist with an initializer
+                    None if !(*ptr).init => emptied.push(idx),
+                    None => kept.push(idx),
+                }
 
-        let mask = align - 1;
+                idx = next;
+            }
+
+            if ptr::eq(list, &self.next) && self.is_tiered() {
+                self.hot_len.fetch_sub(len - kept.len(), Ordering::Relaxed);
+            }
+
+            self.push_chain(list, &kept);
 
-        // If the requested extra memory does not match with the align,
-        // increase it so that it does.
-        if extra & mask != 0 {
-            extra = (extra + align) & !mask;
+            // With an initializer, the empty entries go back on the free list
+            // to be filled on checkout.
+            if self.init.is_some() {
+                self.push_chain(self.idle_list(), &emptied);
+            } else {
+                self.vacant().extend(emptied);
+            }
         }
 
-        // Calculate the size of each entry. Since the extra bytes are
-        // immediately after the entry, just add the sizes
-        let entry_size = mem::size_of::<Entry<T>>() + extra;
+        taken
+    }
 
-        // This should always be true, but let's check it anyway
-        assert!(entry_size & mask == 0, "entry size is not aligned");
+    // Links the given entries in order and pushes them onto the given free
+    // list
+    unsafe fn push_chain(&self, list: &AtomicUsize, entries: &[usize]) {
+        let (&first, &last) = match (entries.first(), entries.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
 
-        // Ensure that the total memory needed is possible. It must be
-        // representable by an `isize` value in order for pointer offset to
-        // work.
-        assert!(entry_size.checked_mul(count).is_some(), "requested pool capacity too big");
-        assert!(entry_size * count < MAX, "requested pool capacity too big");
+        for pair in entries.windows(2) {
+            (*self.entry_ptr(pair[0])).next.store(pair[1], Ordering::Relaxed);
+        }
 
-        let size = count * entry_size;
+        self.push_list(list, first, self.entry_ptr(last));
+    }
 
-        // Allocate the memory
-        let (memory, ptr) = alloc(size, align);
+    // Must only be called by the owner of the pool.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn vacant(&self) -> &mut Vec<usize> {
+        &mut *self.vacant.get()
+    }
 
-        // Zero out the memory for safety
-        unsafe {
-            ptr::write_bytes(ptr, 0, size);
-        }
+    // Take an entry that is being handed off by a concurrent checkin
+    unsafe fn eliminate_checkout(&self) -> Option<*mut Entry<T>> {
+        for slot in self.elimination.iter() {
+            let idx = slot.load(Ordering::Relaxed);
 
-        PoolInner {
-            memory: memory,
-            next: AtomicUsize::new(0),
-            ptr: ptr as *mut Entry<T>,
-            init: 0,
-            count: count,
-            entry_size: entry_size,
+            if idx == EMPTY {
+                continue;
+            }
+
+            if slot.compare_exchange(idx, EMPTY, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return Some(self.entry_ptr(idx));
+            }
         }
+
+        None
     }
 
-    fn checkout(&mut self) -> Option<*mut Entry<T>> {
-        let mut idx = self.next.load(Ordering::Acquire);
+    fn checkin(&self, ptr: *mut Entry<T>) {
+        let idx = unsafe { (*ptr).idx };
 
-        loop {
-            debug_assert!(idx <= self.count, "invalid index: {}", idx);
+        unsafe {
+            let checked_out = (*ptr).tracked().map_or(Stamp::NONE, |track| track.checked_out);
 
-            if idx == self.count {
-                // The pool is depleted
-                return None;
+            if let Some(start) = checked_out.get() {
+                let nanos = cmp::min(start.elapsed().as_nanos(), u64::MAX as u128) as u64;
+                let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+
+                (*Entry::track(ptr)).checked_out = Stamp::NONE;
+                self.hold_times[bucket].fetch_add(1, Ordering::Relaxed);
+            }
+
+            if self.track_extra {
+                // Before wiping, which would hide the bytes written
+                Entry::update_extra_used(ptr);
+            }
+
+            // The entry of a revoked checkout was replaced, the value is not
+            // to be reused
+            let revoked = (*ptr).tracked().is_some_and(|track| {
+                track.lease.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |_| Some(0)) == Ok(REVOKED)
+            });
+
+            if revoked {
+                (*ptr).retire = true;
+            }
+
+            if (*ptr).retire {
+                // Pinned values must not move and retired ones are not to be
+                // reused, they are dropped and the empty entry is filled on
+                // checkout
+                (*ptr).retire = false;
+                Entry::drop_value(ptr);
+                self.events.send(PoolEvent::EntryRetired);
+            }
+
+            if let Some(wipe) = self.wipe {
+                let extra: *mut [u8] = (*ptr).extra_mut();
+                let data = if (*ptr).init { Some((*ptr).data_mut()) } else { None };
+                wipe(data, &mut *extra);
+            }
+
+            if idx == NIL {
+                self.free_transient(ptr);
+                return;
+            }
+
+            if let Some(ref budget) = self.budget {
+                self.measure(ptr);
+
+                // Not worth keeping, the entry is filled again on checkout
+                if (*ptr).init && budget.is_over(self.entry_size) {
+                    Entry::drop_value(ptr);
+                    self.measure(ptr);
+                    self.events.send(PoolEvent::EntryRetired);
+                }
+            }
+
+            if revoked {
+                // Out of the capacity before out of use, so the pool never
+                // looks to have more available than it does
+                self.spare_len.fetch_add(1, Ordering::Relaxed);
             }
 
-            let nxt = self.entry_mut(idx).next;
+            // Before the entry can be checked out again
+            let in_use = self.in_use.fetch_sub(1, Ordering::Relaxed) - 1;
 
-            debug_assert!(nxt <= self.count, "invalid next index: {}", idx);
+            if let Some(ref watermark) = self.low_watermark {
+                watermark.update(in_use);
+            }
 
-            let res = self.next.compare_and_swap(idx, nxt, Ordering::Relaxed);
+            if revoked {
+                // The replacement took the entry's place, set it aside for
+                // the next revoked lease rather than keep the pool grown
+                self.push_list(&self.spare, idx, ptr);
+                return;
+            }
 
-            if res == idx {
-                break;
+            if self.track_idle {
+                (*Entry::track(ptr)).checked_in = Stamp::now();
             }
 
-            // Re-acquire the memory before trying again
-            atomic::fence(Ordering::Acquire);
-            idx = res;
+            // Reserve room in the hot tier, spill to the cold one if full
+            if self.is_tiered() && self.hot_len.fetch_add(1, Ordering::Relaxed) >= self.hot_capacity {
+                self.hot_len.fetch_sub(1, Ordering::Relaxed);
+                self.push_list(&self.cold, idx, ptr);
+            } else if self.lru {
+                self.push_list(&self.recent, idx, ptr);
+            } else {
+                self.push(idx, ptr);
+            }
         }
 
-        Some(self.entry_mut(idx) as *mut Entry<T>)
+        self.events.recovered();
+        self.notify();
     }
 
-    fn checkin(&self, ptr: *mut Entry<T>) {
-        let mut idx;
-        let mut entry: &mut Entry<T>;
+    // Pushes a chain of linked entries onto the given free list. Unlike
+    // `push`, never hands entries off to a concurrent checkout.
+    unsafe fn push_list(&self, list: &AtomicUsize, first: usize, last: *mut Entry<T>) {
+        let mut head = list.load(Ordering::Relaxed);
 
-        unsafe {
-            // Figure out the index
-            idx = ((ptr as usize) - (self.ptr as usize)) / self.entry_size;
-            entry = mem::transmute(ptr);
-        }
+        loop {
+            (*last).next.store(head & NIL, Ordering::Relaxed);
 
-        debug_assert!(idx < self.count, "invalid index; idx={}", idx);
+            match list.compare_exchange_weak(head, push_head(head, first), Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
 
-        let mut nxt = self.next.load(Ordering::Relaxed);
+    // Pushes a chain of linked entries, starting at index `first` and ending
+    // with the entry at `last`, onto the free list.
+    unsafe fn push(&self, first: usize, last: *mut Entry<T>) {
+        let mut head = self.next.load(Ordering::Relaxed);
 
         loop {
             // Update the entry's next pointer
-            entry.next = nxt;
+            (*last).next.store(head & NIL, Ordering::Relaxed);
 
-            let actual = self.next.compare_and_swap(nxt, idx, Ordering::Release);
+            let actual = match self.next.compare_exchange_weak(head, push_head(head, first), Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => actual,
+            };
 
-            if actual == nxt {
-                break;
+            // Lost the race for the head, offer a single entry to a
+            // concurrent checkout before trying again. A spurious failure
+            // just retries.
+            if actual != head && (*last).idx == first && self.eliminate_checkin(first) {
+                return;
             }
 
-            nxt = actual;
+            head = actual;
         }
     }
 
-    fn entry(&self, idx: usize) -> &Entry<T> {
-        unsafe {
-            debug_assert!(idx < self.count, "invalid index");
-            let ptr = self.ptr.offset(idx as isize);
-            mem::transmute(ptr)
+    // Offer the entry at `idx` to a concurrent checkout. Returns true if the
+    // entry was taken.
+    fn eliminate_checkin(&self, idx: usize) -> bool {
+        let slot = &self.elimination[idx % ELIMINATION_SLOTS];
+
+        if slot.compare_exchange(EMPTY, idx, Ordering::Release, Ordering::Relaxed).is_err() {
+            // Slot in use by another checkin
+            return false;
         }
+
+        for _ in 0..ELIMINATION_SPINS {
+            if slot.load(Ordering::Relaxed) != idx {
+                return true;
+            }
+
+            hint::spin_loop();
+        }
+
+        // Nobody showed up, withdraw the offer. If this fails, a checkout
+        // took the entry in the meantime.
+        slot.compare_exchange(idx, EMPTY, Ordering::Relaxed, Ordering::Relaxed).is_err()
+    }
+
+    // Number of extra bytes available to each entry
+    fn extra(&self) -> usize {
+        self.entry_size - mem::size_of::<Entry<T>>()
+    }
+
+    // Whether entries of both pools have the same extra storage, so values
+    // can be swapped between them
+    fn same_layout(&self, other: &PoolInner<T>) -> bool {
+        self.extra() == other.extra() && self.align == other.align
+    }
+
+    // Returns a pointer to the entry at `idx`, which must have been written
+    unsafe fn entry_ptr(&self, idx: usize) -> *mut Entry<T> {
+        debug_assert!(idx < NIL, "invalid index");
+
+        let segment = *self.table.load(Ordering::Acquire).add(idx / self.segment_len);
+        segment.add((idx % self.segment_len) * self.entry_size) as *mut Entry<T>
     }
+}
+
+impl<T> PoolInner<T> {
+    // Moves every value out of the pool, leaving it empty
+    fn take_values(&mut self) -> Vec<T> {
+        let mut values = vec![];
+
+        for segment in self.segments.get_mut().iter() {
+            for i in 0..segment.len {
+                unsafe {
+                    let entry = segment.ptr.add(i * self.entry_size) as *mut Entry<T>;
+                    values.extend(Entry::take(entry));
+                }
+            }
+        }
 
-    #[allow(mutable_transmutes)]
-    fn entry_mut(&mut self, idx: usize) -> &mut Entry<T> {
-        unsafe { mem::transmute(self.entry(idx)) }
+        self.next.store(NIL, Ordering::Relaxed);
+        self.recent.store(NIL, Ordering::Relaxed);
+        values
     }
 }
 
 impl<T> Drop for PoolInner<T> {
+    fn drop(&mut self) {
+        for segment in self.segments.get_mut().iter() {
+            for i in 0..segment.len {
+                unsafe {
+                    Entry::release(segment.ptr.add(i * self.entry_size) as *mut Entry<T>);
+                }
+            }
+        }
+    }
+}
+
+// Drops the entries written so far by one thread of a parallel
+// initialization, unless forgotten once the thread is done.
+struct InitGuard<T> {
+    base: usize,        // Address of the first entry of the range
+    entry_size: usize,  // Byte size of each entry
+    init: usize,        // Number of initialized entries
+    _p: PhantomData<T>,
+}
+
+impl<T> InitGuard<T> {
+    fn next_ptr(&self) -> *mut Entry<T> {
+        (self.base + self.init * self.entry_size) as *mut Entry<T>
+    }
+}
+
+impl<T> Drop for InitGuard<T> {
     fn drop(&mut self) {
         for i in 0..self.init {
             unsafe {
-                let _ = ptr::read(self.entry(i));
+                Entry::release((self.base + i * self.entry_size) as *mut Entry<T>);
             }
         }
     }
 }
 
+// A point in time kept in an entry, as nanoseconds since the first one was
+// taken, plus one so zero means none. Half the size of an `Option<Instant>`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Stamp(u64);
+
+impl Stamp {
+    const NONE: Stamp = Stamp(0);
+
+    fn now() -> Stamp {
+        Stamp::at(Instant::now())
+    }
+
+    // Points in time before the first stamp are rounded up to it
+    fn at(instant: Instant) -> Stamp {
+        let nanos = instant.saturating_duration_since(Stamp::base()).as_nanos();
+        Stamp(cmp::min(nanos, (u64::MAX - 1) as u128) as u64 + 1)
+    }
+
+    fn get(self) -> Option<Instant> {
+        match self.0 {
+            0 => None,
+            n => Some(Stamp::base() + Duration::from_nanos(n - 1)),
+        }
+    }
+
+    fn base() -> Instant {
+        static BASE: OnceLock<Instant> = OnceLock::new();
+        *BASE.get_or_init(Instant::now)
+    }
+}
+
+#[repr(C)]
 struct Entry<T> {
-    data: T,       // Keep first
-    next: usize,   // Index of next available entry
+    data: MaybeUninit<T>, // Keep first
+    next: AtomicUsize, // Index of next available entry
+    idx: usize,    // Index of this entry
     extra: usize,  // Number of extra bytes available
+    uses: usize,   // Number of checkouts of the current value
+    track: AtomicPtr<Track<T>>, // See `Track`, null until first needed
+    init: bool,    // Whether `data` holds a value
+    retire: bool,  // Whether the value is dropped in place on checkin
+}
+
+// What only some pools keep about an entry, allocated the first time the
+// entry needs it. Entries of pools that track nothing are left without.
+//
+// Only whoever holds the entry allocates it, the checkout or the owner of the
+// pool while the entry is idle.
+struct Track<T> {
+    checked_in: Stamp,  // Last checkin, if tracked
+    checked_out: Stamp, // Current checkout, if tracked
+    created: Stamp,     // When the current value was created, if tracked
+    shared: AtomicUsize, // Number of `SharedCheckout` handles to the entry
+    generation: usize,   // Bumped when parked and unparked, odd while parked
+    extra_used: AtomicUsize, // High-water mark of the extra bytes, if tracked
+    owner: *const PoolInner<T>, // Set while checked out as a raw pointer
+    epoch: usize,        // Epoch of the initializer that created the value, if replaced
+    lease: AtomicUsize,  // Deadline of the current checkout's lease, if any
+    heap: usize,         // Heap bytes owned by the value, if measured
 }
 
 impl<T> Entry<T> {
+    // Initializes the entry at `ptr`, handing the slot index and the entry's
+    // extra bytes to the initializer. The entry is linked to the one after it.
+    unsafe fn init<F>(ptr: *mut Entry<T>, idx: usize, extra: usize, init: F)
+            where F: FnOnce(usize, &mut [u8]) -> T {
+        use std::slice;
+
+        let bytes = (ptr as *mut u8).add(mem::size_of::<Entry<T>>());
+        let data = init(idx, slice::from_raw_parts_mut(bytes, extra));
+
+        ptr::write(ptr, Entry {
+            data: MaybeUninit::new(data),
+            next: AtomicUsize::new(idx + 1),
+            idx,
+            extra,
+            uses: 0,
+            track: AtomicPtr::new(ptr::null_mut()),
+            init: true,
+            retire: false,
+        });
+    }
+
+    // Writes an empty entry at `ptr`, linked to the one after it
+    unsafe fn init_empty(ptr: *mut Entry<T>, idx: usize, extra: usize) {
+        ptr::write(ptr, Entry {
+            data: MaybeUninit::uninit(),
+            next: AtomicUsize::new(idx + 1),
+            idx,
+            extra,
+            uses: 0,
+            track: AtomicPtr::new(ptr::null_mut()),
+            init: false,
+            retire: false,
+        });
+    }
+
+    // Returns the entry's tracked state, if it was ever needed
+    fn tracked(&self) -> Option<&Track<T>> {
+        unsafe { self.track.load(Ordering::Acquire).as_ref() }
+    }
+
+    // Returns the tracked state of the entry at `ptr`, allocating it first if
+    // needed. Must only be called by whoever holds the entry.
+    unsafe fn track(ptr: *mut Entry<T>) -> *mut Track<T> {
+        let track = (*ptr).track.load(Ordering::Acquire);
+
+        if !track.is_null() {
+            return track;
+        }
+
+        let track = Box::into_raw(Box::new(Track {
+            checked_in: Stamp::NONE,
+            checked_out: Stamp::NONE,
+            created: Stamp::NONE,
+            shared: AtomicUsize::new(0),
+            generation: 0,
+            extra_used: AtomicUsize::new(0),
+            owner: ptr::null(),
+            epoch: 0,
+            lease: AtomicUsize::new(0),
+            heap: 0,
+        }));
+
+        (*ptr).track.store(track, Ordering::Release);
+        track
+    }
+
+    fn checked_in(&self) -> Stamp {
+        self.tracked().map_or(Stamp::NONE, |track| track.checked_in)
+    }
+
+    fn checked_out(&self) -> Stamp {
+        self.tracked().map_or(Stamp::NONE, |track| track.checked_out)
+    }
+
+    fn created(&self) -> Stamp {
+        self.tracked().map_or(Stamp::NONE, |track| track.created)
+    }
+
+    fn generation(&self) -> usize {
+        self.tracked().map_or(0, |track| track.generation)
+    }
+
+    fn extra_used(&self) -> usize {
+        self.tracked().map_or(0, |track| track.extra_used.load(Ordering::Relaxed))
+    }
+
+    fn epoch(&self) -> usize {
+        self.tracked().map_or(0, |track| track.epoch)
+    }
+
+    fn lease(&self) -> usize {
+        self.tracked().map_or(0, |track| track.lease.load(Ordering::Relaxed))
+    }
+
+    // Untracked entries are left without a stamp, unless they have one to
+    // overwrite
+    unsafe fn set_created(ptr: *mut Entry<T>, created: Stamp) {
+        if created != Stamp::NONE || (*ptr).tracked().is_some() {
+            (*Entry::track(ptr)).created = created;
+        }
+    }
+
+    // Values of the first initializer are of epoch zero, so entries only
+    // keep the epoch once the initializer was replaced
+    unsafe fn set_epoch(ptr: *mut Entry<T>, epoch: usize) {
+        if epoch != 0 || (*ptr).tracked().is_some() {
+            (*Entry::track(ptr)).epoch = epoch;
+        }
+    }
+
+    // Raises the high-water mark to the last non-zero extra byte. Bytes below
+    // the current mark are known to be used and are not looked at again.
+    unsafe fn update_extra_used(ptr: *mut Entry<T>) {
+        let extra = (*ptr).extra();
+        let used = &(*Entry::track(ptr)).extra_used;
+        let mark = used.load(Ordering::Relaxed);
+
+        if let Some(pos) = extra[mark..].iter().rposition(|&b| b != 0) {
+            used.store(mark + pos + 1, Ordering::Relaxed);
+        }
+    }
+
+    // Moves the value out of the entry at `ptr`, leaving it empty
+    unsafe fn take(ptr: *mut Entry<T>) -> Option<T> {
+        if !(*ptr).init {
+            return None;
+        }
+
+        (*ptr).init = false;
+        Some(ptr::read((*ptr).data.as_ptr()))
+    }
+
+    // Swaps the values of two entries, along with what is known about them,
+    // and as many extra bytes as both have
+    unsafe fn swap_values(a: *mut Entry<T>, b: *mut Entry<T>) {
+        mem::swap(&mut (*a).data, &mut (*b).data);
+        mem::swap(&mut (*a).init, &mut (*b).init);
+        mem::swap(&mut (*a).uses, &mut (*b).uses);
+
+        if (*a).tracked().is_some() || (*b).tracked().is_some() {
+            let (a, b) = (Entry::track(a), Entry::track(b));
+            mem::swap(&mut (*a).created, &mut (*b).created);
+            mem::swap(&mut (*a).epoch, &mut (*b).epoch);
+        }
+
+        (*a).extra_mut().swap_with_slice((*b).extra_mut());
+    }
+
+    // Drops the value and the tracked state of the entry at `ptr`, before
+    // its memory is freed
+    unsafe fn release(ptr: *mut Entry<T>) {
+        Entry::drop_value(ptr);
+
+        let track = (*ptr).track.load(Ordering::Relaxed);
+
+        if !track.is_null() {
+            drop(Box::from_raw(track));
+        }
+    }
+
+    // Drops the value of the entry at `ptr`, leaving it empty
+    unsafe fn drop_value(ptr: *mut Entry<T>) {
+        if (*ptr).init {
+            (*ptr).init = false;
+            ptr::drop_in_place((*ptr).data.as_mut_ptr());
+        }
+    }
+
+    fn data(&self) -> &T {
+        debug_assert!(self.init, "entry is empty");
+        unsafe { self.data.assume_init_ref() }
+    }
+
+    fn data_mut(&mut self) -> &mut T {
+        debug_assert!(self.init, "entry is empty");
+        unsafe { self.data.assume_init_mut() }
+    }
+
     fn extra(&self) -> &[u8] {
         use std::slice;
 
         unsafe {
-            let ptr: *const u8 = mem::transmute(self);
-            let ptr = ptr.offset(mem::size_of::<Entry<T>>() as isize);
-
+            let ptr = (self as *const Entry<T> as *const u8).add(mem::size_of::<Entry<T>>());
             slice::from_raw_parts(ptr, self.extra)
         }
     }
 
-    #[allow(mutable_transmutes)]
     fn extra_mut(&mut self) -> &mut [u8] {
-        unsafe { mem::transmute(self.extra()) }
+        use std::slice;
+
+        unsafe {
+            let ptr = (self as *mut Entry<T> as *mut u8).add(mem::size_of::<Entry<T>>());
+            slice::from_raw_parts_mut(ptr, self.extra)
+        }
     }
 }
 
-/// Allocate memory
-fn alloc(mut size: usize, align: usize) -> (Box<[u8]>, *mut u8) {
-    size += align;
+// Returns the free list head after popping its first entry, whose successor
+// is `next`. Bumps the tag.
+fn pop_head(head: usize, next: usize) -> usize {
+    (head & !NIL).wrapping_add(NIL + 1) | next
+}
+
+// Returns the free list head after pushing a chain of entries starting with
+// `first`
+fn push_head(head: usize, first: usize) -> usize {
+    (head & !NIL) | first
+}
 
-    unsafe {
-        // Allocate the memory
-        let mut vec = Vec::with_capacity(size);
-        vec.set_len(size);
+// Takes every entry off the free list at once, returning the index of the
+// first one
+fn take_list(list: &AtomicUsize) -> usize {
+    let head = list.fetch_update(Ordering::Acquire, Ordering::Acquire, |head| Some(pop_head(head, NIL)));
+    head.unwrap() & NIL
+}
 
-        // Juggle values around
-        let mut mem = vec.into_boxed_slice();
-        let ptr = (*mem).as_mut_ptr();
+// Hint the CPU to pull `len` bytes starting at `ptr` into the cache
+#[inline]
+fn prefetch(ptr: *const u8, len: usize) {
+    #[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse")))]
+    {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
 
-        // Align the pointer
-        let p = ptr as usize;
-        let m = align - 1;
+        let mut offset = 0;
 
-        if p & m != 0 {
-            let p = (p + align) & !m;
-            return (mem, p as *mut u8);
+        while offset < len {
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(ptr.add(offset) as *const i8); }
+            offset += CACHE_LINE;
         }
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse"))))]
+    {
+        let _ = (ptr, len);
+    }
+}
+
+/// Allocate zeroed memory
+fn alloc(mut size: usize, align: usize) -> (Box<[u8]>, *mut u8) {
+    size += align;
+
+    // Allocate the memory
+    let mut mem = vec![0u8; size].into_boxed_slice();
+    let ptr = (*mem).as_mut_ptr();
 
-        (mem, ptr)
+    // Align the pointer
+    let p = ptr as usize;
+    let m = align - 1;
+
+    if p & m != 0 {
+        let p = (p + align) & !m;
+        return (mem, p as *mut u8);
     }
+
+    (mem, ptr)
 }
@@ -0,0 +1,192 @@
+use {Arena, Bump, Entry, Reset, NIL, alloc};
+use std::{mem, ops};
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+
+/// A pool of reusable values for use from a single thread.
+///
+/// Works like `Pool`, but values are never returned from another thread, so
+/// there is no need for atomic reference counts or lock-free free lists.
+/// Neither the pool nor its checkouts can be sent to another thread, which
+/// in turn lets the initializer and the values be anything.
+///
+/// ```
+/// use pool::{LocalPool, Dirty};
+/// use std::rc::Rc;
+///
+/// let shared = Rc::new("not Send");
+/// let mut pool = LocalPool::with_capacity(4, 0, move || Dirty(shared.clone()));
+///
+/// let val = pool.checkout();
+/// assert_eq!("not Send", ***val);
+/// ```
+pub struct LocalPool<T: Reset> {
+    inner: Rc<LocalInner<T>>,
+}
+
+impl<T: Reset> LocalPool<T> {
+    /// Creates a new pool that can contain up to `capacity` entries as well as
+    /// `extra` extra bytes. Initializes each entry with the given function.
+    pub fn with_capacity<F>(count: usize, extra: usize, init: F) -> LocalPool<T>
+            where F: Fn() -> T {
+        let align = mem::align_of::<Entry<T>>();
+        let mask = align - 1;
+
+        // Round up to the alignment, as `Pool` does. Any padding is handed
+        // out as extra bytes.
+        let entry_size = (mem::size_of::<Entry<T>>() + extra + mask) & !mask;
+        let extra = entry_size - mem::size_of::<Entry<T>>();
+
+        assert!(count < NIL, "requested pool size too big");
+
+        let size = entry_size.checked_mul(count);
+        assert!(size.is_some_and(|size| size <= isize::MAX as usize), "requested pool capacity too big");
+
+        let (memory, ptr) = alloc(entry_size * count, align);
+        let mut inner = LocalInner {
+            next: Cell::new(if count == 0 { NIL } else { 0 }),
+            memory,
+            ptr,
+            len: 0,
+            entry_size,
+            _p: PhantomData,
+        };
+
+        for idx in 0..count {
+            unsafe {
+                Entry::init(inner.entry_ptr(idx), idx, extra, |_, _| init());
+            }
+
+            // Only drop what has been written, should `init` panic
+            inner.len = idx + 1;
+        }
+
+        if count > 0 {
+            // Terminate the free list
+            unsafe { (*inner.entry_ptr(count - 1)).next.store(NIL, Ordering::Relaxed) };
+        }
+
+        LocalPool { inner: Rc::new(inner) }
+    }
+
+    /// Checkout a value from the pool.
+    ///
+    /// The value is reset before it is returned, see `Reset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool is currently at capacity. Use `try_checkout` to
+    /// handle that case.
+    pub fn checkout(&mut self) -> LocalCheckout<T> {
+        self.try_checkout().expect("pool is depleted")
+    }
+
+    /// Checkout a value from the pool. Returns `None` if the pool is currently
+    /// at capacity.
+    pub fn try_checkout(&mut self) -> Option<LocalCheckout<T>> {
+        let idx = self.inner.next.get();
+
+        if idx == NIL {
+            return None;
+        }
+
+        let entry = self.inner.entry_ptr(idx);
+        self.inner.next.set(unsafe { (*entry).next.load(Ordering::Relaxed) });
+
+        let mut checkout = LocalCheckout {
+            entry,
+            inner: self.inner.clone(),
+        };
+
+        checkout.reset();
+        Some(checkout)
+    }
+}
+
+/// A handle to a value checked out from a `LocalPool`. When dropped out of
+/// scope, the value will be returned to the pool.
+pub struct LocalCheckout<T> {
+    entry: *mut Entry<T>,
+    inner: Rc<LocalInner<T>>,
+}
+
+impl<T> LocalCheckout<T> {
+    /// Read access to the raw bytes
+    pub fn extra(&self) -> &[u8] {
+        self.entry().extra()
+    }
+
+    /// Write access to the extra bytes
+    pub fn extra_mut(&mut self) -> &mut [u8] {
+        self.entry_mut().extra_mut()
+    }
+
+    /// Returns a bump allocator over the extra bytes, starting from the first
+    /// one. See `Bump`.
+    pub fn bump(&mut self) -> Bump<'_> {
+        Bump::new(self.extra_mut())
+    }
+
+    /// Returns an arena over the extra bytes, starting from the first one.
+    /// See `Arena`.
+    pub fn arena(&mut self) -> Arena<'_> {
+        Arena::new(self.extra_mut())
+    }
+
+    fn entry(&self) -> &Entry<T> {
+        unsafe { &*self.entry }
+    }
+
+    fn entry_mut(&mut self) -> &mut Entry<T> {
+        unsafe { &mut *self.entry }
+    }
+}
+
+impl<T> ops::Deref for LocalCheckout<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.entry().data()
+    }
+}
+
+impl<T> ops::DerefMut for LocalCheckout<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.entry_mut().data_mut()
+    }
+}
+
+impl<T> Drop for LocalCheckout<T> {
+    fn drop(&mut self) {
+        let entry = self.entry();
+
+        entry.next.store(self.inner.next.get(), Ordering::Relaxed);
+        self.inner.next.set(entry.idx);
+    }
+}
+
+struct LocalInner<T> {
+    next: Cell<usize>,  // Index of next available entry
+    #[allow(dead_code)]
+    memory: Box<[u8]>,  // Ownership of raw memory
+    ptr: *mut u8,       // Pointer to first entry
+    len: usize,         // Number of entries written
+    entry_size: usize,  // Byte size of each entry
+    _p: PhantomData<T>,
+}
+
+impl<T> LocalInner<T> {
+    fn entry_ptr(&self, idx: usize) -> *mut Entry<T> {
+        unsafe { self.ptr.add(idx * self.entry_size) as *mut Entry<T> }
+    }
+}
+
+impl<T> Drop for LocalInner<T> {
+    fn drop(&mut self) {
+        for idx in 0..self.len {
+            unsafe { Entry::release(self.entry_ptr(idx)); }
+        }
+    }
+}
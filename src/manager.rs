@@ -0,0 +1,258 @@
+//! Pools of values that are created and recycled asynchronously.
+//!
+//! Some values, such as database connections, can only be set up by async
+//! code. A `ManagedPool` leaves that to a `Manager`: its entries start out
+//! empty, and checkouts create a value with `Manager::create` or recycle the
+//! previous one with `Manager::recycle`, awaiting either without blocking the
+//! thread. Checkouts wait for an entry like `Pool::poll_checkout` does.
+//!
+//! A `ManagedPool` is a handle, its clones share the same values, and any
+//! number of tasks may wait on `ManagedPool::get` at once.
+//!
+//! ```
+//! extern crate pool;
+//!
+//! use pool::manager::{ManagedPool, Manager};
+//! use std::future::{self, Future};
+//! use std::task::{Context, Poll, Waker};
+//!
+//! struct Connect;
+//!
+//! impl Manager for Connect {
+//!     type Type = String;
+//!     type Error = ();
+//!
+//!     fn create(&self) -> impl Future<Output = Result<String, ()>> + Send {
+//!         future::ready(Ok("connection".to_string()))
+//!     }
+//!
+//!     fn recycle(&self, conn: String) -> impl Future<Output = Result<String, ()>> + Send {
+//!         future::ready(Ok(conn))
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let pool = ManagedPool::new(Connect, 4);
+//! let mut get = pool.get();
+//!
+//! let mut cx = Context::from_waker(Waker::noop());
+//!
+//! match Future::poll(std::pin::Pin::new(&mut get), &mut cx) {
+//!     Poll::Ready(conn) => assert_eq!("connection", *conn.unwrap()),
+//!     Poll::Pending => unreachable!(),
+//! }
+//! # }
+//! ```
+
+use {Checkout, Pool, PoolStatus, Reset};
+use std::{mem, ops};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// Creates and recycles the values of a `ManagedPool`.
+///
+/// Implementations in crates on the 2018 edition or later can write both
+/// methods as `async fn`.
+pub trait Manager {
+    /// The type of the pooled values.
+    type Type;
+
+    /// The error returned when creating or recycling a value fails.
+    type Error;
+
+    /// Creates a new value.
+    fn create(&self) -> impl Future<Output = Result<Self::Type, Self::Error>> + Send;
+
+    /// Readies a value for reuse, for example by checking that a connection
+    /// is still alive, before it is checked out again. A value that fails to
+    /// recycle is dropped and a new one is created in its place.
+    fn recycle(&self, value: Self::Type) -> impl Future<Output = Result<Self::Type, Self::Error>> + Send;
+}
+
+/// A pool of values created and recycled by a `Manager`.
+///
+/// Cloning the pool returns a new handle to the same values.
+pub struct ManagedPool<M: Manager> {
+    inner: Arc<Shared<M>>,
+}
+
+struct Shared<M: Manager> {
+    // Only locked while polling for an entry, never while the manager works
+    pool: Mutex<Pool<Slot<M::Type>>>,
+    manager: M,
+}
+
+// An entry of a managed pool, empty until the manager created its value
+struct Slot<T>(Option<T>);
+
+impl<T> Reset for Slot<T> {
+    fn reset(&mut self) {
+        // Recycled by the manager instead
+    }
+}
+
+impl<M: Manager> ManagedPool<M> {
+    /// Creates a pool of up to `capacity` values, created by `manager` as
+    /// they are first needed.
+    pub fn new(manager: M, capacity: usize) -> ManagedPool<M> {
+        let pool = Pool::builder().capacity(capacity).build(|| Slot(None));
+
+        ManagedPool {
+            inner: Arc::new(Shared {
+                pool: Mutex::new(pool),
+                manager,
+            }),
+        }
+    }
+
+    /// Returns a future that resolves to a checked out value, once an entry
+    /// is available and its value was created or recycled.
+    ///
+    /// Dropping the future gives up on the checkout, an entry it was waiting
+    /// on or working on is put back.
+    pub fn get(&self) -> Get<'_, M> {
+        Get {
+            shared: &self.inner,
+            state: State::Waiting,
+            waker: None,
+        }
+    }
+
+    /// Returns a reference to the manager.
+    pub fn manager(&self) -> &M {
+        &self.inner.manager
+    }
+
+    /// Returns a snapshot of the pool's counters, see `Pool::status`.
+    pub fn status(&self) -> PoolStatus {
+        self.inner.lock().status()
+    }
+}
+
+impl<M: Manager> Clone for ManagedPool<M> {
+    fn clone(&self) -> ManagedPool<M> {
+        ManagedPool { inner: self.inner.clone() }
+    }
+}
+
+impl<M: Manager> Shared<M> {
+    fn lock(&self) -> MutexGuard<'_, Pool<Slot<M::Type>>> {
+        // Polling doesn't panic with the pool half updated
+        self.pool.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A future checking out a value of a `ManagedPool`, see `ManagedPool::get`.
+pub struct Get<'a, M: Manager + 'a> {
+    shared: &'a Shared<M>,
+    state: State<'a, M>,
+    waker: Option<Waker>, // Left with the pool while waiting
+}
+
+type Pending<'a, M> = Pin<Box<dyn Future<Output = Result<<M as Manager>::Type, <M as Manager>::Error>> + Send + 'a>>;
+
+enum State<'a, M: Manager + 'a> {
+    Waiting,
+    // The entry's value is being created or recycled
+    Preparing(Checkout<Slot<M::Type>>, Pending<'a, M>, bool),
+    Done,
+}
+
+impl<'a, M: Manager + 'a> Future for Get<'a, M> {
+    type Output = Result<Managed<M::Type>, M::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            match this.state {
+                State::Waiting => {
+                    let polled = this.shared.lock().poll_checkout(cx);
+
+                    let mut checkout = match polled {
+                        Poll::Ready(checkout) => checkout,
+                        Poll::Pending => {
+                            this.waker = Some(cx.waker().clone());
+                            return Poll::Pending;
+                        }
+                    };
+
+                    this.waker = None;
+
+                    let recycling = checkout.0.is_some();
+                    let pending: Pending<'a, M> = match checkout.0.take() {
+                        Some(value) => Box::pin(this.shared.manager.recycle(value)),
+                        None => Box::pin(this.shared.manager.create()),
+                    };
+
+                    this.state = State::Preparing(checkout, pending, recycling);
+                }
+                State::Preparing(_, ref mut pending, recycling) => {
+                    let res = match pending.as_mut().poll(cx) {
+                        Poll::Ready(res) => res,
+                        Poll::Pending => return Poll::Pending,
+                    };
+
+                    let mut checkout = match mem::replace(&mut this.state, State::Done) {
+                        State::Preparing(checkout, _, _) => checkout,
+                        _ => unreachable!(),
+                    };
+
+                    match res {
+                        Ok(value) => {
+                            checkout.0 = Some(value);
+                            return Poll::Ready(Ok(Managed { checkout }));
+                        }
+                        // The value didn't survive, create a new one
+                        Err(_) if recycling => {
+                            let pending: Pending<'a, M> = Box::pin(this.shared.manager.create());
+                            this.state = State::Preparing(checkout, pending, false);
+                        }
+                        // The empty entry goes back to the pool
+                        Err(e) => return Poll::Ready(Err(e)),
+                    }
+                }
+                State::Done => panic!("`Get` polled after completion"),
+            }
+        }
+    }
+}
+
+impl<'a, M: Manager + 'a> Drop for Get<'a, M> {
+    fn drop(&mut self) {
+        // Hand a wakeup that may have been received to another waiter
+        if let Some(waker) = self.waker.take() {
+            self.shared.lock().cancel_poll_checkout(&waker);
+        }
+    }
+}
+
+/// A value checked out of a `ManagedPool`, returned to the pool when
+/// dropped.
+pub struct Managed<T> {
+    checkout: Checkout<Slot<T>>,
+}
+
+impl<T> Managed<T> {
+    /// Drops the value instead of returning it to the pool, for example a
+    /// connection found broken. The manager creates a new one in its place.
+    pub fn retire(mut self) {
+        self.checkout.0 = None;
+    }
+}
+
+impl<T> ops::Deref for Managed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.checkout.0.as_ref().unwrap()
+    }
+}
+
+impl<T> ops::DerefMut for Managed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.checkout.0.as_mut().unwrap()
+    }
+}
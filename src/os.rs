@@ -0,0 +1,90 @@
+// Platform specific memory management for the pool's backing memory. All
+// functions degrade to doing nothing where the platform doesn't support them.
+
+// Locks the memory at `ptr` into RAM so it is never swapped to disk. Returns
+// false if that failed, for example because of the process's limit on
+// locked memory.
+#[cfg(unix)]
+pub fn lock(ptr: *const u8, len: usize) -> bool {
+    unsafe { ::libc::mlock(ptr as *const ::libc::c_void, len) == 0 }
+}
+
+#[cfg(windows)]
+pub fn lock(ptr: *const u8, len: usize) -> bool {
+    unsafe { ::windows_sys::Win32::System::Memory::VirtualLock(ptr as *const _, len) != 0 }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn lock(_: *const u8, _: usize) -> bool {
+    false
+}
+
+// Undoes `lock`
+#[cfg(unix)]
+pub fn unlock(ptr: *const u8, len: usize) {
+    unsafe { ::libc::munlock(ptr as *const ::libc::c_void, len); }
+}
+
+#[cfg(windows)]
+pub fn unlock(ptr: *const u8, len: usize) {
+    unsafe { ::windows_sys::Win32::System::Memory::VirtualUnlock(ptr as *const _, len); }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn unlock(_: *const u8, _: usize) {
+}
+
+// Lets the OS reclaim the physical memory of the whole pages within `len`
+// bytes at `ptr`, keeping the address space. Their contents are lost. Returns
+// the number of bytes released.
+#[cfg(unix)]
+pub fn release(ptr: *mut u8, len: usize) -> usize {
+    let page = page_size();
+
+    // Round inwards, the partial pages at either end hold other data
+    let start = (ptr as usize + page - 1) & !(page - 1);
+    let end = (ptr as usize + len) & !(page - 1);
+
+    if end <= start {
+        return 0;
+    }
+
+    match unsafe { ::libc::madvise(start as *mut ::libc::c_void, end - start, ::libc::MADV_DONTNEED) } {
+        0 => end - start,
+        _ => 0,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn release(_: *mut u8, _: usize) -> usize {
+    0
+}
+
+// Hints that the memory at `ptr` should be backed by huge pages
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn advise_huge(ptr: *const u8, len: usize) {
+    let page = page_size();
+
+    // `madvise` requires a page aligned start
+    let start = (ptr as usize + page - 1) & !(page - 1);
+    let end = ptr as usize + len;
+
+    if end > start {
+        unsafe { ::libc::madvise(start as *mut ::libc::c_void, end - start, ::libc::MADV_HUGEPAGE); }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn advise_huge(_: *const u8, _: usize) {
+}
+
+// Size of a page of virtual memory
+#[cfg(unix)]
+pub fn page_size() -> usize {
+    unsafe { ::libc::sysconf(::libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(not(unix))]
+pub fn page_size() -> usize {
+    4096
+}
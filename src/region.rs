@@ -0,0 +1,62 @@
+use std::slice;
+use std::marker::PhantomData;
+
+// A named region of the extra bytes of each entry, see `Builder::region`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Region {
+    pub name: &'static str,
+    pub offset: usize,
+    pub len: usize,
+}
+
+// Lays out the regions one after the other, each at its alignment, and
+// returns them along with the number of bytes they cover
+pub(crate) fn layout(specs: &[(&'static str, usize, usize)]) -> (Vec<Region>, usize) {
+    let mut end: usize = 0;
+
+    let regions = specs.iter().map(|&(name, len, align)| {
+        let offset = end.checked_add(align - 1).expect("requested regions too big") & !(align - 1);
+        end = offset.checked_add(len).expect("requested regions too big");
+
+        Region { name, offset, len }
+    }).collect();
+
+    (regions, end)
+}
+
+/// An iterator over the named regions of a checkout's extra bytes, created
+/// by `Checkout::regions_mut`.
+///
+/// Yields the name and bytes of each region, in the order they were added to
+/// the builder.
+pub struct RegionsMut<'a> {
+    regions: slice::Iter<'a, Region>,
+    ptr: *mut u8,
+    _p: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> RegionsMut<'a> {
+    pub(crate) fn new(regions: &'a [Region], extra: &'a mut [u8]) -> RegionsMut<'a> {
+        RegionsMut {
+            regions: regions.iter(),
+            ptr: extra.as_mut_ptr(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for RegionsMut<'a> {
+    type Item = (&'static str, &'a mut [u8]);
+
+    fn next(&mut self) -> Option<(&'static str, &'a mut [u8])> {
+        // Regions don't overlap and all lie within the extra bytes, so the
+        // slices handed out are disjoint
+        self.regions.next().map(|region| unsafe {
+            (region.name, slice::from_raw_parts_mut(self.ptr.add(region.offset), region.len))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.regions.size_hint()
+    }
+}
@@ -1,7 +1,11 @@
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque};
 use std::default::Default;
+use std::future::Future;
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Dirty<T>(pub T);
 
 impl <T> Reset for Dirty<T> {
@@ -10,9 +14,6 @@ impl <T> Reset for Dirty<T> {
     }
 }
 
-unsafe impl <T: Send> Send for Dirty<T> {}
-unsafe impl <T: Sync> Sync for Dirty<T> {}
-
 impl <T> Deref for Dirty<T> {
     type Target = T;
     fn deref(&self) -> &T {
@@ -26,15 +27,185 @@ impl <T> DerefMut for Dirty<T> {
     }
 }
 
+/// Resets the wrapped value to its default with `clone_from`.
+///
+/// This is what `Reset` used to do for every `Default + Clone` type. For most
+/// of the stdlib collections, this will "clear" the collection without
+/// deallocating.
+///
+/// ```
+/// use pool::{Pool, Clean};
+///
+/// #[derive(Clone, Default)]
+/// struct Request {
+///     path: String,
+///     headers: Vec<(String, String)>,
+/// }
+///
+/// let mut pool = Pool::with_capacity(1, 0, || Clean(Request::default()));
+///
+/// pool.checkout().path.push_str("/index.html");
+/// assert!(pool.checkout().path.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Clean<T>(pub T);
+
+impl <T: Default + Clone> Reset for Clean<T> {
+    fn reset(&mut self) {
+        self.0.clone_from(&Default::default());
+    }
+}
+
+impl <T> Deref for Clean<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl <T> DerefMut for Clean<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "secure")]
+impl <T: ::zeroize::Zeroize> ::zeroize::Zeroize for Dirty<T> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "secure")]
+impl <T: ::zeroize::Zeroize> ::zeroize::Zeroize for Clean<T> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "serde")]
+impl <T: ::serde::Serialize> ::serde::Serialize for Dirty<T> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl <T: ::serde::Serialize> ::serde::Serialize for Clean<T> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl <T: Future> Future for Dirty<T> {
+    type Output = T::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T::Output> {
+        // Pinning is structural, `Dirty` never moves the value out
+        unsafe { self.map_unchecked_mut(|dirty| &mut dirty.0) }.poll(cx)
+    }
+}
+
+#[cfg(unix)]
+impl <T: ::std::os::unix::io::AsRawFd> ::std::os::unix::io::AsRawFd for Dirty<T> {
+    fn as_raw_fd(&self) -> ::std::os::unix::io::RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl <T: ::std::os::unix::io::AsFd> ::std::os::unix::io::AsFd for Dirty<T> {
+    fn as_fd(&self) -> ::std::os::unix::io::BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
 /// Resetting an object reverts that object back to a default state.
+///
+/// Implement it for your own types to control what is kept between uses, or
+/// wrap them in `Dirty` or `Clean`.
 pub trait Reset {
     fn reset(&mut self);
 }
 
-// For most of the stdlib collections, this will "clear" the collection
-// without deallocating.
-impl <T: Default + Clone> Reset for T {
+/// Resetting with a context value that is shared by all values of a pool.
+///
+/// Useful when how a value is reset depends on runtime configuration that
+/// the value itself shouldn't have to store. The context is set with
+/// `Builder::reset_context`, pools without one fall back to `Reset::reset`.
+///
+/// ```
+/// use pool::{Pool, Reset, ResetWith};
+///
+/// struct Buf(Vec<u8>);
+///
+/// impl Reset for Buf {
+///     fn reset(&mut self) {
+///         self.0.clear();
+///     }
+/// }
+///
+/// // Shrinks buffers that grew past the configured limit
+/// impl ResetWith<usize> for Buf {
+///     fn reset_with(&mut self, limit: &usize) {
+///         self.0.clear();
+///         self.0.shrink_to(*limit);
+///     }
+/// }
+///
+/// let mut pool = Pool::builder()
+///     .capacity(1)
+///     .reset_context(1_024)
+///     .build(|| Buf(vec![]));
+///
+/// pool.checkout().0.resize(1 << 20, 0);
+/// assert!(pool.checkout().0.capacity() < 1 << 20);
+/// ```
+pub trait ResetWith<C>: Reset {
+    fn reset_with(&mut self, ctx: &C);
+}
+
+// Collections are cleared, keeping their allocations around for reuse
+macro_rules! reset_clear {
+    ($($ty:ident<$($param:ident),*>;)*) => {$(
+        impl<$($param),*> Reset for $ty<$($param),*> {
+            fn reset(&mut self) {
+                self.clear();
+            }
+        }
+    )*}
+}
+
+reset_clear! {
+    Vec<T>;
+    VecDeque<T>;
+    LinkedList<T>;
+    BinaryHeap<T>;
+    BTreeMap<K, V>;
+    BTreeSet<T>;
+    HashMap<K, V, S>;
+    HashSet<T, S>;
+}
+
+impl Reset for String {
     fn reset(&mut self) {
-        self.clone_from(&Default::default());
+        self.clear();
     }
 }
+
+// Primitives go back to their default
+macro_rules! reset_default {
+    ($($ty:ty)*) => {$(
+        impl Reset for $ty {
+            fn reset(&mut self) {
+                *self = Default::default();
+            }
+        }
+    )*}
+}
+
+reset_default! {
+    bool char f32 f64
+    i8 i16 i32 i64 i128 isize
+    u8 u16 u32 u64 u128 usize
+}
@@ -0,0 +1,343 @@
+use {Checkout, Extra};
+use std::{cmp, io, slice};
+use std::marker::PhantomData;
+use sync::{AtomicUsize, Ordering};
+
+/// A byte ring buffer over a checkout's extra bytes, created by
+/// `ExtraRing::new`.
+///
+/// Bytes are written at the head and read from the tail, wrapping around at
+/// the end of the extra bytes, so a pooled entry can serve as a connection's
+/// I/O buffer. The ring implements `io::Read` and `io::Write`, and exposes
+/// its free and filled bytes as two slices each, for filling the ring with
+/// vectored reads and draining it with vectored writes.
+///
+/// For a producer and a consumer on separate threads, `split` the ring.
+///
+/// ```
+/// use pool::{Dirty, ExtraRing, Pool};
+/// use std::io::{Read, Write};
+///
+/// let mut pool = Pool::with_capacity(4, 16, || Dirty(()));
+/// let mut ring = ExtraRing::new(pool.checkout());
+///
+/// ring.write_all(b"hello").unwrap();
+///
+/// let mut buf = [0; 5];
+/// ring.read_exact(&mut buf).unwrap();
+/// assert_eq!(b"hello", &buf);
+/// assert!(ring.is_empty());
+///
+/// // Checks the entry back in
+/// drop(ring.into_inner());
+/// ```
+pub struct ExtraRing<T, E: Extra = u8> {
+    checkout: Checkout<T, E>,
+    head: AtomicUsize, // Write position, in `0..2 * capacity`
+    tail: AtomicUsize, // Read position, in `0..2 * capacity`
+}
+
+impl<T, E: Extra> ExtraRing<T, E> {
+    /// Returns an empty ring over the extra bytes of `checkout`.
+    ///
+    /// Whatever the extra bytes hold is ignored.
+    pub fn new(checkout: Checkout<T, E>) -> ExtraRing<T, E> {
+        ExtraRing {
+            checkout,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the checkout, dropping whatever is left in the ring.
+    pub fn into_inner(self) -> Checkout<T, E> {
+        self.checkout
+    }
+
+    /// Returns the checked out value, which the ring doesn't touch.
+    pub fn get_ref(&self) -> &T {
+        &self.checkout
+    }
+
+    /// Returns the checked out value mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.checkout
+    }
+
+    /// Number of bytes the ring holds when full, all of the extra bytes.
+    pub fn capacity(&self) -> usize {
+        self.checkout.entry().extra().len()
+    }
+
+    /// Number of bytes ready to be read.
+    pub fn len(&self) -> usize {
+        let (head, tail) = (self.head.load(Ordering::Relaxed), self.tail.load(Ordering::Relaxed));
+        distance(tail, head, self.capacity())
+    }
+
+    /// Returns whether there is nothing to read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether there is no room to write.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Discards everything in the ring.
+    pub fn clear(&mut self) {
+        self.head.store(0, Ordering::Relaxed);
+        self.tail.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the bytes ready to be read, in order. The second slice is
+    /// non-empty when they wrap around the end of the extra bytes.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        unsafe { self.raw().filled() }
+    }
+
+    /// Marks `n` bytes as read, see `as_slices`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes are ready to be read.
+    pub fn consume(&mut self, n: usize) {
+        self.split().1.consume(n)
+    }
+
+    /// Returns the room left to write, in order. The second slice is
+    /// non-empty when it wraps around the end of the extra bytes.
+    pub fn free_slices(&mut self) -> (&mut [u8], &mut [u8]) {
+        unsafe { self.raw().free() }
+    }
+
+    /// Marks `n` bytes as written, see `free_slices`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes are free.
+    pub fn commit(&mut self, n: usize) {
+        self.split().0.commit(n)
+    }
+
+    /// Splits the ring into its writing and reading halves, which can be
+    /// used from different threads.
+    pub fn split(&mut self) -> (RingProducer<'_>, RingConsumer<'_>) {
+        (RingProducer { ring: self.raw() }, RingConsumer { ring: self.raw() })
+    }
+
+    fn raw(&self) -> Raw<'_> {
+        Raw {
+            ptr: self.checkout.extra_ptr() as *mut u8,
+            cap: self.capacity(),
+            head: &self.head,
+            tail: &self.tail,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T, E: Extra> io::Read for ExtraRing<T, E> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.split().1.read(buf)
+    }
+}
+
+impl<T, E: Extra> io::Write for ExtraRing<T, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.split().0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The writing half of an `ExtraRing`, see `ExtraRing::split`.
+///
+/// Writing to a full ring writes nothing, which `io::Write::write_all`
+/// reports as an error.
+pub struct RingProducer<'a> {
+    ring: Raw<'a>,
+}
+
+impl<'a> RingProducer<'a> {
+    /// Number of bytes that can be written.
+    pub fn free(&self) -> usize {
+        self.ring.cap - self.ring.len()
+    }
+
+    /// Returns the room left to write, see `ExtraRing::free_slices`.
+    pub fn free_slices(&mut self) -> (&mut [u8], &mut [u8]) {
+        unsafe { self.ring.free() }
+    }
+
+    /// Marks `n` bytes as written, see `ExtraRing::commit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes are free.
+    pub fn commit(&mut self, n: usize) {
+        assert!(n <= self.free(), "committed more bytes than are free");
+
+        let head = self.ring.head.load(Ordering::Relaxed);
+        // Publishes the bytes written
+        self.ring.head.store(advance(head, n, self.ring.cap), Ordering::Release);
+    }
+}
+
+impl<'a> io::Write for RingProducer<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = {
+            let (first, second) = self.free_slices();
+            copy(&[buf], &mut [first, second])
+        };
+
+        self.commit(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reading half of an `ExtraRing`, see `ExtraRing::split`.
+///
+/// Reading from an empty ring reads nothing, which looks like the end of
+/// the stream to `io::Read` helpers such as `read_to_end`.
+pub struct RingConsumer<'a> {
+    ring: Raw<'a>,
+}
+
+impl<'a> RingConsumer<'a> {
+    /// Number of bytes ready to be read.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns whether there is nothing to read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the bytes ready to be read, see `ExtraRing::as_slices`.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        unsafe { self.ring.filled() }
+    }
+
+    /// Marks `n` bytes as read, see `ExtraRing::consume`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `n` bytes are ready to be read.
+    pub fn consume(&mut self, n: usize) {
+        assert!(n <= self.len(), "consumed more bytes than were written");
+
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        // Hands the bytes back to the producer only once they are read
+        self.ring.tail.store(advance(tail, n, self.ring.cap), Ordering::Release);
+    }
+}
+
+impl<'a> io::Read for RingConsumer<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let (first, second) = self.as_slices();
+            copy(&[first, second], &mut [buf])
+        };
+
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+// Either half only writes the part of the extra bytes the other half
+// doesn't read.
+unsafe impl<'a> Send for RingProducer<'a> { }
+unsafe impl<'a> Send for RingConsumer<'a> { }
+
+// The extra bytes and positions shared by both halves
+struct Raw<'a> {
+    ptr: *mut u8,
+    cap: usize,
+    head: &'a AtomicUsize,
+    tail: &'a AtomicUsize,
+    _p: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> Raw<'a> {
+    fn len(&self) -> usize {
+        // Acquires the bytes written, or the room freed, by the other half
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        distance(tail, head, self.cap)
+    }
+
+    // The bytes from `pos` on, `n` of them, split at the end of the extra
+    // bytes
+    unsafe fn wrapped(&self, pos: usize, n: usize) -> (*mut u8, usize, usize) {
+        let start = if pos >= self.cap { pos - self.cap } else { pos };
+        let first = cmp::min(n, self.cap - start);
+
+        (self.ptr.add(start), first, n - first)
+    }
+
+    // Must only be called by the consumer
+    unsafe fn filled<'b>(&self) -> (&'b [u8], &'b [u8]) {
+        let len = self.len();
+        let (start, first, second) = self.wrapped(self.tail.load(Ordering::Relaxed), len);
+
+        (slice::from_raw_parts(start, first), slice::from_raw_parts(self.ptr, second))
+    }
+
+    // Must only be called by the producer
+    unsafe fn free<'b>(&self) -> (&'b mut [u8], &'b mut [u8]) {
+        let free = self.cap - self.len();
+        let (start, first, second) = self.wrapped(self.head.load(Ordering::Relaxed), free);
+
+        (slice::from_raw_parts_mut(start, first), slice::from_raw_parts_mut(self.ptr, second))
+    }
+}
+
+// Positions run over twice the capacity, which tells a full ring from an
+// empty one without giving up a byte
+fn advance(pos: usize, n: usize, cap: usize) -> usize {
+    let pos = pos + n;
+    if pos >= 2 * cap { pos - 2 * cap } else { pos }
+}
+
+fn distance(from: usize, to: usize, cap: usize) -> usize {
+    if to >= from { to - from } else { to + 2 * cap - from }
+}
+
+// Copies as much as fits from one sequence of slices to another
+fn copy(src: &[&[u8]], dst: &mut [&mut [u8]]) -> usize {
+    let mut src = src.iter().filter(|slice| !slice.is_empty());
+    let mut curr: &[u8] = &[];
+    let mut n = 0;
+
+    for dst in dst.iter_mut() {
+        let mut dst = &mut dst[..];
+
+        while !dst.is_empty() {
+            if curr.is_empty() {
+                match src.next() {
+                    Some(slice) => curr = slice,
+                    None => return n,
+                }
+            }
+
+            let len = cmp::min(curr.len(), dst.len());
+            dst[..len].copy_from_slice(&curr[..len]);
+
+            curr = &curr[len..];
+            dst = &mut dst[len..];
+            n += len;
+        }
+    }
+
+    n
+}
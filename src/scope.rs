@@ -0,0 +1,148 @@
+use {Builder, Checkout, Extra, Init, Pool, Reset};
+use std::{mem, ops, thread};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use sync::Arc;
+
+/// Creates a scope for pools whose initializer borrows from the enclosing
+/// stack frame, much like `std::thread::scope` does for threads.
+///
+/// Pools are created with `Scope::pool`. Before `scope` returns, it waits
+/// for every value checked out of them to be returned, by any thread, and
+/// drops the pools along with their initializer. A checkout that is leaked
+/// with `mem::forget`, or a pool upgraded from a `WeakPool`, keeps `scope`
+/// from returning.
+///
+/// ```
+/// use pool::{Builder, Dirty};
+///
+/// struct Parser<'a> {
+///     schema: &'a [&'a str],
+/// }
+///
+/// let schema = vec!["id", "name"];
+///
+/// pool::scope(|s| {
+///     let mut parsers = s.pool(&Builder::new().capacity(2), || Dirty(Parser { schema: &schema }));
+///
+///     let parser = parsers.checkout();
+///     assert_eq!(2, parser.schema.len());
+/// });
+/// ```
+pub fn scope<'env, F, R>(f: F) -> R
+        where F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> R {
+    let scope = Scope {
+        pools: Mutex::new(vec![]),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+
+    // Waits even if `f` panics, the checkouts may still be in use elsewhere
+    let _guard = Guard(&scope);
+
+    f(&scope)
+}
+
+/// A scope to create pools in, see `scope`.
+pub struct Scope<'scope, 'env: 'scope> {
+    // Drops each pool once done with, returning whether it did
+    pools: Mutex<Vec<Box<dyn FnMut() -> bool + Send + 'env>>>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Creates a pool configured by `builder`, initializing each entry with
+    /// the given function, which may borrow from outside the scope.
+    pub fn pool<T, E, F>(&'scope self, builder: &Builder<T, E>, init: F) -> ScopedPool<'scope, T, E>
+            where T: Reset + Send + 'env,
+                  E: Extra,
+                  F: Fn() -> T + Send + Sync + 'env {
+        let init: ScopedInit<'env, T> = Box::new(move |_, _| init());
+
+        // The scope outlives the pool and everything holding on to its inner
+        // state, see `Guard`, so the initializer is never used, or dropped,
+        // after what it borrows is gone
+        let init: Init<T> = unsafe { mem::transmute(init) };
+        let pool = Pool::build(builder, init);
+        let mut inner = Some(pool.inner.clone());
+
+        // Unwrapping rather than looking at the count, so that a `WeakPool`
+        // can't be upgraded between the two
+        self.pools.lock().unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(move || {
+                match Arc::try_unwrap(inner.take().unwrap()) {
+                    Ok(_) => true,
+                    Err(rest) => {
+                        inner = Some(rest);
+                        false
+                    }
+                }
+            }));
+
+        ScopedPool {
+            pool,
+            _p: PhantomData,
+        }
+    }
+}
+
+// An initializer borrowing from outside the scope, see `Init`
+type ScopedInit<'env, T> = Box<dyn Fn(usize, &mut [u8]) -> T + Send + Sync + 'env>;
+
+// Waits for the pools of a scope to be done with, then drops them
+struct Guard<'a, 'scope: 'a, 'env: 'scope>(&'a Scope<'scope, 'env>);
+
+impl<'a, 'scope, 'env> Drop for Guard<'a, 'scope, 'env> {
+    fn drop(&mut self) {
+        let pools = mem::take(&mut *self.0.pools.lock().unwrap_or_else(|e| e.into_inner()));
+
+        for mut done in pools {
+            // Checkins don't signal anyone, look every now and then
+            while !done() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+/// A pool created in a scope, see `scope`.
+///
+/// Derefs to the `Pool`, and forwards the methods that check out values. It
+/// cannot be moved out of the scope, nor can the `Pool` be moved out of it.
+pub struct ScopedPool<'scope, T: Reset, E: Extra = u8> {
+    pool: Pool<T, E>,
+    _p: PhantomData<&'scope ()>,
+}
+
+impl<'scope, T: Reset, E: Extra> ScopedPool<'scope, T, E> {
+    /// See `Pool::checkout`.
+    pub fn checkout(&mut self) -> Checkout<T, E> {
+        self.pool.checkout()
+    }
+
+    /// See `Pool::checkout_dirty`.
+    pub fn checkout_dirty(&mut self) -> Checkout<T, E> {
+        self.pool.checkout_dirty()
+    }
+
+    /// See `Pool::try_checkout`.
+    pub fn try_checkout(&mut self) -> Option<Checkout<T, E>> {
+        self.pool.try_checkout()
+    }
+
+    /// See `Pool::poll_checkout`.
+    pub fn poll_checkout(&mut self, cx: &mut Context<'_>) -> Poll<Checkout<T, E>> {
+        self.pool.poll_checkout(cx)
+    }
+}
+
+impl<'scope, T: Reset, E: Extra> ops::Deref for ScopedPool<'scope, T, E> {
+    type Target = Pool<T, E>;
+
+    fn deref(&self) -> &Pool<T, E> {
+        &self.pool
+    }
+}
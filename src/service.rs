@@ -0,0 +1,150 @@
+//! Integration with tower services.
+//!
+//! `PoolService` wraps a service so that each request is handed a value
+//! checked out from a pool. The wrapper is only ready once a value is
+//! available, so the pool's capacity applies backpressure to callers, and it
+//! reports the fraction of the pool in use as its load, for balancers.
+//!
+//! ```
+//! extern crate pool;
+//! extern crate tower;
+//!
+//! use pool::{Checkout, Dirty, Pool};
+//! use pool::service::PoolService;
+//! use std::future::{self, Ready};
+//! use std::task::{Context, Poll, Waker};
+//! use tower::Service;
+//!
+//! // Encodes requests into a pooled buffer
+//! struct Encode;
+//!
+//! impl Service<(Checkout<Dirty<Vec<u8>>>, String)> for Encode {
+//!     type Response = usize;
+//!     type Error = ();
+//!     type Future = Ready<Result<usize, ()>>;
+//!
+//!     fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), ()>> {
+//!         Poll::Ready(Ok(()))
+//!     }
+//!
+//!     fn call(&mut self, (mut buf, req): (Checkout<Dirty<Vec<u8>>>, String)) -> Self::Future {
+//!         buf.clear();
+//!         buf.extend_from_slice(req.as_bytes());
+//!         future::ready(Ok(buf.len()))
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let pool = Pool::with_capacity(16, 0, || Dirty(Vec::with_capacity(1024)));
+//! let mut service = PoolService::new(Encode, pool);
+//!
+//! let mut cx = Context::from_waker(Waker::noop());
+//!
+//! if service.poll_ready(&mut cx).is_ready() {
+//!     let len = service.call("hello".to_string()).into_inner();
+//!     assert_eq!(Ok(5), len);
+//! }
+//! # }
+//! ```
+
+use {Checkout, Extra, Pool, Reset};
+use std::task::{Context, Poll, Waker};
+use tower::Service;
+use tower::load::Load;
+
+/// A service that checks a value out of a pool for each request, passing it
+/// to the wrapped service along with the request.
+///
+/// The value is checked out when polled for readiness and held until the
+/// next call, see `Service::poll_ready`.
+pub struct PoolService<S, T: Reset, E: Extra = u8> {
+    inner: S,
+    pool: Pool<T, E>,
+    reserved: Option<Checkout<T, E>>, // Checked out by `poll_ready`
+    waker: Option<Waker>, // Left with the pool while waiting
+}
+
+impl<S, T: Reset, E: Extra> PoolService<S, T, E> {
+    /// Wraps `inner`, handing it values checked out from `pool`.
+    pub fn new(inner: S, pool: Pool<T, E>) -> PoolService<S, T, E> {
+        PoolService {
+            inner,
+            pool,
+            reserved: None,
+            waker: None,
+        }
+    }
+
+    /// Returns a reference to the wrapped service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the pool.
+    pub fn pool(&self) -> &Pool<T, E> {
+        &self.pool
+    }
+
+    /// Returns a mutable reference to the pool.
+    pub fn pool_mut(&mut self) -> &mut Pool<T, E> {
+        &mut self.pool
+    }
+}
+
+impl<S, T: Reset, E: Extra, R> Service<R> for PoolService<S, T, E>
+        where S: Service<(Checkout<T, E>, R)> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        if self.reserved.is_none() {
+            match self.pool.poll_checkout(cx) {
+                Poll::Ready(checkout) => {
+                    self.reserved = Some(checkout);
+                    self.waker = None;
+                }
+                Poll::Pending => {
+                    self.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        self.inner.poll_ready(cx)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the service was not polled ready first.
+    fn call(&mut self, req: R) -> S::Future {
+        let checkout = self.reserved.take().expect("called before the service was ready");
+        self.inner.call((checkout, req))
+    }
+}
+
+impl<S, T: Reset, E: Extra> Load for PoolService<S, T, E> {
+    /// Fraction of the pool's entries checked out, between 0 and 1.
+    type Metric = f64;
+
+    fn load(&self) -> f64 {
+        match self.pool.capacity() {
+            0 => 1.0,
+            capacity => self.pool.in_use() as f64 / capacity as f64,
+        }
+    }
+}
+
+impl<S, T: Reset, E: Extra> Drop for PoolService<S, T, E> {
+    fn drop(&mut self) {
+        // Hand a wakeup that may have been received to another waiter
+        if let Some(waker) = self.waker.take() {
+            self.pool.cancel_poll_checkout(&waker);
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Pools of byte buffers shared between processes.
+//!
+//! A `ShmPool` lives in a named POSIX shared memory object. The buffers as
+//! well as the process holding each of them are stored in the shared memory,
+//! so every process that opens the pool checks buffers out of and back in to
+//! the same pool.
+//! This lets processes hand each other payloads by buffer index instead of
+//! copying them.
+//!
+//! ```no_run
+//! use pool::shm::ShmPool;
+//!
+//! // In one process
+//! let pool = ShmPool::create("/payloads", 128, 4096).unwrap();
+//! let mut buf = pool.try_checkout().unwrap();
+//! buf[..5].copy_from_slice(b"hello");
+//! let idx = buf.into_index();
+//! // ... send `idx` to the other process
+//!
+//! // In the other process
+//! let pool = ShmPool::open("/payloads").unwrap();
+//! let buf = unsafe { pool.from_index(idx) };
+//! assert_eq!(b"hello", &buf[..5]);
+//! // Dropping the buffer checks it back in
+//! ```
+//!
+//! Since the memory is mapped at different addresses in each process, only
+//! plain bytes can be pooled.
+//!
+//! ## Crash recovery
+//!
+//! Each checked out buffer records the process holding it, in the same
+//! atomic step that checks it out. Should a process die without checking its
+//! buffers back in, any other process can reclaim them with
+//! `ShmPool::recover`, for example after noticing the death of a worker, or
+//! periodically.
+//!
+//! Free buffers are kept on a list, so checkouts take one in constant time.
+//! A process dying while it moves a buffer on or off the list can leave a
+//! free buffer off it. Checkouts finding the list empty then look through
+//! every buffer, so it is never lost, and `ShmPool::recover` puts it back
+//! on the list where it can tell.
+
+use std::{io, mem, ops, process, ptr, slice};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// Marks a fully initialized pool, "pool_shm"
+const MAGIC: u64 = 0x706f_6f6c_5f73_686d;
+
+// Bumped whenever the layout of the shared memory changes
+const VERSION: u32 = 4;
+
+// Ends the free list
+const NIL: u32 = u32::MAX;
+
+// Buffers start on cache lines, so processes don't contend on them
+const BUF_ALIGN: usize = 64;
+
+// Start of the shared memory
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,   // Set to `MAGIC` once the pool is initialized
+    version: u32,
+    count: u32,         // Number of buffers
+    buf_size: u64,      // Byte size of each buffer
+    head: AtomicU64,    // First free buffer in the low half, bumped tag in the high half
+    free: AtomicU32,    // Number of free buffers, never less than the actual one
+}
+
+// Follows the header, one per buffer
+#[repr(C)]
+struct Slot {
+    owner: AtomicU64,   // Process holding the buffer, see `Owner`, zero while free
+    next: AtomicU32,    // Next free buffer, while on the list
+    listed: AtomicU32,  // Non zero from being pushed on the list until popped off
+}
+
+/// A pool of byte buffers in named shared memory.
+pub struct ShmPool {
+    ptr: *mut u8,       // Start of the mapping
+    len: usize,         // Byte size of the mapping
+    count: usize,       // Number of buffers
+    buf_size: usize,    // Byte size of each buffer
+    bufs: usize,        // Offset of the first buffer
+    stride: usize,      // Distance between buffers
+}
+
+impl ShmPool {
+    /// Creates a new shared memory object called `name`, holding `count`
+    /// buffers of `size` bytes each.
+    ///
+    /// The name follows the rules of `shm_open`, so should start with a
+    /// slash. Fails if an object with that name exists already. The object
+    /// outlives the process, see `remove`.
+    pub fn create(name: &str, count: usize, size: usize) -> io::Result<ShmPool> {
+        if count > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many buffers"));
+        }
+
+        let (bufs, stride, len) = layout(count, size)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "pool too big"))?;
+
+        let name = c_name(name)?;
+
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o600);
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let res = if libc::ftruncate(fd, len as libc::off_t) == 0 {
+                map(fd, len)
+            } else {
+                Err(io::Error::last_os_error())
+            };
+
+            libc::close(fd);
+
+            let ptr = match res {
+                Ok(ptr) => ptr,
+                Err(e) => {
+                    libc::shm_unlink(name.as_ptr());
+                    return Err(e);
+                }
+            };
+
+            let pool = ShmPool { ptr, len, count, buf_size: size, bufs, stride };
+
+            // The memory starts out zeroed, which leaves every buffer free.
+            // Fill in the header and the free list, and only then mark the
+            // pool as ready for others to open
+            ptr::write(ptr as *mut Header, Header {
+                magic: AtomicU64::new(0),
+                version: VERSION,
+                count: count as u32,
+                buf_size: size as u64,
+                head: AtomicU64::new(if count == 0 { NIL as u64 } else { 0 }),
+                free: AtomicU32::new(count as u32),
+            });
+
+            for idx in 0..count as u32 {
+                let slot = pool.slot(idx);
+                slot.next.store(if idx as usize + 1 == count { NIL } else { idx + 1 }, Ordering::Relaxed);
+                slot.listed.store(1, Ordering::Relaxed);
+            }
+
+            pool.header().magic.store(MAGIC, Ordering::Release);
+
+            Ok(pool)
+        }
+    }
+
+    /// Opens the existing shared memory pool called `name`.
+    ///
+    /// Fails with `InvalidData` if the object is not a pool, or has not been
+    /// fully created yet.
+    pub fn open(name: &str) -> io::Result<ShmPool> {
+        let name = c_name(name)?;
+
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_RDWR, 0);
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut stat: libc::stat = mem::zeroed();
+
+            let res = if libc::fstat(fd, &mut stat) == 0 {
+                let len = stat.st_size as usize;
+
+                if len < mem::size_of::<Header>() {
+                    Err(invalid())
+                } else {
+                    map(fd, len).map(|ptr| (ptr, len))
+                }
+            } else {
+                Err(io::Error::last_os_error())
+            };
+
+            libc::close(fd);
+
+            let (ptr, len) = res?;
+            let header = &*(ptr as *const Header);
+
+            // Checked separately, so the header can be trusted from here on
+            let valid = header.magic.load(Ordering::Acquire) == MAGIC && header.version == VERSION;
+            let layout = layout(header.count as usize, header.buf_size as usize);
+
+            match layout {
+                Some((bufs, stride, expected)) if valid && expected == len => {
+                    Ok(ShmPool {
+                        ptr,
+                        len,
+                        count: header.count as usize,
+                        buf_size: header.buf_size as usize,
+                        bufs,
+                        stride,
+                    })
+                }
+                _ => {
+                    libc::munmap(ptr as *mut libc::c_void, len);
+                    Err(invalid())
+                }
+            }
+        }
+    }
+
+    /// Removes the shared memory object called `name`.
+    ///
+    /// Processes that have the pool open can keep using it, the memory is
+    /// freed once the last of them closes it.
+    pub fn remove(name: &str) -> io::Result<()> {
+        let name = c_name(name)?;
+
+        match unsafe { libc::shm_unlink(name.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Checks out a buffer, or returns `None` if all buffers are in use by
+    /// any of the processes sharing the pool.
+    ///
+    /// The buffer has not been cleared and contains whatever it was last
+    /// checked in with.
+    ///
+    /// Takes the buffer checked in last, which is likely still in the cache,
+    /// in constant time. Should a process have died moving a buffer on or
+    /// off the free list, checkouts that find the list empty look through
+    /// all buffers, in time linear in the capacity.
+    pub fn try_checkout(&self) -> Option<ShmBuffer<'_>> {
+        let me = Owner::current();
+
+        while let Some(idx) = self.pop() {
+            self.slot(idx).listed.store(0, Ordering::Relaxed);
+
+            if self.claim(idx, me) {
+                return Some(ShmBuffer { pool: self, idx });
+            }
+
+            // Taken by a scan below while on the list, its checkin pushes it
+            // again
+        }
+
+        // Every buffer is checked out, no need to look
+        if self.header().free.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+
+        // Free buffers lost by the list, or being checked in right now
+        for idx in 0..self.count as u32 {
+            if self.slot(idx).owner.load(Ordering::Relaxed) == 0 && self.claim(idx, me) {
+                return Some(ShmBuffer { pool: self, idx });
+            }
+        }
+
+        None
+    }
+
+    /// Returns the buffer at `idx`, as obtained from `ShmBuffer::into_index`
+    /// in any of the processes sharing the pool.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be checked out, its index must have been obtained with
+    /// `into_index` and it must only be turned back into a buffer once. The
+    /// process that called `into_index` must still be alive, or the buffer
+    /// may have been reclaimed by `recover`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub unsafe fn from_index(&self, idx: usize) -> ShmBuffer<'_> {
+        assert!(idx < self.count, "buffer index out of bounds");
+
+        // The buffer now belongs to this process
+        self.slot(idx as u32).owner.store(Owner::current().0, Ordering::Relaxed);
+        ShmBuffer { pool: self, idx: idx as u32 }
+    }
+
+    /// Checks buffers held by processes that no longer exist back in,
+    /// returning how many were reclaimed.
+    ///
+    /// Free buffers left off the free list by a process that died while
+    /// checking them in are put back on it as well, without being counted.
+    ///
+    /// Safe to call from any number of processes concurrently, each buffer is
+    /// reclaimed once. Processes are identified by their id along with their
+    /// start time, so a buffer held by a dead process whose id has since been
+    /// reused is reclaimed all the same. On systems other than Linux the
+    /// start time is not known, and such a buffer is only reclaimed once the
+    /// process that reused the id is gone as well.
+    pub fn recover(&self) -> usize {
+        let me = Owner::current();
+        let mut recovered = 0;
+
+        for idx in 0..self.count as u32 {
+            let owner = &self.slot(idx).owner;
+            let curr = Owner(owner.load(Ordering::Acquire));
+
+            if curr.0 == 0 {
+                // Does nothing if it is on the list already
+                self.push(idx);
+                continue;
+            }
+
+            if curr.pid() == me.pid() || curr.is_alive() {
+                continue;
+            }
+
+            // Free the buffer, racing other recovering processes
+            let free = &self.header().free;
+            free.fetch_add(1, Ordering::Relaxed);
+
+            if owner.compare_exchange(curr.0, 0, Ordering::Release, Ordering::Relaxed).is_ok() {
+                self.push(idx);
+                recovered += 1;
+            } else {
+                free.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        recovered
+    }
+
+    /// Number of buffers in the pool.
+    pub fn capacity(&self) -> usize {
+        self.count
+    }
+
+    /// Byte size of each buffer.
+    pub fn buffer_size(&self) -> usize {
+        self.buf_size
+    }
+
+    fn checkin(&self, idx: u32) {
+        // Counted first, so the count never falls short
+        self.header().free.fetch_add(1, Ordering::Relaxed);
+
+        // Releases the writes to the buffer to its next owner
+        self.slot(idx).owner.store(0, Ordering::Release);
+        self.push(idx);
+    }
+
+    // Stamping the owner is what checks the buffer out, so there is no point
+    // at which a crash leaves it neither free nor owned. The list only helps
+    // finding it.
+    fn claim(&self, idx: u32, me: Owner) -> bool {
+        let owner = &self.slot(idx).owner;
+
+        if owner.compare_exchange(0, me.0, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return false;
+        }
+
+        self.header().free.fetch_sub(1, Ordering::Relaxed);
+        true
+    }
+
+    fn pop(&self) -> Option<u32> {
+        let head = &self.header().head;
+        let mut curr = head.load(Ordering::Acquire);
+
+        loop {
+            let idx = curr as u32;
+
+            if idx == NIL {
+                return None;
+            }
+
+            // Stale if the buffer was popped in the meantime, the tag then
+            // fails the exchange
+            let next = self.slot(idx).next.load(Ordering::Relaxed);
+
+            match head.compare_exchange_weak(curr, tagged(curr, next), Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => return Some(idx),
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    fn push(&self, idx: u32) {
+        let slot = self.slot(idx);
+
+        // Keeps a buffer from being on the list twice
+        if slot.listed.compare_exchange(0, 1, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return;
+        }
+
+        let head = &self.header().head;
+        let mut curr = head.load(Ordering::Relaxed);
+
+        loop {
+            slot.next.store(curr as u32, Ordering::Relaxed);
+
+            match head.compare_exchange_weak(curr, tagged(curr, idx), Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.ptr as *const Header) }
+    }
+
+    fn slot(&self, idx: u32) -> &Slot {
+        unsafe { &*(self.ptr.add(mem::size_of::<Header>()) as *const Slot).add(idx as usize) }
+    }
+
+    fn buf_ptr(&self, idx: u32) -> *mut u8 {
+        unsafe { self.ptr.add(self.bufs + idx as usize * self.stride) }
+    }
+}
+
+impl Drop for ShmPool {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len); }
+    }
+}
+
+// The shared state is only accessed atomically, buffers are handed out to one
+// owner at a time.
+unsafe impl Send for ShmPool { }
+unsafe impl Sync for ShmPool { }
+
+/// A buffer checked out from a `ShmPool`. When dropped out of scope, the
+/// buffer is returned to the pool.
+pub struct ShmBuffer<'a> {
+    pool: &'a ShmPool,
+    idx: u32,
+}
+
+impl<'a> ShmBuffer<'a> {
+    /// Index of the buffer in the pool, the same in every process.
+    pub fn index(&self) -> usize {
+        self.idx as usize
+    }
+
+    /// Consumes the buffer without checking it in, returning its index.
+    ///
+    /// The buffer stays checked out until it is turned back into a buffer
+    /// with `ShmPool::from_index`, possibly by another process.
+    pub fn into_index(self) -> usize {
+        let idx = self.idx as usize;
+        mem::forget(self);
+        idx
+    }
+}
+
+impl<'a> ops::Deref for ShmBuffer<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.pool.buf_ptr(self.idx), self.pool.buf_size) }
+    }
+}
+
+impl<'a> ops::DerefMut for ShmBuffer<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.pool.buf_ptr(self.idx), self.pool.buf_size) }
+    }
+}
+
+impl<'a> Drop for ShmBuffer<'a> {
+    fn drop(&mut self) {
+        self.pool.checkin(self.idx);
+    }
+}
+
+// Points the head of the free list at `idx`, bumping the tag so that a pop
+// racing a pop and push of the same buffer fails
+fn tagged(head: u64, idx: u32) -> u64 {
+    ((head >> 32) + 1) << 32 | idx as u64
+}
+
+// Returns the offset of the first buffer, the distance between buffers and
+// the total byte size of a pool
+fn layout(count: usize, size: usize) -> Option<(usize, usize, usize)> {
+    let mask = BUF_ALIGN - 1;
+    let slots = mem::size_of::<Slot>().checked_mul(count)?;
+    let bufs = mem::size_of::<Header>().checked_add(slots)?.checked_add(mask)? & !mask;
+    let stride = size.checked_add(mask)? & !mask;
+    let len = stride.checked_mul(count)?.checked_add(bufs)?;
+
+    if len > isize::MAX as usize {
+        return None;
+    }
+
+    Some((bufs, stride, len))
+}
+
+unsafe fn map(fd: libc::c_int, len: usize) -> io::Result<*mut u8> {
+    let ptr = libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd, 0);
+
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(ptr as *mut u8)
+}
+
+// Identifies a process holding a buffer: its id in the low half, and the low
+// bits of its start time in the high half, zero where unknown
+#[derive(Clone, Copy)]
+struct Owner(u64);
+
+impl Owner {
+    fn current() -> Owner {
+        // Cached along with the id, which changes in a forked child
+        static CURRENT: AtomicU64 = AtomicU64::new(0);
+
+        let pid = process::id();
+        let cached = Owner(CURRENT.load(Ordering::Relaxed));
+
+        if cached.pid() == pid {
+            return cached;
+        }
+
+        let owner = Owner::new(pid, start_time(pid).unwrap_or(0));
+        CURRENT.store(owner.0, Ordering::Relaxed);
+        owner
+    }
+
+    fn new(pid: u32, start: u32) -> Owner {
+        Owner((start as u64) << 32 | pid as u64)
+    }
+
+    fn pid(self) -> u32 {
+        self.0 as u32
+    }
+
+    fn start(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    // Whether the process still exists, and not just another one that reused
+    // its id
+    fn is_alive(self) -> bool {
+        let pid = self.pid();
+
+        let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+            // Exists, but belongs to someone else
+            || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH);
+
+        match start_time(pid) {
+            Some(start) if exists && self.start() != 0 => start == self.start(),
+            _ => exists,
+        }
+    }
+}
+
+// The low bits of the time the process started at, in clock ticks since boot
+#[cfg(target_os = "linux")]
+fn start_time(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // The fields after the command name, which is in parentheses and can hold
+    // anything, start with the third field. The start time is the 22nd.
+    let fields = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(19)?.parse::<u64>().ok().map(|start| start as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start_time(_pid: u32) -> Option<u32> {
+    None
+}
+
+fn c_name(name: &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name contains a nul byte"))
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a shared memory pool")
+}
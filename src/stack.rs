@@ -0,0 +1,216 @@
+//! Pools of stacks for coroutines and green threads.
+//!
+//! A `StackPool` maps all of its stacks up front, each preceded by a guard
+//! page that is neither readable nor writable. A coroutine overflowing its
+//! stack faults on the guard page instead of silently corrupting the stack
+//! below it.
+//!
+//! ```
+//! use pool::stack::StackPool;
+//!
+//! let stacks = StackPool::new(64, 64 * 1024).unwrap();
+//!
+//! let stack = stacks.try_checkout().unwrap();
+//! // Stacks grow down, from `top` towards `bottom`
+//! assert_eq!(stack.bottom() as usize + stack.size(), stack.top() as usize);
+//! // Dropping the stack checks it back in
+//! ```
+//!
+//! Stacks are not cleared on checkin, see `StackPool::release` to hand
+//! their memory back to the OS.
+
+use os;
+use std::{fmt, io, ptr};
+use std::sync::{Mutex, MutexGuard};
+
+/// A pool of guarded stacks, see the module documentation.
+pub struct StackPool {
+    ptr: *mut u8,           // Start of the mapping
+    len: usize,             // Byte size of the mapping
+    guard: usize,           // Byte size of each guard
+    size: usize,            // Byte size of each stack, without its guard
+    count: usize,           // Number of stacks
+    idle: Mutex<Vec<usize>>,
+}
+
+impl StackPool {
+    /// Maps `count` stacks of `size` bytes each, rounded up to whole pages.
+    ///
+    /// Only address space is reserved, the OS backs each page with memory
+    /// once a stack reaches into it.
+    pub fn new(count: usize, size: usize) -> io::Result<StackPool> {
+        let guard = os::page_size();
+
+        let size = size.checked_add(guard - 1).map(|size| size & !(guard - 1));
+        let len = size
+            .and_then(|size| size.checked_add(guard))
+            .and_then(|stride| stride.checked_mul(count))
+            .filter(|&len| len <= isize::MAX as usize);
+
+        let (size, len) = match (size, len) {
+            (Some(size), Some(len)) if size != 0 => (size, len),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid stack size")),
+        };
+
+        let ptr = if len == 0 {
+            ptr::null_mut()
+        } else {
+            unsafe { map(len, guard, size + guard)? }
+        };
+
+        Ok(StackPool {
+            ptr,
+            len,
+            guard,
+            size,
+            count,
+            // Check out the lowest stacks first
+            idle: Mutex::new((0..count).rev().collect()),
+        })
+    }
+
+    /// Checks out a stack, or returns `None` if all stacks are in use.
+    pub fn try_checkout(&self) -> Option<Stack<'_>> {
+        self.lock().pop().map(|idx| Stack { pool: self, idx })
+    }
+
+    /// Number of stacks in the pool.
+    pub fn capacity(&self) -> usize {
+        self.count
+    }
+
+    /// Number of stacks currently checked out.
+    pub fn in_use(&self) -> usize {
+        self.count - self.lock().len()
+    }
+
+    /// Byte size of each stack, not counting its guard page.
+    pub fn stack_size(&self) -> usize {
+        self.size
+    }
+
+    /// Byte size of the guard page below each stack.
+    pub fn guard_size(&self) -> usize {
+        self.guard
+    }
+
+    /// Hands the memory of the idle stacks back to the OS, keeping their
+    /// address space. Returns the number of bytes released.
+    ///
+    /// Useful after a burst of coroutines, which may have touched far more
+    /// stack than the ones that follow.
+    pub fn release(&self) -> usize {
+        let idle = self.lock();
+
+        idle.iter()
+            .map(|&idx| os::release(self.bottom(idx), self.size))
+            .sum()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<usize>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn bottom(&self, idx: usize) -> *mut u8 {
+        unsafe { self.ptr.add(idx * (self.guard + self.size) + self.guard) }
+    }
+}
+
+impl fmt::Debug for StackPool {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("StackPool")
+            .field("capacity", &self.count)
+            .field("in_use", &self.in_use())
+            .field("stack_size", &self.size)
+            .field("guard_size", &self.guard)
+            .finish()
+    }
+}
+
+impl Drop for StackPool {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len); }
+        }
+    }
+}
+
+// Stacks are handed out to one owner at a time.
+unsafe impl Send for StackPool { }
+unsafe impl Sync for StackPool { }
+
+/// A stack checked out from a `StackPool`. When dropped out of scope, the
+/// stack is returned to the pool.
+///
+/// The stack must no longer be executing on by then.
+pub struct Stack<'a> {
+    pool: &'a StackPool,
+    idx: usize,
+}
+
+impl<'a> Stack<'a> {
+    /// Lowest address of the stack, right above its guard page.
+    pub fn bottom(&self) -> *mut u8 {
+        self.pool.bottom(self.idx)
+    }
+
+    /// Highest address of the stack, where it starts to grow down from. Page
+    /// aligned, and so suitably aligned for any platform's ABI.
+    pub fn top(&self) -> *mut u8 {
+        unsafe { self.bottom().add(self.pool.size) }
+    }
+
+    /// Byte size of the stack.
+    pub fn size(&self) -> usize {
+        self.pool.size
+    }
+
+    /// Index of the stack in the pool.
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+}
+
+impl<'a> fmt::Debug for Stack<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Stack")
+            .field("index", &self.idx)
+            .field("bottom", &self.bottom())
+            .field("top", &self.top())
+            .finish()
+    }
+}
+
+impl<'a> Drop for Stack<'a> {
+    fn drop(&mut self) {
+        self.pool.lock().push(self.idx);
+    }
+}
+
+// Maps `len` bytes of private memory and protects the first `guard` bytes of
+// every `stride`
+unsafe fn map(len: usize, guard: usize, stride: usize) -> io::Result<*mut u8> {
+    let ptr = libc::mmap(
+        ptr::null_mut(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_NORESERVE,
+        -1,
+        0);
+
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    let ptr = ptr as *mut u8;
+
+    for offset in (0..len).step_by(stride) {
+        if libc::mprotect(ptr.add(offset) as *mut libc::c_void, guard, libc::PROT_NONE) != 0 {
+            let e = io::Error::last_os_error();
+            libc::munmap(ptr as *mut libc::c_void, len);
+            return Err(e);
+        }
+    }
+
+    Ok(ptr)
+}
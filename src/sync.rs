@@ -0,0 +1,107 @@
+// Synchronization primitives used by the pool.
+//
+// On targets without threads, or without atomics at all, the atomic free
+// lists are pure overhead. There, the primitives are replaced by
+// single-threaded versions with the same API.
+
+pub use self::imp::*;
+
+#[cfg(not(any(
+    not(target_has_atomic = "ptr"),
+    all(target_arch = "wasm32", not(target_feature = "atomics")),
+)))]
+mod imp {
+    pub use std::sync::{Arc, Weak};
+    pub use std::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+
+    // Whether threads can be spawned
+    pub const HAS_THREADS: bool = true;
+}
+
+#[cfg(any(
+    not(target_has_atomic = "ptr"),
+    all(target_arch = "wasm32", not(target_feature = "atomics")),
+))]
+mod imp {
+    use std::cell::Cell;
+
+    pub use std::rc::{Rc as Arc, Weak};
+    pub use std::sync::atomic::Ordering;
+
+    // Whether threads can be spawned
+    pub const HAS_THREADS: bool = false;
+
+    pub fn fence(_: Ordering) {
+    }
+
+    pub struct AtomicUsize(Cell<usize>);
+
+    impl AtomicUsize {
+        pub const fn new(val: usize) -> AtomicUsize {
+            AtomicUsize(Cell::new(val))
+        }
+
+        pub fn load(&self, _: Ordering) -> usize {
+            self.0.get()
+        }
+
+        pub fn store(&self, val: usize, _: Ordering) {
+            self.0.set(val)
+        }
+
+        pub fn compare_exchange(&self, current: usize, new: usize, _: Ordering, _: Ordering) -> Result<usize, usize> {
+            match self.0.get() {
+                actual if actual == current => {
+                    self.0.set(new);
+                    Ok(actual)
+                }
+                actual => Err(actual),
+            }
+        }
+
+        pub fn compare_exchange_weak(&self, current: usize, new: usize, success: Ordering, failure: Ordering) -> Result<usize, usize> {
+            self.compare_exchange(current, new, success, failure)
+        }
+
+        pub fn fetch_add(&self, val: usize, _: Ordering) -> usize {
+            let prev = self.0.get();
+            self.0.set(prev.wrapping_add(val));
+            prev
+        }
+
+        pub fn fetch_sub(&self, val: usize, _: Ordering) -> usize {
+            let prev = self.0.get();
+            self.0.set(prev.wrapping_sub(val));
+            prev
+        }
+
+        pub fn fetch_update<F>(&self, _: Ordering, _: Ordering, mut f: F) -> Result<usize, usize>
+                where F: FnMut(usize) -> Option<usize> {
+            let prev = self.0.get();
+
+            match f(prev) {
+                Some(next) => {
+                    self.0.set(next);
+                    Ok(prev)
+                }
+                None => Err(prev),
+            }
+        }
+    }
+
+    pub struct AtomicPtr<T>(Cell<*mut T>);
+
+    impl<T> AtomicPtr<T> {
+        pub const fn new(ptr: *mut T) -> AtomicPtr<T> {
+            AtomicPtr(Cell::new(ptr))
+        }
+
+        pub fn load(&self, _: Ordering) -> *mut T {
+            self.0.get()
+        }
+
+        pub fn store(&self, ptr: *mut T, _: Ordering) {
+            self.0.set(ptr)
+        }
+    }
+}
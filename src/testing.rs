@@ -0,0 +1,275 @@
+//! Concurrency stress testing for pools.
+//!
+//! `Stress` hammers a pool from several threads with a random mix of
+//! checkouts and checkins, checking along the way that no value is handed
+//! out twice, and checks afterwards that every entry made it back to the
+//! free lists. Use it to test pool configurations, or code built on top of
+//! pools, against the same machinery the crate tests itself with.
+//!
+//! The mix of operations of each thread follows from the seed, so a failing
+//! seed can be run again. The interleaving of the threads is up to the OS.
+//!
+//! ```
+//! use pool::{Pool, Dirty};
+//! use pool::testing::Stress;
+//!
+//! let mut pool = Pool::with_capacity(16, 64, || Dirty(0u64));
+//!
+//! let report = Stress::new()
+//!     .threads(4)
+//!     .iterations(1_000)
+//!     .seed(42)
+//!     .run(&mut pool);
+//!
+//! assert!(report.checkouts > 0);
+//! ```
+
+use {Builder, Checkout, Extra, Pool, Reset};
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+/// Runs a stress test on a pool, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct Stress {
+    threads: usize,
+    iterations: usize,
+    max_held: usize,
+    seed: u64,
+}
+
+/// What happened during a stress test, returned by `Stress::run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Report {
+    /// Number of values checked out
+    pub checkouts: usize,
+    /// Number of checkouts that found the pool empty
+    pub failed: usize,
+    /// Number of values checked in by another thread than the one that
+    /// checked them out
+    pub handoffs: usize,
+}
+
+impl Stress {
+    /// Returns a stress test running 10,000 iterations on each of 4 threads,
+    /// each holding up to 4 values at a time, with a seed of 0.
+    pub fn new() -> Stress {
+        Stress {
+            threads: 4,
+            iterations: 10_000,
+            max_held: 4,
+            seed: 0,
+        }
+    }
+
+    /// Sets the number of threads, at least one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the number of operations each thread performs.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the number of values each thread holds on to at most. More
+    /// threads holding more values than the pool has exhausts it more
+    /// often.
+    pub fn max_held(mut self, max_held: usize) -> Self {
+        self.max_held = max_held;
+        self
+    }
+
+    /// Sets the seed the mix of operations of each thread follows from.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the stress test on `pool`.
+    ///
+    /// The calling thread checks out of `pool` directly. Every other thread
+    /// checks out through a child pool of its own, see `Builder::parent`,
+    /// so that checkouts race with each other as well as with checkins.
+    /// Values are checked back in by the thread that checked them out, or
+    /// handed to another thread to check in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value is checked out while already checked out, or if an
+    /// entry is missing from, or twice on, the free lists afterwards. Values
+    /// must not be checked out of `pool` while the test runs.
+    pub fn run<T, E>(&self, pool: &mut Pool<T, E>) -> Report
+            where T: Reset + Send,
+                  E: Extra {
+        let shared = Shared {
+            owners: Mutex::new(HashSet::new()),
+            exchange: Mutex::new(vec![]),
+        };
+
+        let children: Vec<Pool<T, E>> = (1..self.threads)
+            .map(|_| {
+                Builder::<T>::new()
+                    .extra_of::<E>(0)
+                    .parent(pool)
+                    .build(|| unreachable!("child pools have no entries of their own"))
+            })
+            .collect();
+
+        let report = thread::scope(|scope| {
+            let shared = &shared;
+
+            let handles: Vec<_> = children.into_iter().enumerate()
+                .map(|(i, mut child)| {
+                    scope.spawn(move || self.work(&mut child, i + 1, shared))
+                })
+                .collect();
+
+            let mut report = self.work(pool, 0, shared);
+
+            for handle in handles {
+                let other = handle.join().unwrap();
+
+                report.checkouts += other.checkouts;
+                report.failed += other.failed;
+                report.handoffs += other.handoffs;
+            }
+
+            report
+        });
+
+        // Values handed off but not picked up
+        for checkout in shared.lock_exchange().drain(..) {
+            shared.release(checkout);
+        }
+
+        check_integrity(pool);
+        report
+    }
+
+    // Runs the operations of one thread
+    fn work<T, E>(&self, pool: &mut Pool<T, E>, thread: usize, shared: &Shared<T, E>) -> Report
+            where T: Reset + Send,
+                  E: Extra {
+        let mut rng = Rng::new(self.seed, thread);
+        let mut held = vec![];
+        let mut report = Report::default();
+
+        for _ in 0..self.iterations {
+            match rng.next() % 8 {
+                // Check out, a little more often than in
+                0..=3 if held.len() < self.max_held => match pool.try_checkout() {
+                    Some(checkout) => {
+                        shared.acquire(&checkout);
+                        held.push(checkout);
+                        report.checkouts += 1;
+                    }
+                    None => report.failed += 1,
+                },
+                // Hand a value to another thread
+                4 if !held.is_empty() => {
+                    let idx = rng.next() as usize % held.len();
+                    shared.lock_exchange().push((thread, held.swap_remove(idx)));
+                }
+                // Check in a value handed over by another thread
+                5 => {
+                    if let Some((from, checkout)) = shared.lock_exchange().pop() {
+                        if from != thread {
+                            report.handoffs += 1;
+                        }
+
+                        shared.release((from, checkout));
+                    }
+                }
+                _ if !held.is_empty() => {
+                    let idx = rng.next() as usize % held.len();
+                    shared.release((thread, held.swap_remove(idx)));
+                }
+                _ => {}
+            }
+        }
+
+        for checkout in held {
+            shared.release((thread, checkout));
+        }
+
+        report
+    }
+}
+
+impl Default for Stress {
+    fn default() -> Stress {
+        Stress::new()
+    }
+}
+
+/// Checks that every entry of `pool` is idle and on the free lists exactly
+/// once.
+///
+/// # Panics
+///
+/// Panics if values are checked out of the pool, or if the free lists are
+/// corrupt.
+pub fn check_integrity<T: Reset, E: Extra>(pool: &mut Pool<T, E>) {
+    let capacity = pool.capacity();
+
+    assert_eq!(0, pool.in_use(), "values are still checked out");
+
+    let mut seen = HashSet::new();
+
+    pool.for_each_idle(|entry| {
+        assert!(entry.index() < capacity, "entry {} is out of bounds", entry.index());
+        assert!(seen.insert(entry.index()), "entry {} is on the free lists twice", entry.index());
+    });
+
+    assert_eq!(capacity, seen.len(), "entries are missing from the free lists");
+}
+
+// State shared by the threads of a stress test
+struct Shared<T, E: Extra> {
+    owners: Mutex<HashSet<usize>>, // Values currently checked out
+    exchange: Mutex<Vec<(usize, Checkout<T, E>)>>, // Values handed off, by thread
+}
+
+impl<T, E: Extra> Shared<T, E> {
+    fn acquire(&self, checkout: &Checkout<T, E>) {
+        let ptr = checkout.data_ptr() as usize;
+        let fresh = lock(&self.owners).insert(ptr);
+
+        assert!(fresh, "value at {:#x} checked out twice", ptr);
+    }
+
+    // Forgets about the value, then checks it in
+    fn release(&self, (_, checkout): (usize, Checkout<T, E>)) {
+        lock(&self.owners).remove(&(checkout.data_ptr() as usize));
+        drop(checkout);
+    }
+
+    fn lock_exchange(&self) -> MutexGuard<'_, Vec<(usize, Checkout<T, E>)>> {
+        lock(&self.exchange)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panicking thread fails the test anyway
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// A xorshift generator, good enough to pick operations
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64, thread: usize) -> Rng {
+        // Never zero, which xorshift would stay at
+        Rng((seed ^ (thread as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
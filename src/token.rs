@@ -0,0 +1,111 @@
+use {Checkout, Extra};
+use std::collections::HashMap;
+use std::collections::hash_map;
+
+/// Checkouts registered under event loop tokens, such as `mio::Token` or
+/// plain `usize`.
+///
+/// Register a checkout along with the event source it backs, look it up by
+/// token when the source becomes ready, and deregister it when done, which
+/// hands it back to be checked in. Any token type that converts into a
+/// `usize` works.
+///
+/// ```
+/// use pool::{Dirty, Pool, TokenTable};
+///
+/// let mut pool = Pool::with_capacity(16, 0, || Dirty(Vec::<u8>::new()));
+/// let mut conns = TokenTable::new();
+///
+/// // On accept
+/// let conn = pool.checkout();
+/// let token = conn.slot();
+/// conns.register(token, conn);
+///
+/// // On readiness
+/// conns.get_mut(token).unwrap().extend_from_slice(b"request");
+///
+/// // On close, the connection goes back to the pool
+/// drop(conns.deregister(token));
+/// assert_eq!(0, pool.in_use());
+/// ```
+pub struct TokenTable<T, E: Extra = u8> {
+    checkouts: HashMap<usize, Checkout<T, E>>,
+}
+
+impl<T, E: Extra> TokenTable<T, E> {
+    /// Returns an empty table.
+    pub fn new() -> TokenTable<T, E> {
+        TokenTable {
+            checkouts: HashMap::new(),
+        }
+    }
+
+    /// Registers a checkout under `token`, returning the checkout that was
+    /// registered under it before, if any.
+    pub fn register<K: Into<usize>>(&mut self, token: K, checkout: Checkout<T, E>) -> Option<Checkout<T, E>> {
+        self.checkouts.insert(token.into(), checkout)
+    }
+
+    /// Returns the checkout registered under `token`.
+    pub fn get<K: Into<usize>>(&self, token: K) -> Option<&Checkout<T, E>> {
+        self.checkouts.get(&token.into())
+    }
+
+    /// Returns the checkout registered under `token`, mutably.
+    pub fn get_mut<K: Into<usize>>(&mut self, token: K) -> Option<&mut Checkout<T, E>> {
+        self.checkouts.get_mut(&token.into())
+    }
+
+    /// Removes the checkout registered under `token` and returns it. It is
+    /// checked in once dropped.
+    pub fn deregister<K: Into<usize>>(&mut self, token: K) -> Option<Checkout<T, E>> {
+        self.checkouts.remove(&token.into())
+    }
+
+    /// Returns whether a checkout is registered under `token`.
+    pub fn contains<K: Into<usize>>(&self, token: K) -> bool {
+        self.checkouts.contains_key(&token.into())
+    }
+
+    /// Returns the number of registered checkouts.
+    pub fn len(&self) -> usize {
+        self.checkouts.len()
+    }
+
+    /// Returns whether no checkouts are registered.
+    pub fn is_empty(&self) -> bool {
+        self.checkouts.is_empty()
+    }
+
+    /// Returns the registered checkouts along with their tokens, in no
+    /// particular order.
+    pub fn iter_mut(&mut self) -> TokensMut<'_, T, E> {
+        TokensMut { inner: self.checkouts.iter_mut() }
+    }
+
+    /// Deregisters every checkout and returns them along with their tokens,
+    /// for example on shutdown.
+    pub fn drain(&mut self) -> Vec<(usize, Checkout<T, E>)> {
+        self.checkouts.drain().collect()
+    }
+}
+
+impl<T, E: Extra> Default for TokenTable<T, E> {
+    fn default() -> TokenTable<T, E> {
+        TokenTable::new()
+    }
+}
+
+/// Iterator over the checkouts of a `TokenTable`, see
+/// `TokenTable::iter_mut`.
+pub struct TokensMut<'a, T: 'a, E: Extra + 'a> {
+    inner: hash_map::IterMut<'a, usize, Checkout<T, E>>,
+}
+
+impl<'a, T, E: Extra> Iterator for TokensMut<'a, T, E> {
+    type Item = (usize, &'a mut Checkout<T, E>);
+
+    fn next(&mut self) -> Option<(usize, &'a mut Checkout<T, E>)> {
+        self.inner.next().map(|(&token, checkout)| (token, checkout))
+    }
+}
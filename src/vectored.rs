@@ -0,0 +1,51 @@
+use {Checkout, Extra};
+use std::io::{IoSlice, IoSliceMut};
+
+/// Returns the extra storage of each checkout as an `IoSlice`, in order, to
+/// be written out in one go with `Write::write_vectored` or `writev`.
+///
+/// Takes anything that iterates over checkouts, such as `&Vec<Checkout>`
+/// or a `WeightedCheckout`. The slices borrow the checkouts, which can't be
+/// checked in until the slices are dropped.
+///
+/// ```
+/// use pool::{Dirty, Pool};
+/// use std::io::Write;
+///
+/// let mut pool = Pool::with_capacity(4, 64, || Dirty(()));
+/// let mut bufs: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+///
+/// bufs[0].extra_mut().fill(b'a');
+/// bufs[1].extra_mut().fill(b'b');
+///
+/// let mut out = vec![];
+/// out.write_vectored(&pool::io_slices(&bufs)).unwrap();
+/// assert_eq!([bufs[0].extra(), bufs[1].extra()].concat(), out);
+/// ```
+pub fn io_slices<'a, T: 'a, E: Extra + 'a, I>(checkouts: I) -> Vec<IoSlice<'a>>
+        where I: IntoIterator<Item = &'a Checkout<T, E>> {
+    checkouts.into_iter().map(Checkout::io_slice).collect()
+}
+
+/// Returns the extra storage of each checkout as an `IoSliceMut`, in order,
+/// to be filled in one go with `Read::read_vectored` or `readv`.
+///
+/// ```
+/// use pool::{Dirty, Pool};
+/// use std::io::Read;
+///
+/// let mut pool = Pool::with_capacity(4, 64, || Dirty(()));
+/// let mut bufs: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+///
+/// let data: Vec<u8> = (0..=255).collect();
+/// let len = (&data[..]).read_vectored(&mut pool::io_slices_mut(&mut bufs)).unwrap();
+///
+/// // The second buffer picks up where the first one ends
+/// let split = bufs[0].extra().len();
+/// assert_eq!(len, split + bufs[1].extra().len());
+/// assert_eq!(&data[split..len], bufs[1].extra());
+/// ```
+pub fn io_slices_mut<'a, T: 'a, E: Extra + 'a, I>(checkouts: I) -> Vec<IoSliceMut<'a>>
+        where I: IntoIterator<Item = &'a mut Checkout<T, E>> {
+    checkouts.into_iter().map(Checkout::io_slice_mut).collect()
+}
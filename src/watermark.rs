@@ -0,0 +1,193 @@
+use std::cell::UnsafeCell;
+use std::time::{Duration, Instant};
+use sync::{Arc, AtomicUsize, Ordering};
+
+// Called with whether availability dropped below the watermark
+pub(crate) type WatermarkFn = Arc<dyn Fn(bool) + Send + Sync>;
+
+// Tracks whether the fraction of available entries is below a threshold, see
+// `Builder::low_watermark`
+pub(crate) struct Watermark {
+    fraction: f64,
+    capacity: AtomicUsize, // Mirrors the capacity, readable from any thread
+    below: AtomicUsize,    // Non-zero while below the watermark
+    callback: WatermarkFn,
+}
+
+impl Watermark {
+    pub fn new(fraction: f64, callback: WatermarkFn) -> Watermark {
+        Watermark {
+            fraction,
+            capacity: AtomicUsize::new(0),
+            below: AtomicUsize::new(0),
+            callback,
+        }
+    }
+
+    // Must only be called by the owner of the pool.
+    pub fn set_capacity(&self, capacity: usize, in_use: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        self.update(in_use);
+    }
+
+    // Calls the callback if availability crossed the watermark. Concurrent
+    // updates may see stale counts, the last one to act wins and the next
+    // update corrects it.
+    pub fn update(&self, in_use: usize) {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        let available = capacity.saturating_sub(in_use);
+        let below = (available as f64) < self.fraction * capacity as f64;
+        let (from, to) = if below { (0, 1) } else { (1, 0) };
+
+        if self.below.compare_exchange(from, to, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            (self.callback)(below);
+        }
+    }
+}
+
+// Grows the pool once availability has stayed below a threshold for a grace
+// period, see `Builder::auto_grow`
+pub(crate) struct AutoGrow {
+    fraction: f64,
+    pub max: usize,
+    below: Sustained,
+}
+
+impl AutoGrow {
+    pub fn new(fraction: f64, grace: Duration, max: usize) -> AutoGrow {
+        AutoGrow {
+            fraction,
+            max,
+            below: Sustained::new(grace),
+        }
+    }
+
+    // Whether the pool should grow, given the current counts. Availability
+    // is only sampled on checkout, which is all that matters: a pool nobody
+    // checks out from has no use for more entries.
+    //
+    // Must only be called by the owner of the pool.
+    pub unsafe fn should_grow(&self, capacity: usize, in_use: usize) -> bool {
+        let available = capacity.saturating_sub(in_use);
+        let below = capacity < self.max && (available as f64) < self.fraction * capacity as f64;
+
+        self.below.check(below)
+    }
+}
+
+// Retires idle values once availability has stayed above a threshold for a
+// period, see `Builder::auto_shrink`
+pub(crate) struct AutoShrink {
+    fraction: f64,
+    pub floor: usize,
+    above: Sustained,
+}
+
+impl AutoShrink {
+    pub fn new(fraction: f64, period: Duration, floor: usize) -> AutoShrink {
+        AutoShrink {
+            fraction,
+            floor,
+            above: Sustained::new(period),
+        }
+    }
+
+    // Whether idle values should be retired, given the current counts.
+    //
+    // Must only be called by the owner of the pool.
+    pub unsafe fn should_shrink(&self, capacity: usize, in_use: usize) -> bool {
+        let available = capacity.saturating_sub(in_use);
+        let above = (available as f64) > self.fraction * capacity as f64;
+
+        self.above.check(above)
+    }
+}
+
+// Sizes the pool to keep the peak utilization of each interval near a
+// target, see `Builder::adaptive`
+pub(crate) struct Adaptive {
+    target: f64,
+    min: usize,
+    max: usize,
+    interval: Duration,
+    window: UnsafeCell<Window>,
+}
+
+struct Window {
+    start: Option<Instant>,
+    peak: usize, // Most entries seen in use since `start`
+}
+
+impl Adaptive {
+    pub fn new(target: f64, min: usize, max: usize, interval: Duration) -> Adaptive {
+        Adaptive {
+            target,
+            min,
+            max,
+            interval,
+            window: UnsafeCell::new(Window {
+                start: None,
+                peak: 0,
+            }),
+        }
+    }
+
+    // Records the number of entries in use and, at the end of an interval,
+    // returns the size the pool should have.
+    //
+    // Must only be called by the owner of the pool.
+    pub unsafe fn sample(&self, in_use: usize) -> Option<usize> {
+        let window = &mut *self.window.get();
+        let start = *window.start.get_or_insert_with(Instant::now);
+
+        window.peak = window.peak.max(in_use);
+
+        if start.elapsed() < self.interval {
+            return None;
+        }
+
+        let size = (window.peak as f64 / self.target).ceil() as usize;
+
+        window.start = None;
+        window.peak = 0;
+
+        Some(size.clamp(self.min, self.max))
+    }
+}
+
+// Tracks how long a condition has held for
+struct Sustained {
+    period: Duration,
+    since: UnsafeCell<Option<Instant>>,
+}
+
+impl Sustained {
+    fn new(period: Duration) -> Sustained {
+        Sustained {
+            period,
+            since: UnsafeCell::new(None),
+        }
+    }
+
+    // Returns true once the condition has held on every check for the
+    // period, then starts over.
+    //
+    // Must only be called by the owner of the pool.
+    unsafe fn check(&self, holds: bool) -> bool {
+        let since = &mut *self.since.get();
+
+        if !holds {
+            *since = None;
+            return false;
+        }
+
+        let start = *since.get_or_insert_with(Instant::now);
+
+        if start.elapsed() < self.period {
+            return false;
+        }
+
+        *since = None;
+        true
+    }
+}
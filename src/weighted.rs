@@ -0,0 +1,66 @@
+use {io_slices, io_slices_mut, Checkout, Extra};
+use std::io::{IoSlice, IoSliceMut};
+use std::ops;
+use std::vec;
+use sync::Arc;
+
+// Weighs a value, see `Builder::weight`
+pub(crate) type WeightFn<T> = Arc<dyn Fn(&T) -> usize + Send + Sync>;
+
+/// Entries checked out together to cover a requested weight, created by
+/// `Pool::checkout_weighted`.
+///
+/// Derefs to the checkouts, which are returned to the pool together when the
+/// weighted checkout is dropped.
+pub struct WeightedCheckout<T, E: Extra = u8> {
+    pub(crate) checkouts: Vec<Checkout<T, E>>,
+    pub(crate) weight: usize,
+}
+
+impl<T, E: Extra> WeightedCheckout<T, E> {
+    /// Returns the total weight of the checked out entries, at least the
+    /// weight that was requested.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Returns the extra storage of the checkouts, in order, for vectored
+    /// writes.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        io_slices(&self.checkouts)
+    }
+
+    /// Returns the extra storage of the checkouts, in order, for vectored
+    /// reads.
+    pub fn io_slices_mut(&mut self) -> Vec<IoSliceMut<'_>> {
+        io_slices_mut(&mut self.checkouts)
+    }
+
+    /// Returns the checkouts, to be released one by one.
+    pub fn into_vec(self) -> Vec<Checkout<T, E>> {
+        self.checkouts
+    }
+}
+
+impl<T, E: Extra> ops::Deref for WeightedCheckout<T, E> {
+    type Target = [Checkout<T, E>];
+
+    fn deref(&self) -> &[Checkout<T, E>] {
+        &self.checkouts
+    }
+}
+
+impl<T, E: Extra> ops::DerefMut for WeightedCheckout<T, E> {
+    fn deref_mut(&mut self) -> &mut [Checkout<T, E>] {
+        &mut self.checkouts
+    }
+}
+
+impl<T, E: Extra> IntoIterator for WeightedCheckout<T, E> {
+    type Item = Checkout<T, E>;
+    type IntoIter = vec::IntoIter<Checkout<T, E>>;
+
+    fn into_iter(self) -> vec::IntoIter<Checkout<T, E>> {
+        self.checkouts.into_iter()
+    }
+}
@@ -1,4 +1,18 @@
 extern crate pool;
+#[cfg(feature = "allocator-api2")]
+extern crate allocator_api2;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+extern crate serde_json;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "proptest")]
+#[macro_use]
+extern crate proptest;
+#[cfg(feature = "tower")]
+extern crate tower;
 
 use pool::{Pool, Dirty};
 
@@ -6,14 +20,14 @@ use pool::{Pool, Dirty};
 pub fn test_checkout_checkin() {
     let mut pool: Pool<Dirty<i32>> = Pool::with_capacity(10, 0, || Dirty(0));
 
-    let mut val = pool.checkout().unwrap();
+    let mut val = pool.checkout();
     assert_eq!(**val, 0);
 
     // Update the value & return to the pool
     *val = Dirty(1);
     drop(val);
 
-    let val = pool.checkout().unwrap();
+    let val = pool.checkout();
     assert_eq!(**val, 1);
 }
 
@@ -25,7 +39,7 @@ pub fn test_multiple_checkouts() {
     let mut vec = vec![];
 
     for _ in 0..10 {
-        let mut i = pool.checkout().unwrap();
+        let mut i = pool.checkout();
         assert_eq!(*i, 0);
         *i = 1;
         vec.push(i);
@@ -39,31 +53,36 @@ pub fn test_depleting_pool() {
     let mut vec = vec![];
 
     for _ in 0..5 {
-        vec.push(pool.checkout().unwrap());
+        vec.push(pool.checkout());
     }
 
-    assert!(pool.checkout().is_none());
+    assert!(pool.try_checkout().is_none());
     drop(vec);
-    assert!(pool.checkout().is_some());
+    assert!(pool.try_checkout().is_some());
 }
 
 #[test]
+#[allow(clippy::redundant_closure, clippy::len_zero)]
 pub fn test_resetting_pool() {
     let mut pool: Pool<Vec<i32>> = Pool::with_capacity(1, 0, || Vec::new());
     {
-        let mut val = pool.checkout().unwrap();
+        let mut val = pool.checkout();
         val.push(5);
         val.push(6);
     }
     {
-        let val = pool.checkout().unwrap();
+        let val = pool.checkout();
         assert!(val.len() == 0);
     }
 }
 
-#[derive(Clone, Default)]
 struct Zomg;
 
+impl pool::Reset for Zomg {
+    fn reset(&mut self) {
+    }
+}
+
 impl Drop for Zomg {
     fn drop(&mut self) {
         println!("Dropping");
@@ -81,4 +100,3742 @@ pub fn test_safe_when_init_panics() {
     let _ = pool::Pool::<Zomg>::with_capacity(1, 0, || panic!("oops"));
 }
 
-// TODO: Add concurrency stress tests
+#[test]
+pub fn test_checkin_while_checking_out() {
+    use std::sync::mpsc;
+    use std::thread;
+
+    let mut pool: Pool<Dirty<usize>> = Pool::with_capacity(8, 0, || Dirty(0));
+
+    // Values are returned to the pool from other threads while the main thread
+    // keeps checking them out again.
+    let handles: Vec<_> = (0..4).map(|_| {
+        let (tx, rx) = mpsc::channel::<pool::Checkout<Dirty<usize>>>();
+        let handle = thread::spawn(move || {
+            for mut val in rx {
+                **val += 1;
+            }
+        });
+        (tx, handle)
+    }).collect();
+
+    let mut total = 0;
+
+    while total < 10_000 {
+        if let Some(val) = pool.try_checkout() {
+            handles[total % handles.len()].0.send(val).unwrap();
+            total += 1;
+        }
+    }
+
+    for (tx, handle) in handles {
+        drop(tx);
+        handle.join().unwrap();
+    }
+
+    let mut sum = 0;
+    let mut vals = vec![];
+
+    while let Some(val) = pool.try_checkout() {
+        sum += **val;
+        vals.push(val);
+    }
+
+    assert_eq!(8, vals.len());
+    assert_eq!(10_000, sum);
+}
+
+#[test]
+pub fn test_cache_line_aligned_entries() {
+    let mut pool: Pool<Dirty<u8>> = Pool::builder()
+        .capacity(4)
+        .align(64)
+        .build(|| Dirty(0));
+
+    let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+
+    for val in &vals {
+        let ptr = &***val as *const u8 as usize;
+        assert_eq!(0, ptr % 64);
+    }
+}
+
+#[test]
+pub fn test_parallel_init() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let next = AtomicUsize::new(0);
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(100)
+        .build_parallel(4, || Dirty(next.fetch_add(1, Ordering::Relaxed)));
+
+    let checkouts: Vec<_> = (0..100).map(|_| pool.checkout()).collect();
+    let mut vals: Vec<usize> = checkouts.iter().map(|val| ***val).collect();
+    vals.sort();
+
+    assert_eq!((0..100).collect::<Vec<_>>(), vals);
+}
+
+#[test]
+#[should_panic]
+pub fn test_safe_when_parallel_init_panics() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let next = AtomicUsize::new(0);
+    let _ = Pool::<Zomg>::builder()
+        .capacity(10)
+        .build_parallel(3, || {
+            if next.fetch_add(1, Ordering::Relaxed) == 5 {
+                panic!("oops");
+            }
+            Zomg
+        });
+}
+
+#[test]
+#[should_panic(expected = "requires a pool that keeps its initializer")]
+pub fn test_parallel_init_rejects_growing() {
+    use pool::OnEmpty;
+
+    let _ = Pool::<Dirty<usize>>::builder()
+        .capacity(2)
+        .on_empty(OnEmpty::Grow { max: 4 })
+        .build_parallel(2, || Dirty(0));
+}
+
+#[test]
+pub fn test_init_with_slot_index() {
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(5)
+        .build_with(|idx| Dirty(idx * 10));
+
+    let checkouts: Vec<_> = (0..5).map(|_| pool.checkout()).collect();
+    let vals: Vec<usize> = checkouts.iter().map(|val| ***val).collect();
+
+    assert_eq!(vec![0, 10, 20, 30, 40], vals);
+}
+
+#[test]
+pub fn test_from_existing_values() {
+    let mut pool: Pool<Dirty<&str>> = Pool::from_iter(vec!["a", "b", "c"].into_iter().map(Dirty), 8);
+
+    let checkouts: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    let vals: Vec<&str> = checkouts.iter().map(|val| ***val).collect();
+
+    assert_eq!(vec!["a", "b", "c"], vals);
+    assert!(checkouts[0].extra().len() >= 8);
+    assert!(pool.try_checkout().is_none());
+}
+
+#[test]
+pub fn test_extending_pool() {
+    let mut pool: Pool<Dirty<usize>> = Pool::with_capacity(2, 16, || Dirty(0));
+
+    let mut vals = vec![];
+    vals.push(pool.checkout());
+    vals.push(pool.checkout());
+    assert!(pool.try_checkout().is_none());
+
+    pool.extend((1..4).map(Dirty));
+
+    for _ in 0..3 {
+        let val = pool.checkout();
+        assert!(**val > 0);
+        assert!(val.extra().len() >= 16);
+        vals.push(val);
+    }
+
+    assert!(pool.try_checkout().is_none());
+    drop(vals);
+
+    let vals: Vec<_> = (0..5).map(|_| pool.checkout()).collect();
+    assert_eq!(6, vals.iter().map(|val| ***val).sum::<usize>());
+}
+
+#[test]
+pub fn test_into_values() {
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(3)
+        .build_with(Dirty);
+
+    let val = pool.checkout();
+
+    let pool = match pool.try_into_values() {
+        Ok(_) => panic!("values taken while checked out"),
+        Err(pool) => pool,
+    };
+
+    drop(val);
+
+    let vals: Vec<usize> = pool.try_into_values().ok().unwrap()
+        .into_iter().map(|val| val.0).collect();
+
+    assert_eq!(vec![0, 1, 2], vals);
+}
+
+#[test]
+pub fn test_drain_idle() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+
+    let mut pool: Pool<Dirty<usize>> = Pool::with_capacity(3, 0, move || {
+        Dirty(counter.fetch_add(1, Ordering::Relaxed))
+    });
+
+    let held = pool.checkout();
+
+    let mut drained: Vec<usize> = pool.drain_idle().into_iter().map(|val| val.0).collect();
+    drained.sort();
+    assert_eq!(vec![1, 2], drained);
+    assert!(pool.drain_idle().is_empty());
+
+    // Emptied entries are re-initialized lazily
+    let vals: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+    assert_eq!(5, created.load(Ordering::Relaxed));
+    assert_eq!(7, vals.iter().map(|val| ***val).sum::<usize>());
+    assert!(pool.try_checkout().is_none());
+
+    drop(held);
+    assert_eq!(vec![0], pool.drain_idle().into_iter().map(|val| val.0).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_drain_idle_without_init() {
+    let mut pool: Pool<Dirty<usize>> = Pool::from_vec(vec![Dirty(1), Dirty(2)], 0);
+
+    assert_eq!(2, pool.drain_idle().len());
+    assert!(pool.try_checkout().is_none());
+
+    // Emptied entries are refilled first
+    pool.extend(vec![Dirty(3)]);
+    assert_eq!(3, **pool.checkout());
+    assert_eq!(1, pool.drain_idle().len());
+}
+
+#[test]
+pub fn test_unbounded_pool() {
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(2)
+        .unbounded()
+        .build(|| Dirty(0));
+
+    let mut vals = vec![];
+
+    for i in 0..10 {
+        let mut val = pool.checkout();
+        **val = i;
+        vals.push(val);
+    }
+
+    drop(vals);
+
+    // The grown entries are reused
+    let vals: Vec<_> = (0..10).map(|_| pool.checkout()).collect();
+    assert_eq!(45, vals.iter().map(|val| ***val).sum::<usize>());
+}
+
+#[test]
+pub fn test_on_empty_grow() {
+    use pool::OnEmpty;
+
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(2)
+        .segment_size(1)
+        .on_empty(OnEmpty::Grow { max: 3 })
+        .build(|| Dirty(0));
+
+    let vals: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    assert!(pool.try_checkout().is_none());
+
+    drop(vals);
+    assert!(pool.try_checkout().is_some());
+}
+
+#[test]
+pub fn test_on_empty_transient() {
+    use pool::OnEmpty;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+
+    let mut pool: Pool<Dirty<Vec<u8>>> = Pool::builder()
+        .capacity(1)
+        .extra(64)
+        .align_extra(64)
+        .on_empty(OnEmpty::Transient)
+        .build(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Dirty(vec![1, 2, 3])
+        });
+
+    let a = pool.checkout();
+    let mut b = pool.checkout();
+    assert_eq!(2, created.load(Ordering::Relaxed));
+
+    assert_eq!(0, b.extra().as_ptr() as usize % 64);
+    assert!(b.extra().iter().all(|&b| b == 0));
+    b.extra_mut()[0] = 1;
+
+    // The transient value is dropped, not added to the pool
+    drop(b);
+    assert!(pool.try_checkout().is_some());
+    assert_eq!(3, created.load(Ordering::Relaxed));
+
+    drop(a);
+    let _a = pool.checkout();
+    assert_eq!(3, created.load(Ordering::Relaxed));
+}
+
+#[test]
+pub fn test_on_empty_block() {
+    use pool::OnEmpty;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .build(|| Dirty(0));
+
+    for i in 0..10 {
+        let mut val = pool.checkout();
+        **val = i;
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(1));
+            drop(val);
+        });
+    }
+
+    let val = pool.checkout();
+    assert_eq!(9, **val);
+
+    // Never waits
+    assert!(pool.try_checkout().is_none());
+}
+
+#[test]
+pub fn test_poll_checkout() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread;
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+
+    let mut val = match pool.poll_checkout(&mut cx) {
+        Poll::Ready(val) => val,
+        Poll::Pending => panic!("pool is not empty"),
+    };
+
+    **val = 5;
+
+    assert!(pool.poll_checkout(&mut cx).is_pending());
+    assert!(pool.poll_checkout(&mut cx).is_pending());
+    assert_eq!(0, count.0.load(Ordering::SeqCst));
+
+    thread::spawn(move || drop(val)).join().unwrap();
+
+    // Woken once, even though it polled twice
+    assert_eq!(1, count.0.load(Ordering::SeqCst));
+
+    match pool.poll_checkout(&mut cx) {
+        Poll::Ready(val) => assert_eq!(5, **val),
+        Poll::Pending => panic!("value was checked in"),
+    }
+}
+
+#[test]
+pub fn test_poll_checkout_wakes_one() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Wake, Waker};
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let counts: Vec<_> = (0..3).map(|_| Arc::new(Count(AtomicUsize::new(0)))).collect();
+    let wakers: Vec<_> = counts.iter().map(|count| Waker::from(count.clone())).collect();
+    let woken = || counts.iter().map(|count| count.0.load(Ordering::SeqCst)).collect::<Vec<_>>();
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    let val = pool.checkout();
+
+    for waker in &wakers {
+        assert!(pool.poll_checkout(&mut Context::from_waker(waker)).is_pending());
+    }
+
+    // The longest waiting task is woken first
+    drop(val);
+    assert_eq!(vec![1, 0, 0], woken());
+
+    // It gives up, passing the wakeup on
+    pool.cancel_poll_checkout(&wakers[0]);
+    assert_eq!(vec![1, 1, 0], woken());
+
+    // The next one takes the value, the last one keeps waiting
+    let val = pool.poll_checkout(&mut Context::from_waker(&wakers[1]));
+    assert!(val.is_ready());
+    assert_eq!(vec![1, 1, 0], woken());
+
+    drop(val);
+    assert_eq!(vec![1, 1, 1], woken());
+}
+
+#[test]
+#[should_panic(expected = "pool is depleted")]
+pub fn test_checkout_depleted() {
+    use pool::OnEmpty;
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Fail)
+        .build(|| Dirty(0));
+
+    let _val = pool.checkout();
+    pool.checkout();
+}
+
+#[test]
+pub fn test_checkout_waits() {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    let val = pool.checkout();
+
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        drop(val);
+    });
+
+    // Waits for the value by default, rather than panicking
+    drop(pool.checkout());
+    handle.join().unwrap();
+
+    let _val = pool.checkout();
+    assert!(pool.try_checkout().is_none());
+}
+
+#[test]
+pub fn test_segmented_pool() {
+    // Room for about three entries per segment
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(10)
+        .extra(100)
+        .segment_size(400)
+        .build_with(Dirty);
+
+    let mut vals: Vec<_> = (0..10).map(|_| pool.checkout()).collect();
+    assert!(pool.try_checkout().is_none());
+
+    for val in &mut vals {
+        let idx = ***val;
+        val.extra_mut()[99] = idx as u8;
+    }
+
+    pool.extend((10..15).map(Dirty));
+    vals.extend((0..5).map(|_| pool.checkout()));
+
+    let mut idxs: Vec<usize> = vals.iter().map(|val| ***val).collect();
+    idxs.sort();
+    assert_eq!((0..15).collect::<Vec<_>>(), idxs);
+
+    for val in &vals[..10] {
+        assert_eq!(***val as u8, val.extra()[99]);
+    }
+}
+
+#[test]
+pub fn test_segmented_parallel_init() {
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(50)
+        .segment_size(256)
+        .build_parallel(3, || Dirty(1));
+
+    let vals: Vec<_> = (0..50).map(|_| pool.checkout()).collect();
+    assert_eq!(50, vals.iter().map(|val| ***val).sum::<usize>());
+    assert!(pool.try_checkout().is_none());
+}
+
+#[test]
+pub fn test_hot_tier() {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(8)
+        .hot_capacity(2)
+        .build_with(Dirty);
+
+    let vals: Vec<_> = (0..8).map(|_| pool.checkout()).collect();
+    let idxs: Vec<usize> = vals.iter().map(|val| ***val).collect();
+    drop(vals);
+
+    // The first two checkins fill the hot tier and are reused first
+    let a = pool.checkout();
+    let b = pool.checkout();
+    let mut hot = [**a, **b];
+    hot.sort();
+    assert_eq!(&idxs[..2], &hot[..]);
+
+    drop(a);
+    drop(b);
+    thread::sleep(Duration::from_millis(5));
+    assert_eq!(2, pool.demote_idle(Duration::from_millis(1)));
+    assert_eq!(0, pool.demote_idle(Duration::from_millis(1)));
+
+    // All values are still available
+    let vals: Vec<_> = (0..8).map(|_| pool.checkout()).collect();
+    assert!(pool.try_checkout().is_none());
+    drop(vals);
+}
+
+#[test]
+pub fn test_parent_fallback() {
+    let mut parent = Pool::with_capacity(2, 0, || Dirty(0));
+    let mut child = Pool::builder()
+        .capacity(1)
+        .parent(&parent)
+        .build(|| Dirty(1));
+
+    let a = child.checkout();
+    let b = child.checkout();
+    let c = child.checkout();
+    assert_eq!(1, **a);
+    assert_eq!(0, **b);
+    assert_eq!(0, **c);
+    assert!(child.try_checkout().is_none());
+    assert!(parent.try_checkout().is_none());
+
+    // Borrowed values go back to the parent
+    drop(b);
+    assert_eq!(0, **parent.checkout());
+
+    // Local values are preferred
+    drop(a);
+    assert_eq!(1, **child.checkout());
+}
+
+#[test]
+pub fn test_children_share_parent() {
+    use std::thread;
+
+    let parent = Pool::with_capacity(4, 0, || Dirty(0));
+
+    let handles: Vec<_> = (0..4).map(|_| {
+        let mut child = Pool::builder().parent(&parent).build(|| Dirty(0));
+
+        thread::spawn(move || {
+            let mut checkouts = 0;
+
+            for _ in 0..10_000 {
+                if let Some(mut val) = child.try_checkout() {
+                    **val += 1;
+                    checkouts += 1;
+                }
+            }
+
+            checkouts
+        })
+    }).collect();
+
+    let checkouts: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+    let values = parent.try_into_values().ok().unwrap();
+
+    // No value was handed out twice at once
+    assert_eq!(checkouts, values.iter().map(|val| **val).sum::<usize>());
+}
+
+#[test]
+pub fn test_shared_checkout() {
+    use std::thread;
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+
+    let mut val = pool.checkout();
+    **val = 42;
+
+    let shared = val.share();
+    let handles: Vec<_> = (0..4).map(|_| {
+        let shared = shared.clone();
+        thread::spawn(move || **shared)
+    }).collect();
+
+    for handle in handles {
+        assert_eq!(42, handle.join().unwrap());
+    }
+
+    assert!(pool.try_checkout().is_none());
+    drop(shared);
+    assert_eq!(42, **pool.checkout());
+}
+
+#[test]
+pub fn test_upgrade_shared_checkout() {
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+
+    let a = pool.checkout().share();
+    let b = a.clone();
+
+    let b = b.try_upgrade().err().unwrap();
+    drop(a);
+
+    let mut val = b.try_upgrade().ok().unwrap();
+    **val = 7;
+    assert!(pool.try_checkout().is_none());
+
+    // Sharing again starts over with a single handle
+    let val = val.share().try_upgrade().ok().unwrap();
+    drop(val);
+    assert_eq!(7, **pool.checkout());
+}
+
+#[test]
+pub fn test_parked_checkouts() {
+    let mut pool = Pool::with_capacity(2, 0, || Dirty(0));
+
+    let a = pool.checkout();
+    let b = pool.checkout();
+    let a = pool.park(a);
+    let b = pool.park(b);
+    assert!(a != b);
+
+    **pool.get_mut(a).unwrap() = 1;
+    **pool.get_mut(b).unwrap() = 2;
+    assert_eq!(1, **pool.get(a).unwrap());
+    assert_eq!(2, **pool.get(b).unwrap());
+
+    // Parked values stay checked out
+    assert!(pool.try_checkout().is_none());
+
+    let val = pool.unpark(a).unwrap();
+    assert_eq!(1, **val);
+    assert!(pool.get(a).is_none());
+    assert!(pool.unpark(a).is_none());
+
+    // Parking the same entry again gives a new id
+    let c = pool.park(val);
+    assert!(a != c);
+    assert!(pool.get(a).is_none());
+    assert_eq!(1, **pool.get(c).unwrap());
+
+    drop(pool.unpark(c));
+    assert_eq!(1, **pool.checkout());
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+pub fn test_ffi_buffer_pool() {
+    use pool::ffi::*;
+    use std::slice;
+
+    unsafe {
+        let pool = pool_new(2, 100);
+
+        let a = pool_checkout(pool);
+        let b = pool_checkout(pool);
+        assert!(!a.is_null() && !b.is_null());
+        assert!(pool_checkout(pool).is_null());
+        assert!(pool_buffer_len(a) >= 100);
+
+        slice::from_raw_parts_mut(pool_buffer_ptr(a), pool_buffer_len(a))[99] = 7;
+        pool_checkin(pool, b);
+        pool_checkin(pool, a);
+
+        let c = pool_checkout(pool);
+        assert_eq!(7, *pool_buffer_ptr(c).add(99));
+        pool_checkin(pool, c);
+
+        pool_free(pool);
+    }
+}
+
+#[cfg(all(unix, feature = "ffi"))]
+#[test]
+pub fn test_ffi_checkin_to_another_pool_aborts() {
+    use pool::ffi::*;
+
+    unsafe {
+        let a = pool_new(1, 8);
+        let b = pool_new(1, 8);
+        let buf = pool_checkout(a);
+
+        let pid = libc::fork();
+        assert!(pid >= 0);
+
+        if pid == 0 {
+            pool_checkin(b, buf);
+            libc::_exit(0);
+        }
+
+        let mut status = 0;
+        libc::waitpid(pid, &mut status, 0);
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::SIGABRT, libc::WTERMSIG(status));
+
+        pool_checkin(a, buf);
+        pool_free(a);
+        pool_free(b);
+    }
+}
+
+#[cfg(all(unix, feature = "shm"))]
+#[test]
+pub fn test_shm_pool() {
+    use pool::shm::ShmPool;
+    use std::process;
+
+    let name = format!("/pool-test-{}", process::id());
+
+    let a = ShmPool::create(&name, 2, 100).unwrap();
+    assert!(ShmPool::create(&name, 2, 100).is_err());
+
+    // A second mapping of the same pool, as another process would have
+    let b = ShmPool::open(&name).unwrap();
+    assert_eq!(2, b.capacity());
+    assert_eq!(100, b.buffer_size());
+
+    let mut buf = a.try_checkout().unwrap();
+    buf[..5].copy_from_slice(b"hello");
+    let idx = buf.into_index();
+
+    let other = b.try_checkout().unwrap();
+    assert!(idx != other.index());
+    assert!(a.try_checkout().is_none());
+
+    let buf = unsafe { b.from_index(idx) };
+    assert_eq!(b"hello", &buf[..5]);
+
+    drop(other);
+    drop(buf);
+
+    assert_eq!(b"hello", &a.try_checkout().unwrap()[..5]);
+
+    ShmPool::remove(&name).unwrap();
+    assert!(ShmPool::open(&name).is_err());
+
+    // Still usable after removal
+    assert_eq!(b"hello", &b.try_checkout().unwrap()[..5]);
+}
+
+#[test]
+#[cfg(all(unix, feature = "shm"))]
+pub fn test_shm_concurrent_checkouts() {
+    use pool::shm::ShmPool;
+    use std::{process, thread};
+
+    let name = format!("/pool-test-concurrent-{}", process::id());
+    let pool = ShmPool::create(&name, 3, 8).unwrap();
+
+    // Threads racing on the free list never share a buffer
+    thread::scope(|s| {
+        for i in 0..4u8 {
+            let pool = &pool;
+
+            s.spawn(move || {
+                for _ in 0..10_000 {
+                    if let Some(mut buf) = pool.try_checkout() {
+                        buf.fill(i);
+                        thread::yield_now();
+                        assert!(buf.iter().all(|&b| b == i));
+                    }
+                }
+            });
+        }
+    });
+
+    // Every buffer is back on the list
+    let held: Vec<_> = (0..3).map(|_| pool.try_checkout().unwrap()).collect();
+    assert!(pool.try_checkout().is_none());
+    drop(held);
+
+    ShmPool::remove(&name).unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "shm"))]
+pub fn test_shm_recover() {
+    use pool::shm::ShmPool;
+    use std::process;
+
+    let name = format!("/pool-test-recover-{}", process::id());
+    let pool = ShmPool::create(&name, 2, 100).unwrap();
+
+    let mine = pool.try_checkout().unwrap();
+
+    // A child process checks a buffer out and dies without checking it in
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+
+        if pid == 0 {
+            let pool = ShmPool::open(&name).unwrap();
+            pool.try_checkout().unwrap().into_index();
+            libc::_exit(0);
+        }
+
+        libc::waitpid(pid, std::ptr::null_mut(), 0);
+    }
+
+    assert!(pool.try_checkout().is_none());
+
+    // Buffers of live processes are left alone
+    assert_eq!(1, pool.recover());
+    assert_eq!(0, pool.recover());
+
+    assert!(pool.try_checkout().is_some());
+    drop(mine);
+
+    ShmPool::remove(&name).unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "shm"))]
+pub fn test_shm_recover_killed_mid_checkout() {
+    use pool::shm::ShmPool;
+    use std::{process, thread};
+    use std::time::Duration;
+
+    let name = format!("/pool-test-killed-{}", process::id());
+    let pool = ShmPool::create(&name, 4, 100).unwrap();
+
+    // A child process checks buffers out and in until it is killed, at any
+    // point of a checkout
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+
+        if pid == 0 {
+            let pool = ShmPool::open(&name).unwrap();
+
+            loop {
+                let a = pool.try_checkout();
+                let b = pool.try_checkout();
+                drop((a, b));
+            }
+        }
+
+        thread::sleep(Duration::from_millis(20));
+        libc::kill(pid, libc::SIGKILL);
+        libc::waitpid(pid, std::ptr::null_mut(), 0);
+    }
+
+    // Whatever the child held is reclaimed, nothing leaked
+    assert!(pool.recover() <= 2);
+    let held: Vec<_> = (0..4).map(|_| pool.try_checkout().unwrap()).collect();
+    assert!(pool.try_checkout().is_none());
+    drop(held);
+
+    ShmPool::remove(&name).unwrap();
+}
+
+#[test]
+#[cfg(feature = "tower")]
+pub fn test_pool_service() {
+    use pool::Checkout;
+    use pool::service::PoolService;
+    use std::future::{self, Ready};
+    use std::task::{Context, Poll, Waker};
+    use tower::Service;
+    use tower::load::Load;
+
+    // Holds on to the values it is handed
+    struct Hold(Vec<Checkout<Dirty<usize>>>);
+
+    impl Service<(Checkout<Dirty<usize>>, usize)> for Hold {
+        type Response = ();
+        type Error = ();
+        type Future = Ready<Result<(), ()>>;
+
+        fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, (mut val, req): (Checkout<Dirty<usize>>, usize)) -> Self::Future {
+            **val = req;
+            self.0.push(val);
+            future::ready(Ok(()))
+        }
+    }
+
+    let mut cx = Context::from_waker(Waker::noop());
+    let pool = Pool::with_capacity(2, 0, || Dirty(0));
+    let mut service = PoolService::new(Hold(vec![]), pool);
+
+    assert_eq!(0.0, service.load());
+
+    for i in 0..2 {
+        assert_eq!(Poll::Ready(Ok(())), service.poll_ready(&mut cx));
+        assert_eq!(Ok(()), service.call(i).into_inner());
+    }
+
+    // The pool is exhausted, applying backpressure
+    assert!(service.poll_ready(&mut cx).is_pending());
+    assert_eq!(1.0, service.load());
+
+    service.get_mut().0.pop();
+    assert_eq!(0.5, service.load());
+    assert_eq!(Poll::Ready(Ok(())), service.poll_ready(&mut cx));
+}
+
+#[test]
+pub fn test_local_pool() {
+    use pool::LocalPool;
+    use std::rc::Rc;
+
+    let tag = Rc::new(());
+    let mut pool = LocalPool::with_capacity(2, 16, move || Dirty((tag.clone(), 0)));
+
+    let mut a = pool.checkout();
+    let b = pool.checkout();
+    assert!(pool.try_checkout().is_none());
+    assert!(b.extra().len() >= 16);
+
+    a.1 = 5;
+    a.extra_mut()[15] = 9;
+    drop(a);
+
+    let a = pool.checkout();
+    assert_eq!(5, a.1);
+    assert_eq!(9, a.extra()[15]);
+
+    // Values outlive the pool while checked out
+    let tag = (**a).0.clone();
+    drop(pool);
+    drop(b);
+    assert_eq!(3, Rc::strong_count(&tag));
+    drop(a);
+    assert_eq!(1, Rc::strong_count(&tag));
+}
+
+#[test]
+pub fn test_custom_reset() {
+    use pool::{Clean, Reset};
+
+    #[derive(Clone, Default)]
+    struct Conn {
+        uses: usize,
+        buf: Vec<u8>,
+    }
+
+    // Keeps the use count, which a blanket impl would have clobbered
+    impl Reset for Conn {
+        fn reset(&mut self) {
+            self.uses += 1;
+            self.buf.clear();
+        }
+    }
+
+    let mut pool = Pool::with_capacity(1, 0, Conn::default);
+
+    pool.checkout().buf.push(1);
+    let conn = pool.checkout();
+    assert_eq!(2, conn.uses);
+    assert!(conn.buf.is_empty());
+    drop(conn);
+
+    let mut pool = Pool::with_capacity(1, 0, || Clean(Conn::default()));
+
+    pool.checkout().uses = 5;
+    assert_eq!(0, pool.checkout().uses);
+}
+
+#[test]
+pub fn test_reset_context() {
+    use pool::{Reset, ResetWith};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Session {
+        epoch: usize,
+    }
+
+    impl Reset for Session {
+        fn reset(&mut self) {
+        }
+    }
+
+    impl ResetWith<Arc<AtomicUsize>> for Session {
+        fn reset_with(&mut self, epoch: &Arc<AtomicUsize>) {
+            self.epoch = epoch.load(Ordering::Relaxed);
+        }
+    }
+
+    let epoch = Arc::new(AtomicUsize::new(1));
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .reset_context(epoch.clone())
+        .build(|| Session { epoch: 0 });
+
+    assert_eq!(1, pool.checkout().epoch);
+    epoch.store(2, Ordering::Relaxed);
+    assert_eq!(2, pool.checkout().epoch);
+}
+
+#[cfg(feature = "secure")]
+#[test]
+pub fn test_zeroize_on_checkin() {
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .extra(64)
+        .zeroize_on_checkin()
+        .build(|| Dirty(vec![0u8; 16]));
+
+    let mut val = pool.checkout();
+    val.copy_from_slice(&[0xaa; 16]);
+    for byte in val.extra_mut() {
+        *byte = 0xaa;
+    }
+
+    // Keep a view on the memory to check that it was wiped
+    let ptr = val.as_ptr();
+    drop(val);
+
+    let val = pool.checkout();
+    assert!(val.is_empty());
+    assert!(val.extra().iter().all(|&byte| byte == 0));
+    assert_eq!(0, unsafe { *ptr });
+}
+
+#[test]
+pub fn test_lock_memory() {
+    let pool = Pool::with_capacity(4, 64, || Dirty(0));
+    assert!(!pool.is_memory_locked());
+
+    // Whether locking succeeds depends on the environment, the pool must
+    // work either way
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .extra(64)
+        .unbounded()
+        .lock_memory()
+        .build(|| Dirty(0));
+
+    let vals: Vec<_> = (0..8).map(|_| pool.checkout()).collect();
+    drop(vals);
+    drop(pool);
+}
+
+#[test]
+pub fn test_release_idle_memory() {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .extra(64 * 1024)
+        .track_idle()
+        .build(|| Dirty(0));
+
+    {
+        let mut val = pool.checkout();
+        **val = 7;
+        val.extra_mut()[0] = 1;
+    }
+
+    // Only the entry that was never checked out has been idle for that long
+    let released = pool.release_idle_memory(Duration::from_secs(3600));
+
+    if cfg!(unix) {
+        assert!((60 * 1024..=64 * 1024).contains(&released));
+    }
+
+    thread::sleep(Duration::from_millis(10));
+    let released = pool.release_idle_memory(Duration::from_millis(1));
+
+    if cfg!(unix) {
+        assert!(released >= 2 * 60 * 1024);
+    }
+
+    // The values are kept, and the entries remain usable
+    let mut val = pool.checkout();
+    assert_eq!(7, **val);
+    val.extra_mut()[1024] = 1;
+
+    // Without tracking, nothing is released
+    let mut pool = Pool::with_capacity(2, 64 * 1024, || Dirty(0));
+    assert_eq!(0, pool.release_idle_memory(Duration::from_secs(0)));
+}
+
+#[test]
+pub fn test_warm_up() {
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .extra(16 * 1024)
+        .huge_pages()
+        .build(|| Dirty(0));
+
+    let mut held = pool.checkout();
+    held.extra_mut()[8 * 1024] = 3;
+
+    {
+        let mut val = pool.checkout();
+        val.extra_mut()[4 * 1024] = 7;
+    }
+
+    pool.warm_up();
+
+    // Contents are left alone
+    assert_eq!(3, held.extra()[8 * 1024]);
+
+    let val = pool.checkout();
+    assert_eq!(7, val.extra()[4 * 1024]);
+    assert!(val.extra().iter().enumerate().all(|(i, &b)| i == 4 * 1024 || b == 0));
+}
+
+#[test]
+pub fn test_extra_usage() {
+    let pool = Pool::with_capacity(4, 64, || Dirty(0));
+    assert!(pool.extra_usage().is_none());
+
+    let mut pool = Pool::builder()
+        .capacity(10)
+        .extra(256)
+        .track_extra_usage()
+        .build(|| Dirty(0));
+
+    let mut vals: Vec<_> = (0..10).map(|_| pool.checkout()).collect();
+
+    for (i, val) in vals.iter_mut().enumerate() {
+        val.extra_mut()[i * 10] = 1;
+    }
+
+    // Only counted on checkin
+    assert_eq!(0, pool.extra_usage().unwrap().max);
+    drop(vals);
+
+    let usage = pool.extra_usage().unwrap();
+    assert_eq!(10, usage.entries);
+    assert!(usage.available >= 256);
+    assert_eq!(1, usage.min);
+    assert_eq!(41, usage.p50);
+    assert_eq!(81, usage.p90);
+    assert_eq!(81, usage.p99);
+    assert_eq!(91, usage.max);
+    assert_eq!(46, usage.mean);
+
+    // The high-water mark never goes down
+    let mut val = pool.checkout();
+    val.extra_mut().iter_mut().for_each(|b| *b = 0);
+    drop(val);
+
+    assert_eq!(91, pool.extra_usage().unwrap().max);
+}
+
+#[test]
+pub fn test_bump_alloc() {
+    let mut pool = Pool::with_capacity(1, 64, || Dirty(0));
+    let mut val = pool.checkout();
+
+    {
+        let bump = val.bump();
+        let a = bump.alloc::<u8>(3).unwrap();
+        let b = bump.alloc::<u32>(2).unwrap();
+
+        a.copy_from_slice(&[1, 2, 3]);
+        b[1] = 7;
+
+        // Padded to the alignment of `u32`
+        assert_eq!(0, b.as_ptr() as usize % 4);
+        assert!(bump.used() >= 11);
+
+        assert!(bump.alloc::<u8>(bump.remaining() + 1).is_none());
+        assert_eq!(bump.remaining(), bump.alloc::<u8>(bump.remaining()).unwrap().len());
+        assert!(bump.alloc::<u64>(1).is_none());
+
+        assert_eq!(&[1, 2, 3], a);
+        assert_eq!(&[0, 7], b);
+    }
+
+    // Starts over from the first byte
+    let bump = val.bump();
+    assert_eq!(&[1, 2, 3], bump.alloc_copy(&[1u8, 2, 3]).unwrap());
+    assert_eq!(3, bump.used());
+}
+
+#[test]
+pub fn test_arena() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Noisy(u32, Rc<RefCell<Vec<u32>>>);
+
+    impl Drop for Noisy {
+        fn drop(&mut self) {
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    let dropped = Rc::new(RefCell::new(vec![]));
+    let mut pool = Pool::with_capacity(1, 128, || Dirty(0));
+    let mut val = pool.checkout();
+
+    {
+        let arena = val.arena();
+        let a = arena.alloc(Noisy(1, dropped.clone())).ok().unwrap();
+        let b = arena.alloc(Noisy(2, dropped.clone())).ok().unwrap();
+        assert_eq!(7, *arena.alloc(7u64).ok().unwrap());
+
+        a.0 += 10;
+        assert_eq!(2, b.0);
+
+        // Handed back when out of room
+        assert!(arena.alloc([0u8; 128]).is_err());
+        let rejected = arena.alloc((Noisy(3, dropped.clone()), [0u8; 128]));
+        assert_eq!(3, rejected.err().unwrap().0 .0);
+
+        assert_eq!(vec![3], *dropped.borrow());
+        dropped.borrow_mut().clear();
+    }
+
+    // Dropped in reverse order along with the arena
+    assert_eq!(vec![2, 11], *dropped.borrow());
+}
+
+#[test]
+pub fn test_typed_extra() {
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .extra_of::<u128>(5)
+        .build_with_extra(|idx, extra: &mut [u128]| {
+            extra[4] = idx as u128;
+            Dirty(extra.len())
+        });
+
+    let mut vals: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+
+    for val in &mut vals {
+        assert!(***val >= 5);
+        assert_eq!(***val, val.extra().len());
+        assert_eq!(0, val.extra().as_ptr() as usize % 16);
+        assert!(val.extra()[4] < 3);
+
+        val.extra_mut()[0] = u128::MAX;
+    }
+
+    // Each entry has storage of its own
+    drop(vals);
+    assert_eq!(1, pool.checkout().extra().iter().filter(|&&v| v == u128::MAX).count());
+
+    let parent = Pool::builder()
+        .capacity(1)
+        .extra_of::<[u32; 4]>(2)
+        .build(|| Dirty(0));
+
+    let mut child = Pool::builder()
+        .extra_of::<[u32; 4]>(2)
+        .parent(&parent)
+        .build(|| Dirty(1));
+
+    assert_eq!([0; 4], child.checkout().extra()[1]);
+}
+
+#[test]
+pub fn test_align_extra() {
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .extra(100)
+        .align_extra(64)
+        .segment_size(1)
+        .unbounded()
+        .build(|| Dirty(1u8));
+
+    let vals: Vec<_> = (0..6).map(|_| pool.checkout()).collect();
+
+    for val in &vals {
+        assert_eq!(0, val.extra().as_ptr() as usize % 64);
+        assert!(val.extra().len() >= 100);
+    }
+
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .extra_of::<u64>(3)
+        .align_extra(4096)
+        .build_parallel(2, || Dirty(0u16));
+
+    let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+
+    for val in &vals {
+        assert_eq!(0, val.extra().as_ptr() as usize % 4096);
+        assert!(val.extra().len() >= 3);
+    }
+}
+
+#[test]
+pub fn test_named_regions() {
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .extra(8)
+        .region("header", 10, 1)
+        .region("body", 100, 64)
+        .region("trailer", 3, 4)
+        .build(|| Dirty(0));
+
+    let mut val = pool.checkout();
+
+    assert!(val.extra().len() >= 64 + 100 + 3);
+    assert!(val.region("missing").is_none());
+    assert!(val.region_at(3).is_none());
+
+    assert_eq!(10, val.region("header").unwrap().len());
+    assert_eq!(100, val.region_at(1).unwrap().len());
+    assert_eq!(0, val.region("body").unwrap().as_ptr() as usize % 64);
+    assert_eq!(0, val.region("trailer").unwrap().as_ptr() as usize % 4);
+
+    val.region_mut("header").unwrap().copy_from_slice(&[1; 10]);
+    val.region_at_mut(2).unwrap().copy_from_slice(&[3; 3]);
+
+    {
+        let mut regions = val.regions_mut();
+        let (_, header) = regions.next().unwrap();
+        let (name, body) = regions.next().unwrap();
+
+        assert_eq!("body", name);
+        body[0] = header[0] + 1;
+    }
+
+    assert_eq!(&[1; 10], val.region_at(0).unwrap());
+    assert_eq!(2, val.region("body").unwrap()[0]);
+    assert_eq!(&[3; 3], val.region("trailer").unwrap());
+
+    // Regions are laid out at the start of the extra bytes
+    assert_eq!(1, val.extra()[0]);
+    assert_eq!(2, val.extra()[64]);
+}
+
+#[test]
+pub fn test_pinned_checkout() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let created = Arc::new(AtomicUsize::new(0));
+    let c = created.clone();
+
+    let mut pool = Pool::with_capacity(1, 0, move || {
+        Dirty(c.fetch_add(1, Ordering::Relaxed))
+    });
+
+    let val = pool.checkout().into_pin();
+    let addr = &*val as *const Dirty<usize>;
+    assert_eq!(0, val.0);
+    drop(val);
+
+    // The pinned value was dropped in place and replaced in the same slot
+    let val = pool.checkout();
+    assert_eq!(1, **val);
+    assert_eq!(addr, &*val as *const Dirty<usize>);
+    drop(val);
+
+    // Values that were never pinned are reused
+    assert_eq!(1, **pool.checkout());
+    assert_eq!(2, created.load(Ordering::Relaxed));
+}
+
+#[test]
+#[should_panic(expected = "pinning requires a pool with an initializer")]
+pub fn test_pin_without_initializer() {
+    let mut pool = Pool::from_vec(vec![Dirty(0)], 0);
+    pool.checkout().into_pin();
+}
+
+#[test]
+pub fn test_raw_checkout() {
+    use pool::Checkout;
+
+    let mut parent = Pool::with_capacity(1, 16, || Dirty(0));
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .extra(16)
+        .parent(&parent)
+        .build(|| Dirty(1));
+
+    let mut val = pool.checkout();
+    assert_eq!(&*val as *const Dirty<i32> as *mut Dirty<i32>, val.data_ptr());
+    assert_eq!(val.extra().as_ptr() as *mut u8, val.extra_ptr());
+
+    unsafe { *val.extra_ptr().add(3) = 9; }
+    assert_eq!(9, val.extra_mut()[3]);
+
+    // Entries of the parent find their way home as well
+    let a = val.into_raw();
+    let b = pool.checkout().into_raw();
+    assert!(pool.try_checkout().is_none());
+
+    unsafe {
+        assert_eq!(1, (*a).0);
+        assert_eq!(0, (*b).0);
+
+        drop(Checkout::<_>::from_raw(b));
+        assert!(parent.try_checkout().is_some());
+
+        let a = Checkout::<Dirty<i32>>::from_raw(a);
+        assert_eq!(9, a.extra()[3]);
+    }
+
+    assert!(pool.try_checkout().is_some());
+}
+
+#[test]
+pub fn test_hold_times() {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .track_hold_time()
+        .build(|| Dirty(()));
+
+    assert!(pool.hold_times().is_none());
+
+    for _ in 0..9 {
+        drop(pool.checkout());
+    }
+
+    let val = pool.checkout();
+    thread::sleep(Duration::from_millis(20));
+    drop(val);
+
+    let times = pool.hold_times().unwrap();
+    assert_eq!(10, times.count);
+    assert!(times.p50 < Duration::from_millis(10));
+    assert!(times.max >= Duration::from_millis(20));
+    assert!(times.max < Duration::from_secs(10));
+
+    // Not tracked by default
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(()));
+    drop(pool.checkout());
+    assert!(pool.hold_times().is_none());
+}
+
+#[test]
+pub fn test_pool_status() {
+    use pool::OnEmpty;
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .on_empty(OnEmpty::Transient)
+        .build(|| Dirty(0));
+
+    let a = pool.checkout();
+    let b = pool.checkout();
+    let c = pool.checkout();
+
+    let status = pool.status();
+    assert_eq!(2, status.capacity);
+    assert_eq!(0, status.available);
+    assert_eq!(2, status.in_use);
+    assert_eq!(0, status.waiters);
+    assert_eq!(3, status.created);
+    assert_eq!(2, status.recycled);
+    assert_eq!(0, status.failed_checkouts);
+
+    drop((a, b, c));
+    drop(pool.drain_idle());
+    drop(pool.checkout());
+
+    let status = pool.status();
+    assert_eq!(2, status.available);
+    assert_eq!(4, status.created);
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    let _val = pool.checkout();
+
+    assert!(pool.try_checkout().is_none());
+    assert_eq!(1, pool.status().failed_checkouts);
+}
+
+#[test]
+pub fn test_pool_events() {
+    use pool::{OnEmpty, PoolEvent};
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .segment_size(1)
+        .on_empty(OnEmpty::Grow { max: 2 })
+        .build(|| Dirty(0));
+
+    let events = pool.events();
+
+    let a = pool.checkout();
+    let b = pool.checkout();
+    assert_eq!(Ok(PoolEvent::Grew { by: 1 }), events.try_recv());
+
+    // Reported once, until recovered
+    assert!(pool.try_checkout().is_none());
+    assert!(pool.try_checkout().is_none());
+    assert_eq!(Ok(PoolEvent::Exhausted), events.try_recv());
+    assert!(events.try_recv().is_err());
+
+    drop(a);
+    assert!(matches!(events.try_recv(), Ok(PoolEvent::Recovered { .. })));
+
+    drop(b);
+    assert!(events.try_recv().is_err());
+
+    pool.checkout().into_pin();
+    assert_eq!(Ok(PoolEvent::EntryRetired), events.try_recv());
+
+    assert_eq!(1, pool.drain_idle().len());
+    assert_eq!(Ok(PoolEvent::Shrank { by: 1 }), events.try_recv());
+
+    // Late subscribers only get new events
+    let late = pool.events();
+    drop(events);
+
+    pool.extend(vec![Dirty(1), Dirty(2)]);
+    assert_eq!(Ok(PoolEvent::Grew { by: 2 }), late.try_recv());
+}
+
+#[test]
+pub fn test_low_watermark() {
+    use pool::OnEmpty;
+    use std::sync::{Arc, Mutex};
+
+    let crossings = Arc::new(Mutex::new(vec![]));
+    let record = crossings.clone();
+
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .segment_size(1)
+        .on_empty(OnEmpty::Grow { max: 8 })
+        .low_watermark(0.5, move |below| record.lock().unwrap().push(below))
+        .build(|| Dirty(0));
+
+    let mut vals: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+    assert!(crossings.lock().unwrap().is_empty());
+
+    vals.push(pool.checkout());
+    assert_eq!(vec![true], *crossings.lock().unwrap());
+
+    // Staying below is not reported again
+    vals.push(pool.checkout());
+    assert_eq!(vec![true], *crossings.lock().unwrap());
+
+    // Growing does not bring availability back up by itself, the new entry
+    // is checked out right away
+    vals.push(pool.checkout());
+    assert_eq!(vec![true], *crossings.lock().unwrap());
+
+    vals.truncate(2);
+    assert_eq!(vec![true, false], *crossings.lock().unwrap());
+}
+
+#[test]
+pub fn test_auto_grow() {
+    use pool::PoolEvent;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .segment_size(1)
+        .auto_grow(0.5, Duration::from_millis(20), 3)
+        .build(|| Dirty(0));
+
+    let mut vals = vec![pool.checkout(), pool.checkout()];
+
+    // Below the watermark, but still within the grace period
+    assert!(pool.try_checkout().is_none());
+    assert_eq!(2, pool.capacity());
+
+    let events = pool.events();
+
+    thread::sleep(Duration::from_millis(30));
+    vals.push(pool.checkout());
+
+    assert_eq!(3, pool.capacity());
+    assert_eq!(Ok(PoolEvent::Grew { by: 1 }), events.try_recv());
+
+    // Never beyond the max
+    thread::sleep(Duration::from_millis(30));
+    assert!(pool.try_checkout().is_none());
+    assert_eq!(3, pool.capacity());
+}
+
+#[test]
+pub fn test_auto_shrink() {
+    use pool::PoolEvent;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .auto_shrink(0.5, Duration::from_millis(20), 2)
+        .build(|| Dirty(vec![0u8; 16]));
+
+    let events = pool.events();
+
+    // Above the watermark, but not for long enough
+    drop(pool.checkout());
+    assert!(events.try_recv().is_err());
+
+    thread::sleep(Duration::from_millis(30));
+    let val = pool.checkout();
+    assert_eq!(Ok(PoolEvent::Shrank { by: 2 }), events.try_recv());
+
+    // Retired values are created again on checkout
+    let vals: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    assert!(vals.iter().all(|val| val.len() == 16));
+    assert_eq!(6, pool.status().created);
+    drop(val);
+}
+
+#[test]
+pub fn test_adaptive() {
+    use pool::PoolEvent;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .adaptive(0.5, 2, 6, Duration::from_millis(20))
+        .build(|| Dirty(0));
+
+    let events = pool.events();
+    let mut vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+    assert!(pool.try_checkout().is_none());
+
+    // Sized for the peak of the interval, within the max
+    thread::sleep(Duration::from_millis(30));
+    vals.push(pool.checkout());
+    assert_eq!(6, pool.capacity());
+    assert_eq!(Ok(PoolEvent::Exhausted), events.try_recv());
+    assert_eq!(Ok(PoolEvent::Grew { by: 2 }), events.try_recv());
+
+    // Down to the min once the load is gone
+    vals.clear();
+    drop(pool.checkout());
+    thread::sleep(Duration::from_millis(30));
+    drop(pool.checkout());
+    assert!(matches!(events.try_recv(), Ok(PoolEvent::Recovered { .. })));
+    assert_eq!(Ok(PoolEvent::Shrank { by: 4 }), events.try_recv());
+    assert_eq!(6, pool.capacity());
+}
+
+#[test]
+pub fn test_checkout_dirty() {
+    use pool::Clean;
+
+    let mut pool = Pool::with_capacity(1, 0, || Clean(String::new()));
+
+    pool.checkout_dirty().push_str("hello");
+    assert_eq!("hello", pool.checkout_dirty().0);
+    assert_eq!("", pool.checkout().0);
+}
+
+#[test]
+pub fn test_reuse_count() {
+    let mut pool = Pool::with_capacity(2, 0, || Dirty(0));
+
+    let (a, b) = (pool.checkout(), pool.checkout());
+    assert_eq!((0, 0), (a.reuse_count(), b.reuse_count()));
+    drop(a);
+
+    for i in 1..4 {
+        assert_eq!(i, pool.checkout().reuse_count());
+    }
+
+    // Starts over with a new value
+    drop(b);
+    pool.drain_idle();
+    assert_eq!(0, pool.checkout().reuse_count());
+}
+
+#[test]
+pub fn test_entry_timestamps() {
+    use std::time::Instant;
+
+    let start = Instant::now();
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .track_idle()
+        .build(|| Dirty(0));
+
+    let val = pool.checkout();
+    let created = val.created_at().unwrap();
+    assert!(created >= start);
+    assert!(val.last_used().is_none());
+    drop(val);
+
+    let val = pool.checkout();
+    assert_eq!(Some(created), val.created_at());
+    let last_used = val.last_used().unwrap();
+    assert!(last_used >= created);
+
+    // The other entry was never used
+    let mut idle = vec![];
+    pool.for_each_idle(|entry| idle.push((**entry.value().unwrap(), entry.created_at(), entry.last_used())));
+    assert_eq!(vec![(0, Some(created), None)], idle);
+    drop(val);
+
+    // Untracked pools have no timestamps
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    drop(pool.checkout());
+    assert!(pool.checkout().created_at().is_none());
+    assert!(pool.checkout().last_used().is_none());
+}
+
+#[test]
+pub fn test_retire_idle_older_than() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    let next = AtomicUsize::new(0);
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .track_idle()
+        .build(move || Dirty(next.fetch_add(1, Ordering::Relaxed)));
+
+    let held = pool.checkout();
+    thread::sleep(Duration::from_millis(20));
+
+    assert_eq!(0, pool.retire_idle_older_than(Duration::from_secs(60), true));
+    assert_eq!(2, pool.retire_idle_older_than(Duration::from_millis(10), true));
+
+    // Only the idle values were replaced
+    let mut values = vec![];
+    pool.for_each_idle(|entry| values.push(**entry.value().unwrap()));
+    values.sort();
+    assert_eq!(vec![3, 4], values);
+    assert_eq!(5, pool.status().created);
+
+    // Without re-creating them, entries are filled on checkout
+    assert_eq!(2, pool.retire_idle_older_than(Duration::from_secs(0), false));
+    pool.for_each_idle(|entry| assert!(entry.value().is_none()));
+    assert_eq!(5, **pool.checkout());
+    drop(held);
+}
+
+#[test]
+pub fn test_checkout_retire() {
+    use pool::{PoolEvent, Reset};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Conn(u32, Arc<AtomicUsize>);
+
+    impl Reset for Conn {
+        fn reset(&mut self) {
+        }
+    }
+
+    impl Drop for Conn {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let d = dropped.clone();
+
+    let mut pool = Pool::with_capacity(1, 0, move || Conn(0, d.clone()));
+    let events = pool.events();
+
+    let mut conn = pool.checkout();
+    conn.0 = 1;
+    conn.retire();
+
+    assert_eq!(1, dropped.load(Ordering::Relaxed));
+    assert_eq!(Ok(PoolEvent::EntryRetired), events.try_recv());
+
+    // The entry is back in circulation with a new value
+    assert_eq!(0, pool.checkout().0);
+    assert_eq!(2, pool.status().created);
+}
+
+#[test]
+pub fn test_refresh_slot() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = AtomicUsize::new(0);
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .build_with(move |idx| Dirty((idx, calls.fetch_add(1, Ordering::Relaxed))));
+
+    let held = pool.checkout();
+    let held_idx = (**held).0;
+
+    assert!(!pool.refresh_slot(held_idx));
+    assert!(!pool.refresh_slot(3));
+
+    let idle_idx = (held_idx + 1) % 3;
+    assert!(pool.refresh_slot(idle_idx));
+
+    // Only that entry got a new value
+    let mut values = vec![];
+    pool.for_each_idle(|entry| values.push(**entry.value().unwrap()));
+
+    let other_idx = (held_idx + 2) % 3;
+    assert_eq!(2, values.len());
+    assert!(values.contains(&(idle_idx, 3)));
+    assert!(values.contains(&(other_idx, other_idx)));
+    drop(held);
+}
+
+#[test]
+pub fn test_set_init() {
+    let mut pool = Pool::with_capacity(2, 0, || Dirty(1));
+    let held = pool.checkout();
+
+    assert_eq!(0, pool.init_epoch());
+    assert_eq!(1, pool.set_init(|| Dirty(2)));
+    assert_eq!(1, pool.init_epoch());
+
+    // Idle values are replaced lazily, on checkout
+    pool.for_each_idle(|entry| assert_eq!(1, **entry.value().unwrap()));
+    assert_eq!(2, **pool.checkout());
+    assert_eq!(3, pool.status().created);
+
+    // So are the values checked out at the time of the swap
+    drop(held);
+    let (a, b) = (pool.checkout(), pool.checkout());
+    assert_eq!((2, 2), (**a, **b));
+    assert_eq!(4, pool.status().created);
+}
+
+#[test]
+pub fn test_merge() {
+    let mut a = Pool::from_vec(vec![Dirty(1), Dirty(2)], 4);
+    let mut b = Pool::with_capacity(3, 4, || Dirty(3));
+
+    // Leaves an empty entry in `a` to be refilled
+    let kept = a.checkout();
+    assert_eq!(1, a.drain_idle().len());
+    drop(kept);
+
+    let held = b.checkout();
+    b.checkout().extra_mut()[..4].copy_from_slice(&[7; 4]);
+
+    assert_eq!(2, a.merge(b));
+    assert_eq!(3, a.capacity());
+
+    let vals: Vec<_> = (0..3).map(|_| a.checkout()).collect();
+    let mut values: Vec<_> = vals.iter().map(|val| ***val).collect();
+    values.sort();
+    assert!(values == [1, 3, 3] || values == [2, 3, 3]);
+    assert!(vals.iter().any(|val| val.extra()[..4] == [7; 4]));
+
+    // Still usable on its own
+    assert_eq!(3, **held);
+}
+
+#[test]
+pub fn test_split() {
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .extra(4)
+        .region("header", 2, 2)
+        .build(|| Dirty(1));
+
+    let held = pool.checkout();
+    pool.checkout().region_mut("header").unwrap().copy_from_slice(&[9, 9]);
+
+    // Only idle values move, up to the number asked for
+    let mut split = pool.split(5);
+    assert_eq!(2, split.capacity());
+    assert_eq!(pool.checkout().extra().len(), split.checkout().extra().len());
+
+    let vals: Vec<_> = (0..2).map(|_| split.checkout()).collect();
+    assert!(vals.iter().all(|val| ***val == 1));
+    assert!(vals.iter().any(|val| val.region("header") == Some(&[9, 9][..])));
+
+    // Both pools refill their emptied entries with the initializer
+    drop(vals);
+    split.drain_idle();
+    assert_eq!(1, **split.checkout());
+    assert_eq!(3, pool.capacity());
+    drop(held);
+}
+
+#[test]
+#[cfg(feature = "log")]
+pub fn test_log() {
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    struct Capture(Mutex<Vec<(Level, String)>>);
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {
+        }
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(vec![]));
+
+    log::set_logger(&CAPTURE).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .name("conns")
+        .build(|| Dirty(0));
+
+    assert_eq!("conns", pool.name());
+
+    let val = pool.checkout();
+    assert!(pool.try_checkout().is_none());
+    assert!(pool.try_checkout().is_none());
+    val.retire();
+    pool.extend(vec![Dirty(1)]);
+
+    // Other tests log too
+    let logged: Vec<_> = CAPTURE.0.lock().unwrap().iter()
+        .filter(|(_, msg)| msg.starts_with("conns: "))
+        .map(|(level, msg)| (*level, msg.split(" after ").next().unwrap().to_string()))
+        .collect();
+
+    assert_eq!(vec![
+        (Level::Debug, "conns: recycled entry 0".to_string()),
+        (Level::Warn, "conns: exhausted".to_string()),
+        (Level::Debug, "conns: retired a value".to_string()),
+        (Level::Info, "conns: recovered".to_string()),
+        (Level::Info, "conns: grew by 1".to_string()),
+    ], logged);
+}
+
+#[test]
+pub fn test_on_exhausted() {
+    use pool::Exhaustion;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let s = seen.clone();
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .on_exhausted(Duration::from_millis(20), move |exhaustion| s.lock().unwrap().push(*exhaustion))
+        .build(|| Dirty(0));
+
+    let _vals = (pool.checkout(), pool.checkout());
+
+    // Rate limited
+    assert!(pool.try_checkout().is_none());
+    assert!(pool.try_checkout().is_none());
+    assert_eq!(1, seen.lock().unwrap().len());
+
+    thread::sleep(Duration::from_millis(30));
+    assert!(pool.try_checkout().is_none());
+
+    let expected = Exhaustion { waiters: 0, in_use: 2, capacity: 2 };
+    assert_eq!(vec![expected, expected], *seen.lock().unwrap());
+}
+
+#[test]
+pub fn test_wait_stats() {
+    use pool::OnEmpty;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool: Pool<Dirty<usize>> = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .build(|| Dirty(0));
+
+    // Not waiting
+    drop(pool.checkout());
+
+    let status = pool.status();
+    assert_eq!(0, status.waits);
+    assert_eq!(Duration::from_secs(0), status.wait_time);
+
+    let val = pool.checkout();
+
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        drop(val);
+    });
+
+    drop(pool.checkout());
+
+    let status = pool.status();
+    assert_eq!(1, status.waits);
+    assert!(status.wait_time >= Duration::from_millis(10));
+    assert_eq!(0, status.failed_checkouts);
+}
+
+#[test]
+pub fn test_priority_reserve() {
+    use pool::{OnEmpty, Priority};
+
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .reserve_high_priority(1)
+        .build(|| Dirty(0));
+
+    let a = pool.checkout();
+    let b = pool.try_checkout();
+    assert!(b.is_some());
+    assert!(pool.try_checkout().is_none());
+
+    let c = pool.try_checkout_priority(Priority::High);
+    assert!(c.is_some());
+    assert!(pool.try_checkout_priority(Priority::High).is_none());
+
+    // The freed entry is held back again
+    drop(a);
+    assert!(pool.try_checkout().is_none());
+
+    drop(c);
+    assert!(pool.try_checkout().is_some());
+
+    // Growing makes room for normal checkouts
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .reserve_high_priority(1)
+        .on_empty(OnEmpty::Grow { max: 4 })
+        .build(|| Dirty(0));
+
+    let _a = pool.checkout();
+    assert_eq!(2, pool.capacity());
+}
+
+#[test]
+pub fn test_priority_wakeups() {
+    use pool::Priority;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Wake, Waker};
+    use std::thread;
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let counts: Vec<_> = (0..3).map(|_| Arc::new(Count(AtomicUsize::new(0)))).collect();
+    let wakers: Vec<_> = counts.iter().map(|count| Waker::from(count.clone())).collect();
+    let woken = || counts.iter().map(|count| count.0.load(Ordering::SeqCst)).collect::<Vec<_>>();
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    let val = pool.checkout();
+
+    // Two normal tasks, then a high priority one
+    assert!(pool.poll_checkout(&mut Context::from_waker(&wakers[0])).is_pending());
+    assert!(pool.poll_checkout(&mut Context::from_waker(&wakers[1])).is_pending());
+    assert!(pool.poll_checkout_priority(&mut Context::from_waker(&wakers[2]), Priority::High).is_pending());
+
+    thread::spawn(move || drop(val)).join().unwrap();
+    assert_eq!(vec![0, 0, 1], woken());
+
+    let val = match pool.poll_checkout_priority(&mut Context::from_waker(&wakers[2]), Priority::High) {
+        std::task::Poll::Ready(val) => val,
+        std::task::Poll::Pending => panic!("value was checked in"),
+    };
+
+    thread::spawn(move || drop(val)).join().unwrap();
+    assert_eq!(vec![1, 0, 1], woken());
+}
+
+#[test]
+pub fn test_block_parks_in_priority_order() {
+    use pool::{OnEmpty, Priority};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Wake, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .build(|| Dirty(0));
+
+    let val = pool.checkout();
+    assert!(pool.poll_checkout(&mut Context::from_waker(&waker)).is_pending());
+
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        drop(val);
+    });
+
+    // Parks after spinning, and is unparked ahead of the polled task
+    let val = pool.checkout_priority(Priority::High);
+    assert_eq!(0, count.0.load(Ordering::SeqCst));
+    assert_eq!(1, pool.status().waiters);
+
+    drop(val);
+    assert_eq!(1, count.0.load(Ordering::SeqCst));
+    assert_eq!(0, pool.status().waiters);
+}
+
+#[test]
+pub fn test_checkout_cancelable() {
+    use pool::{CancelToken, OnEmpty};
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .build(|| Dirty(0));
+
+    let cancel = CancelToken::new();
+    let val = pool.checkout_cancelable(&cancel).unwrap();
+
+    // Checked back in before cancelling
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        drop(val);
+    });
+
+    assert!(pool.checkout_cancelable(&cancel).is_some());
+    handle.join().unwrap();
+
+    let val = pool.checkout();
+    let c = cancel.clone();
+
+    let handle = thread::spawn(move || {
+        let res = pool.checkout_cancelable(&c).is_none();
+        (res, pool)
+    });
+
+    thread::sleep(Duration::from_millis(10));
+    cancel.cancel();
+
+    let (res, mut pool) = handle.join().unwrap();
+    assert!(res);
+    assert_eq!(0, pool.status().waiters);
+    assert_eq!(0, pool.status().failed_checkouts);
+
+    // Stays cancelled, but still hands out available values
+    drop(val);
+    assert!(cancel.is_cancelled());
+    assert!(pool.checkout_cancelable(&cancel).is_some());
+
+    let _val = pool.checkout();
+    assert!(pool.checkout_cancelable(&cancel).is_none());
+
+    // Does not panic when the pool does not block
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Fail)
+        .build(|| Dirty(0));
+    let _val = pool.checkout();
+
+    assert!(pool.checkout_cancelable(&CancelToken::new()).is_none());
+    assert_eq!(1, pool.status().failed_checkouts);
+}
+
+#[test]
+pub fn test_lease() {
+    use pool::{OnEmpty, PoolEvent};
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(2)
+        .lease(Duration::from_millis(10))
+        .build(|| Dirty(0));
+
+    let events = pool.events();
+
+    // Returned in time
+    let mut val = pool.checkout();
+    **val = 1;
+    drop(val);
+
+    let mut hung = pool.checkout();
+    **hung = 2;
+
+    let val = pool.checkout();
+    assert!(pool.try_checkout().is_none());
+
+    thread::sleep(Duration::from_millis(20));
+
+    // Both expired
+    assert_eq!(2, pool.reclaim_expired_leases());
+    assert!(hung.is_revoked());
+    assert_eq!(4, pool.capacity());
+    assert_eq!(2, pool.in_use());
+
+    // Still the holder's
+    assert_eq!(2, **hung);
+    drop(hung);
+
+    // Retired, the replacement took its place
+    assert_eq!(3, pool.capacity());
+    assert_eq!(1, pool.in_use());
+    assert_eq!(0, pool.reclaim_expired_leases());
+
+    drop(val);
+    assert_eq!(2, pool.capacity());
+    assert_eq!(0, pool.in_use());
+
+    let got: Vec<_> = events.try_iter().collect();
+    assert_eq!(&[
+        PoolEvent::Exhausted,
+        PoolEvent::LeaseRevoked,
+        PoolEvent::LeaseRevoked,
+        PoolEvent::Grew { by: 2 },
+        PoolEvent::EntryRetired,
+        PoolEvent::EntryRetired,
+    ], &got[..]);
+
+    // Values of revoked checkouts are not reused
+    let vals: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+    assert!(vals.iter().all(|val| ***val != 2));
+
+    // Retired entries are taken back before the pool grows again
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(2, pool.reclaim_expired_leases());
+    assert_eq!(4, pool.capacity());
+    drop(vals);
+
+    assert_eq!(2, pool.capacity());
+    assert!(events.try_iter().all(|event| !matches!(event, PoolEvent::Grew { .. })));
+
+    // A blocked checkout gets a replacement
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .lease(Duration::from_millis(10))
+        .build(|| Dirty(0));
+
+    let hung = pool.checkout();
+    let _val = pool.checkout();
+
+    assert!(hung.is_revoked());
+    assert_eq!(2, pool.capacity());
+}
+
+#[test]
+pub fn test_lease_renew() {
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .lease(Duration::from_millis(10))
+        .build(|| Dirty(0));
+
+    let mut val = pool.checkout();
+    assert!(val.lease_remaining().unwrap() <= Duration::from_millis(10));
+
+    for _ in 0..3 {
+        thread::sleep(Duration::from_millis(5));
+        assert!(val.renew(Duration::from_millis(50)));
+    }
+
+    // Held for longer than the lease
+    assert_eq!(0, pool.reclaim_expired_leases());
+    assert!(val.lease_remaining().unwrap() > Duration::from_millis(10));
+
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(Some(Duration::from_secs(0)), val.lease_remaining());
+
+    assert_eq!(1, pool.reclaim_expired_leases());
+    assert!(!val.renew(Duration::from_millis(50)));
+    assert_eq!(Some(Duration::from_secs(0)), val.lease_remaining());
+
+    // Not leased
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(0));
+    assert_eq!(None, pool.checkout().lease_remaining());
+}
+
+#[test]
+pub fn test_transfer() {
+    let mut hot = Pool::builder()
+        .capacity(1)
+        .extra(8)
+        .build(|| Dirty(vec![0u8; 4]));
+
+    let mut cold = Pool::builder()
+        .capacity(1)
+        .extra(8)
+        .build(|| Dirty(vec![]));
+
+    let mut buf = hot.checkout();
+    buf[0] = 1;
+    buf.extra_mut()[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+    let held = cold.checkout();
+
+    // No idle entry
+    let buf = buf.transfer(&mut cold).unwrap_err();
+    assert_eq!(1, buf[0]);
+    drop(held);
+
+    assert!(buf.transfer(&mut cold).is_ok());
+    assert_eq!(1, hot.capacity());
+    assert_eq!(0, hot.in_use());
+    assert_eq!(1, cold.capacity());
+    assert_eq!(0, cold.in_use());
+
+    let buf = cold.checkout();
+    assert_eq!(vec![1, 0, 0, 0], **buf);
+    assert_eq!(&[1, 2, 3, 4], &buf.extra()[..4]);
+
+    // The value that was in the cold pool
+    let buf = hot.checkout();
+    assert!(buf.is_empty());
+    assert_eq!(&[0, 0, 0, 0], &buf.extra()[..4]);
+}
+
+#[test]
+pub fn test_transfer_rejected() {
+    use pool::{OnEmpty, Priority};
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .extra(8)
+        .build(|| Dirty(1));
+
+    // Extra storage of another size
+    let mut larger = Pool::builder()
+        .capacity(1)
+        .extra(1024)
+        .build(|| Dirty(2));
+
+    let buf = pool.checkout().transfer(&mut larger).unwrap_err();
+    assert_eq!(1, **buf);
+    assert_eq!(0, larger.in_use());
+
+    // Entries held back for high priority checkouts are left alone
+    let mut reserved = Pool::builder()
+        .capacity(1)
+        .extra(8)
+        .reserve_high_priority(1)
+        .build(|| Dirty(3));
+
+    let buf = buf.transfer(&mut reserved).unwrap_err();
+    assert_eq!(1, **buf);
+    assert_eq!(3, **reserved.checkout_priority(Priority::High));
+
+    // Transient values would be dropped along with the value
+    let mut transient = Pool::builder()
+        .capacity(0)
+        .extra(8)
+        .on_empty(OnEmpty::Transient)
+        .build(|| Dirty(4));
+
+    let buf = buf.transfer(&mut transient).unwrap_err();
+    assert_eq!(1, **buf);
+    assert_eq!(0, transient.in_use());
+}
+
+#[test]
+pub fn test_idle_order() {
+    use pool::IdleOrder;
+
+    fn order(order: IdleOrder) -> Vec<usize> {
+        let mut pool = Pool::builder()
+            .capacity(4)
+            .idle_order(order)
+            .build(|| Dirty(0));
+
+        let mut vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+
+        for (i, val) in vals.iter_mut().enumerate() {
+            ***val = i;
+        }
+
+        // Checked in 0, 1, 2, 3
+        drop(vals);
+
+        // Checked in again, in reverse
+        let mut vals: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+        vals.reverse();
+        drop(vals);
+
+        let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+        vals.iter().map(|val| ***val).collect()
+    }
+
+    assert_eq!(vec![3, 2, 1, 0], order(IdleOrder::Mru));
+    assert_eq!(vec![2, 3, 1, 0], order(IdleOrder::Lru));
+
+    // Idle entries are found on either list
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .idle_order(IdleOrder::Lru)
+        .build(|| Dirty(0));
+
+    let vals: Vec<_> = (0..2).map(|_| pool.checkout()).collect();
+    drop(vals);
+
+    let mut idle = 0;
+    pool.for_each_idle(|_| idle += 1);
+    assert_eq!(4, idle);
+    assert_eq!(4, pool.drain_idle().len());
+}
+
+#[test]
+pub fn test_shrink_lru_first() {
+    use pool::IdleOrder;
+    use std::time::Duration;
+
+    // Shrinks to two entries on every checkout
+    let mut pool = Pool::builder()
+        .capacity(4)
+        .idle_order(IdleOrder::Lru)
+        .adaptive(1.0, 2, 2, Duration::from_secs(0))
+        .build_with(Dirty);
+
+    let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+    assert_eq!(vec![0, 1, 2, 3], vals.iter().map(|val| ***val).collect::<Vec<_>>());
+
+    // Checked in 0, 1, 2, 3, and 0 again
+    drop(vals);
+    drop(pool.checkout());
+
+    let mut idle = vec![];
+    pool.for_each_idle(|entry| idle.extend(entry.value().map(|val| **val)));
+    idle.sort();
+    assert_eq!(vec![0, 2, 3], idle);
+
+    // Drops 2, the least recently used one, keeping 3 and 0
+    let val = pool.checkout();
+    assert_eq!(1, **val);
+
+    let mut idle = vec![];
+    pool.for_each_idle(|entry| idle.extend(entry.value().map(|val| **val)));
+    idle.sort();
+    assert_eq!(vec![0, 3], idle);
+}
+
+#[test]
+pub fn test_byte_budget() {
+    use pool::OnEmpty;
+
+    let mut pool = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Grow { max: 1024 })
+        .byte_budget(64 * 1024)
+        .build(|| Dirty(Vec::<u8>::with_capacity(1024)));
+
+    let start = pool.byte_usage().unwrap();
+    assert!(start >= 1024);
+
+    // Growth stops at the budget
+    let vals: Vec<_> = (0..1024).map_while(|_| pool.try_checkout()).collect();
+    assert!(vals.len() > 1);
+    assert!(vals.len() < 1024);
+    assert!(pool.byte_usage().unwrap() <= 64 * 1024);
+    drop(vals);
+
+    // Values that push the pool over are dropped on checkin
+    let mut val = pool.checkout();
+    val.reserve(128 * 1024);
+    drop(val);
+
+    assert!(pool.byte_usage().unwrap() <= 64 * 1024);
+    assert_eq!(1024, pool.checkout().capacity());
+
+    let pool = Pool::with_capacity(1, 0, || Dirty(0));
+    assert!(pool.byte_usage().is_none());
+}
+
+#[test]
+pub fn test_checkout_weighted() {
+    let mut pool = Pool::builder()
+        .capacity(3)
+        .weight(|val: &Dirty<usize>| **val)
+        .build_with(|idx| Dirty(idx + 1));
+
+    // 2 covers the weight on its own, 1 taken before it is put back
+    let vals = pool.checkout_weighted(2).unwrap();
+    assert_eq!(vec![2], vals.iter().map(|val| ***val).collect::<Vec<_>>());
+    assert_eq!(2, vals.weight());
+    assert_eq!(1, pool.in_use());
+
+    // 1 and 3 don't cover 5, both are put back
+    assert!(pool.checkout_weighted(5).is_none());
+    assert_eq!(1, pool.in_use());
+
+    let more = pool.checkout_weighted(4).unwrap();
+    assert_eq!(2, more.len());
+    assert_eq!(4, more.weight());
+    assert_eq!(3, pool.in_use());
+
+    drop(vals);
+    drop(more);
+    assert_eq!(0, pool.in_use());
+
+    // Every entry weighs one by default
+    let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    let vals = pool.checkout_weighted(3).unwrap();
+    assert_eq!(3, vals.len());
+    assert_eq!(3, vals.into_vec().len());
+}
+
+#[test]
+pub fn test_joint_checkout() {
+    use pool::{JointCheckout, OnEmpty};
+    use std::thread;
+    use std::time::Duration;
+
+    let mut a = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Fail)
+        .build(|| Dirty(0u8));
+    let mut b = Pool::builder()
+        .capacity(1)
+        .on_empty(OnEmpty::Block)
+        .build(|| Dirty(String::new()));
+    let mut c = Pool::with_capacity(1, 0, || Dirty(0u64));
+
+    let held = b.checkout();
+
+    // Nothing is kept when one pool is out
+    assert!((&mut a, &mut b, &mut c).try_checkout_all().is_none());
+    assert_eq!(0, a.in_use());
+    assert_eq!(0, c.in_use());
+
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        drop(held);
+    });
+
+    // Waits for `b`, holding nothing meanwhile
+    let (x, y, z) = (&mut a, &mut b, &mut c).checkout_all().unwrap();
+    handle.join().unwrap();
+
+    assert_eq!(1, a.in_use());
+    assert_eq!(1, b.in_use());
+    assert_eq!(1, c.in_use());
+
+    // `a` doesn't block
+    assert!((&mut a, &mut c).checkout_all().is_none());
+    assert_eq!(1, c.in_use());
+
+    drop((x, y, z));
+    assert!((&mut a, &mut c).checkout_all().is_some());
+}
+
+#[test]
+pub fn test_managed_pool() {
+    use pool::manager::{ManagedPool, Manager};
+    use std::future::{self, Future};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    // Hands out numbered connections, odd ones fail to recycle
+    struct Connect(AtomicUsize);
+
+    impl Manager for Connect {
+        type Type = usize;
+        type Error = &'static str;
+
+        fn create(&self) -> impl Future<Output = Result<usize, &'static str>> + Send {
+            future::ready(Ok(self.0.fetch_add(1, Ordering::Relaxed)))
+        }
+
+        fn recycle(&self, conn: usize) -> impl Future<Output = Result<usize, &'static str>> + Send {
+            future::ready(if conn.is_multiple_of(2) { Ok(conn) } else { Err("broken") })
+        }
+    }
+
+    fn poll<F: Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+        Pin::new(f).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    let pool = ManagedPool::new(Connect(AtomicUsize::new(0)), 1);
+
+    let conn = match poll(&mut pool.get()) {
+        Poll::Ready(conn) => conn.unwrap(),
+        Poll::Pending => panic!("pool is empty"),
+    };
+    assert_eq!(0, *conn);
+
+    // Waits for the connection to be returned
+    {
+        let mut get = pool.get();
+        assert!(poll(&mut get).is_pending());
+    }
+
+    // Recycled
+    drop(conn);
+    let conn = match poll(&mut pool.get()) {
+        Poll::Ready(conn) => conn.unwrap(),
+        Poll::Pending => panic!("pool is empty"),
+    };
+    assert_eq!(0, *conn);
+
+    // Created again once retired
+    conn.retire();
+    let mut conn = match poll(&mut pool.get()) {
+        Poll::Ready(conn) => conn.unwrap(),
+        Poll::Pending => panic!("pool is empty"),
+    };
+    assert_eq!(1, *conn);
+
+    // Failed to recycle, replaced
+    *conn = 3;
+    drop(conn);
+    let conn = match poll(&mut pool.get()) {
+        Poll::Ready(conn) => conn.unwrap(),
+        Poll::Pending => panic!("pool is empty"),
+    };
+    assert_eq!(2, *conn);
+    assert_eq!(3, pool.manager().0.load(Ordering::Relaxed));
+}
+
+#[test]
+pub fn test_managed_pool_concurrent_waiters() {
+    use pool::manager::{ManagedPool, Manager};
+    use std::future::{self, Future};
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Connect;
+
+    impl Manager for Connect {
+        type Type = usize;
+        type Error = ();
+
+        fn create(&self) -> impl Future<Output = Result<usize, ()>> + Send {
+            future::ready(Ok(0))
+        }
+
+        fn recycle(&self, conn: usize) -> impl Future<Output = Result<usize, ()>> + Send {
+            future::ready(Ok(conn + 1))
+        }
+    }
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let pool = ManagedPool::new(Connect, 1);
+    let clone = pool.clone();
+
+    let wakes: Vec<_> = (0..3).map(|_| Arc::new(Count(AtomicUsize::new(0)))).collect();
+    let wakers: Vec<Waker> = wakes.iter().map(|w| w.clone().into()).collect();
+
+    let mut conn = match Pin::new(&mut pool.get()).poll(&mut Context::from_waker(&wakers[0])) {
+        Poll::Ready(conn) => Some(conn.unwrap()),
+        Poll::Pending => panic!("pool is empty"),
+    };
+
+    // Three tasks wait at once, through either handle
+    let mut gets = [pool.get(), clone.get(), clone.get()];
+
+    for (get, waker) in gets.iter_mut().zip(&wakers) {
+        assert!(Pin::new(get).poll(&mut Context::from_waker(waker)).is_pending());
+    }
+
+    assert_eq!(3, pool.status().waiters);
+
+    // Each checkin hands the connection to the next waiter, in order
+    for (i, (get, waker)) in gets.iter_mut().zip(&wakers).enumerate() {
+        drop(conn.take());
+        assert_eq!(1, wakes[i].0.load(Ordering::Relaxed));
+
+        match Pin::new(get).poll(&mut Context::from_waker(waker)) {
+            Poll::Ready(c) => {
+                let c = c.unwrap();
+                assert_eq!(i + 1, *c);
+                conn = Some(c);
+            }
+            Poll::Pending => panic!("woken without a connection"),
+        }
+    }
+
+    assert_eq!(0, pool.status().waiters);
+    drop(conn);
+    assert_eq!(1, clone.status().available);
+}
+
+#[test]
+pub fn test_scoped_pool() {
+    use pool::Builder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    let schema = ["id".to_string(), "name".to_string()];
+    let created = AtomicUsize::new(0);
+    let returned = AtomicUsize::new(0);
+
+    thread::scope(|t| {
+        let len = pool::scope(|s| {
+            let mut parsers = s.pool(&Builder::new().capacity(2), || {
+                created.fetch_add(1, Ordering::Relaxed);
+                Dirty(&schema[..])
+            });
+
+            let parser = parsers.checkout();
+            let returned = &returned;
+
+            t.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                returned.store(parser.len(), Ordering::Relaxed);
+            });
+
+            parsers.checkout().len()
+        });
+
+        // The scope waited for the checkout held by the thread
+        assert_eq!(2, len);
+        assert_eq!(2, returned.load(Ordering::Relaxed));
+    });
+
+    assert_eq!(2, created.load(Ordering::Relaxed));
+}
+
+#[test]
+#[cfg(feature = "testing")]
+pub fn test_stress() {
+    use pool::testing::Stress;
+
+    let mut pool = Pool::with_capacity(8, 16, || Dirty(0));
+
+    for seed in 0..4 {
+        let report = Stress::new()
+            .threads(4)
+            .iterations(5_000)
+            .seed(seed)
+            .run(&mut pool);
+
+        assert!(report.checkouts > 0);
+        assert!(report.failed > 0);
+    }
+
+    assert_eq!(8, pool.capacity());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+pub fn test_stress_configurations() {
+    use pool::IdleOrder;
+    use pool::testing::Stress;
+
+    let stress = Stress::new().threads(6).iterations(2_000).max_held(2);
+
+    let mut tiered = Pool::builder()
+        .capacity(16)
+        .hot_capacity(4)
+        .build(|| Dirty(0));
+    stress.run(&mut tiered);
+
+    let mut lru = Pool::builder()
+        .capacity(16)
+        .idle_order(IdleOrder::Lru)
+        .build(|| Dirty(0));
+    stress.run(&mut lru);
+
+    let mut segmented = Pool::builder()
+        .capacity(16)
+        .segment_size(1)
+        .build(|| Dirty(0));
+    stress.run(&mut segmented);
+}
+
+#[test]
+pub fn test_checkout_pool() {
+    use std::thread;
+
+    let mut pool = Pool::with_capacity(3, 0, || Dirty(0));
+    let val = pool.checkout();
+
+    // Handed to code that only gets the checkout
+    let handle = thread::spawn(move || {
+        let mut other = val.pool();
+        let more = (other.checkout(), other.checkout());
+
+        assert!(other.try_checkout().is_none());
+        drop(more);
+    });
+
+    handle.join().unwrap();
+    assert_eq!(0, pool.in_use());
+    assert_eq!(3, pool.capacity());
+}
+
+#[test]
+pub fn test_weak_pool() {
+    use pool::{Reset, WeakPool};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Conn {
+        pool: Option<WeakPool<Conn>>,
+        dropped: Arc<AtomicUsize>,
+    }
+
+    impl Reset for Conn {
+        fn reset(&mut self) {}
+    }
+
+    impl Drop for Conn {
+        fn drop(&mut self) {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let init = dropped.clone();
+    let mut pool = Pool::with_capacity(2, 0, move || Conn { pool: None, dropped: init.clone() });
+    let weak = pool.downgrade();
+
+    // Each value refers back to its pool
+    {
+        let (mut a, mut b) = (pool.checkout(), pool.checkout());
+        a.pool = Some(weak.clone());
+        b.pool = Some(weak.clone());
+    }
+
+    let val = pool.checkout();
+    drop(pool);
+
+    // Kept alive by the checkout, and by the upgraded pool
+    let mut other = val.pool.as_ref().unwrap().upgrade().unwrap();
+    drop(val);
+    assert!(other.try_checkout().is_some());
+    assert_eq!(0, dropped.load(Ordering::SeqCst));
+
+    drop(other);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(2, dropped.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn test_weak_pool_scoped() {
+    use pool::Builder;
+    use std::thread;
+
+    let name = String::from("conn");
+
+    // Upgrades race with the scope dropping the pool
+    thread::scope(|threads| {
+        pool::scope(|s| {
+            let pool = s.pool(&Builder::new().capacity(1), || Dirty(name.as_str()));
+            let weak = pool.downgrade();
+
+            threads.spawn(move || {
+                for _ in 0..1_000 {
+                    if let Some(mut pool) = weak.upgrade() {
+                        if let Some(val) = pool.try_checkout() {
+                            assert_eq!("conn", **val);
+                        }
+                    }
+                }
+            });
+        });
+    });
+}
+
+#[test]
+pub fn test_checkout_same_slot() {
+    let mut pool = Pool::with_capacity(2, 0, || Dirty(0));
+
+    let a = pool.checkout();
+    let b = pool.checkout();
+    assert!(a.same_slot(&a));
+    assert!(!a.same_slot(&b));
+
+    let a = a.share();
+    let b = b.share();
+    assert!(a.same_slot(&a.clone()));
+    assert!(!a.same_slot(&b));
+}
+
+#[test]
+pub fn test_checkout_slot() {
+    use pool::{Builder, OnEmpty};
+    use std::collections::HashSet;
+
+    let mut pool = Builder::new()
+        .capacity(3)
+        .on_empty(OnEmpty::Transient)
+        .build(|| Dirty(0));
+
+    let vals: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    let slots: HashSet<_> = vals.iter().map(|val| val.slot()).collect();
+    assert_eq!((0..3).collect::<HashSet<_>>(), slots);
+
+    assert_eq!(usize::MAX, pool.checkout().slot());
+
+    // The parent's indices through a child pool
+    drop(vals);
+
+    let mut child = Builder::new().parent(&pool).build(|| Dirty(0));
+    let vals: Vec<_> = (0..3).map(|_| child.checkout()).collect();
+    assert_eq!(slots, vals.iter().map(|val| val.slot()).collect());
+}
+
+#[test]
+pub fn test_slot_ptr() {
+    use pool::{Builder, Checkout};
+
+    let mut pool = Builder::new()
+        .capacity(4)
+        .extra_of::<u32>(2)
+        .build(|| Dirty(0usize));
+
+    let mut tokens = vec![];
+
+    for i in 0..4 {
+        let mut val = pool.checkout();
+        **val = i;
+        val.extra_mut()[1] = i as u32;
+
+        tokens.push(val.slot());
+        val.into_raw();
+    }
+
+    for (i, &token) in tokens.iter().enumerate() {
+        unsafe {
+            assert_eq!(i, **pool.slot_ptr(token));
+            assert_eq!(i as u32, *pool.slot_extra_ptr(token).add(1));
+
+            drop(Checkout::<_, u32>::from_raw(pool.slot_ptr(token)));
+        }
+    }
+
+    assert_eq!(0, pool.in_use());
+}
+
+#[test]
+#[should_panic]
+pub fn test_slot_ptr_out_of_bounds() {
+    let pool = Pool::with_capacity(4, 0, || Dirty(0));
+    unsafe { pool.slot_ptr(4); }
+}
+
+#[test]
+pub fn test_into_iter() {
+    let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+
+    let vals: Vec<_> = (0..4).map(|_| pool.checkout()).collect();
+    for mut val in vals {
+        **val = val.slot();
+    }
+
+    // Everything idle, in slot order
+    let values: Vec<_> = pool.into_iter().map(|val| *val).collect();
+    assert_eq!(vec![0, 1, 2, 3], values);
+
+    let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    let busy = (pool.checkout(), pool.checkout());
+
+    assert_eq!(2, pool.into_iter().count());
+    drop(busy);
+}
+
+#[test]
+pub fn test_checkout_as_ref() {
+    use std::borrow::{Borrow, BorrowMut};
+
+    fn len<V: AsRef<Dirty<Vec<u8>>>>(val: V) -> usize {
+        val.as_ref().len()
+    }
+
+    fn push<V: BorrowMut<Dirty<Vec<u8>>>>(mut val: V) {
+        val.borrow_mut().push(1);
+    }
+
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(vec![]));
+
+    push(pool.checkout());
+    assert_eq!(1, len(pool.checkout()));
+
+    let mut val = pool.checkout();
+    val.as_mut().clear();
+    let borrowed: &Dirty<Vec<u8>> = val.borrow();
+    assert!(borrowed.is_empty());
+}
+
+#[test]
+pub fn test_checkout_ord() {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut pool = Pool::with_capacity(3, 0, || Dirty(0));
+    let mut heap = BinaryHeap::new();
+
+    for deadline in [30, 10, 20] {
+        let mut val = pool.checkout();
+        **val = deadline;
+        heap.push(Reverse(val));
+    }
+
+    let order: Vec<_> = std::iter::from_fn(|| heap.pop()).map(|Reverse(val)| **val).collect();
+    assert_eq!(vec![10, 20, 30], order);
+
+    fn hash<V: Hash>(val: &V) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let mut a = pool.checkout();
+    let mut b = pool.checkout();
+    **a = 5;
+    **b = 5;
+
+    assert!(a == b);
+    assert_eq!(hash(&Dirty(5)), hash(&a));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+pub fn test_checkout_serialize() {
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(vec![]));
+    let mut val = pool.checkout();
+    val.extend([1, 2]);
+
+    assert_eq!("[1,2]", serde_json::to_string(&val).unwrap());
+}
+
+#[test]
+pub fn test_checkout_fmt() {
+    use pool::Builder;
+
+    let mut pool = Pool::with_capacity(1, 0, String::new);
+    let mut val = pool.checkout();
+    val.push_str("conn");
+
+    assert_eq!("conn", val.to_string());
+    assert_eq!(r#"Checkout { slot: 0, held_for: None, value: "conn" }"#, format!("{:?}", val));
+    drop(val);
+
+    let mut pool = Builder::new()
+        .capacity(1)
+        .track_hold_time()
+        .build(|| Dirty(0));
+
+    let val = pool.checkout();
+    assert!(format!("{:?}", val).contains("held_for: Some("));
+}
+
+#[test]
+pub fn test_token_table() {
+    use pool::TokenTable;
+
+    struct Token(usize);
+
+    impl From<Token> for usize {
+        fn from(token: Token) -> usize {
+            token.0
+        }
+    }
+
+    let mut pool = Pool::with_capacity(4, 0, || Dirty(0));
+    let mut table = TokenTable::new();
+
+    for i in 0..3 {
+        assert!(table.register(Token(i * 10), pool.checkout()).is_none());
+    }
+
+    assert_eq!(3, table.len());
+    assert!(table.contains(Token(10)));
+    assert!(table.get(Token(5)).is_none());
+
+    ***table.get_mut(Token(20)).unwrap() = 7;
+    assert_eq!(7, ***table.get(20usize).unwrap());
+
+    // Replacing hands back the previous checkout
+    let prev = table.register(Token(20), pool.checkout()).unwrap();
+    assert_eq!(7, **prev);
+    drop(prev);
+    assert_eq!(3, pool.in_use());
+
+    for (_, val) in table.iter_mut() {
+        ***val = 1;
+    }
+
+    drop(table.deregister(Token(0)));
+    assert!(table.deregister(Token(0)).is_none());
+    assert_eq!(2, pool.in_use());
+
+    let rest = table.drain();
+    assert_eq!(2, rest.len());
+    assert!(table.is_empty());
+
+    drop(rest);
+    assert_eq!(0, pool.in_use());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_checkout_fd() {
+    use std::fs::File;
+    use std::os::unix::io::{AsFd, AsRawFd, RawFd};
+    use std::sync::{Arc, Mutex};
+
+    struct Timer {
+        file: File,
+        closed: Arc<Mutex<Vec<RawFd>>>,
+    }
+
+    impl AsRawFd for Timer {
+        fn as_raw_fd(&self) -> RawFd {
+            self.file.as_raw_fd()
+        }
+    }
+
+    impl Drop for Timer {
+        fn drop(&mut self) {
+            self.closed.lock().unwrap().push(self.file.as_raw_fd());
+        }
+    }
+
+    let closed = Arc::new(Mutex::new(vec![]));
+    let c = closed.clone();
+    let mut pool = Pool::with_capacity(1, 0, move || Dirty(Timer {
+        file: File::open("/dev/null").unwrap(),
+        closed: c.clone(),
+    }));
+
+    let timer = pool.checkout();
+    let fd = timer.as_raw_fd();
+    assert_eq!(fd, timer.file.as_raw_fd());
+
+    // Returned, not closed
+    drop(timer);
+    assert!(closed.lock().unwrap().is_empty());
+
+    // Invalidated, closed on the spot
+    pool.checkout().retire();
+    assert_eq!(vec![fd], *closed.lock().unwrap());
+
+    let mut files = Pool::with_capacity(1, 0, || Dirty(File::open("/dev/null").unwrap()));
+    let file = files.checkout();
+    assert_eq!(file.as_raw_fd(), file.as_fd().as_raw_fd());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_iovecs() {
+    use pool::{Builder, OnEmpty};
+
+    let mut pool = Builder::new()
+        .capacity(3)
+        .extra(512)
+        .on_empty(OnEmpty::Transient)
+        .build(|| Dirty(()));
+
+    let iovecs = pool.iovecs();
+    assert_eq!(3, iovecs.len());
+
+    let mut bufs: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+
+    for buf in &mut bufs {
+        let iovec = iovecs[buf.buf_index().unwrap() as usize];
+
+        assert_eq!(512, iovec.iov_len);
+        assert_eq!(buf.extra_mut().as_mut_ptr() as *mut _, iovec.iov_base);
+    }
+
+    assert!(pool.checkout().buf_index().is_none());
+}
+
+#[test]
+pub fn test_page_aligned() {
+    use pool::{Builder, OnEmpty};
+
+    let mut pool = Builder::new()
+        .capacity(3)
+        .extra(4096)
+        .page_aligned()
+        .align_extra(64)
+        .segment_size(16 * 1024)
+        .on_empty(OnEmpty::Grow { max: 8 })
+        .build(|| Dirty(0u64));
+
+    // Across segments, and grown ones
+    let vals: Vec<_> = (0..8).map(|_| pool.checkout()).collect();
+
+    for val in &vals {
+        assert_eq!(0, val.extra().as_ptr() as usize % 4096);
+        assert!(val.extra().len() >= 4096);
+    }
+}
+
+#[test]
+pub fn test_global_pool() {
+    use pool::global::GlobalPool;
+    use std::thread;
+
+    static POOL: GlobalPool<Dirty<usize>> = GlobalPool::new(|| Pool::with_capacity(4, 0, || Dirty(0)));
+
+    assert!(!POOL.is_initialized());
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| thread::spawn(move || {
+            for _ in 0..100 {
+                let mut val = POOL.checkout();
+                **val = i;
+            }
+        }))
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(POOL.is_initialized());
+    assert_eq!(4, POOL.with(|pool| pool.capacity()));
+
+    let held: Vec<_> = (0..4).map(|_| POOL.checkout()).collect();
+    assert!(POOL.try_checkout().is_none());
+    drop(held);
+    assert_eq!(0, POOL.with(|pool| pool.in_use()));
+}
+
+#[test]
+pub fn test_global_pool_waits_without_lock() {
+    use pool::global::GlobalPool;
+    use std::thread;
+    use std::time::Duration;
+
+    static POOL: GlobalPool<Dirty<usize>> = GlobalPool::new(|| Pool::with_capacity(1, 0, || Dirty(0)));
+
+    let held = POOL.checkout();
+    let waiter = thread::spawn(|| **POOL.checkout());
+
+    // The waiting checkout does not keep others from the pool
+    thread::sleep(Duration::from_millis(50));
+    assert!(POOL.try_checkout().is_none());
+    assert_eq!(1, POOL.with(|pool| pool.in_use()));
+
+    drop(held);
+    assert_eq!(0, waiter.join().unwrap());
+}
+
+#[test]
+#[cfg(feature = "allocator-api2")]
+pub fn test_pool_allocator() {
+    use allocator_api2::alloc::Allocator;
+    use allocator_api2::boxed::Box;
+    use allocator_api2::vec::Vec;
+    use std::alloc::Layout;
+    use pool::Builder;
+    use pool::allocator::PoolAllocator;
+
+    let alloc = PoolAllocator::new(Builder::new()
+        .capacity(2)
+        .extra(256)
+        .align_extra(64)
+        .build(|| Dirty(())));
+
+    assert!(alloc.block_size() >= 256);
+
+    {
+        let mut a: Vec<u64, _> = Vec::with_capacity_in(32, &alloc);
+        a.extend(0..32);
+
+        let b = Box::new_in([7u8; 64], &alloc);
+        assert_eq!(0, &*b as *const _ as usize % 64);
+
+        // Out of entries, and too large, from the global allocator
+        let too_large: Vec<u8, _> = Vec::with_capacity_in(4096, &alloc);
+        assert!(too_large.capacity() >= 4096);
+
+        assert_eq!(496, a.iter().sum::<u64>());
+        assert!(alloc.allocate(Layout::new::<[u8; 16]>()).is_err());
+    }
+
+    let mut pool = alloc.into_inner();
+    assert_eq!(0, pool.in_use());
+    assert!(pool.try_checkout().is_some());
+}
+
+#[test]
+#[cfg(feature = "macros")]
+pub fn test_pooled() {
+    use pool::{pooled, Builder};
+
+    fn halve(buf: &mut Vec<u8>) {
+        buf.truncate(buf.len() / 2);
+    }
+
+    fn frame() -> Frame {
+        Frame(vec![0; 4], 1)
+    }
+
+    #[pooled(pool = Frames, handle = FrameBuf, init = frame)]
+    struct Frame(#[reset(with = halve)] Vec<u8>, u32);
+
+    let mut frames = Frames::build(&Builder::new().capacity(2));
+    assert_eq!(2, frames.capacity());
+
+    // Values are reset on checkout
+    let mut buf: FrameBuf = frames.checkout();
+    assert_eq!((2, 0), (buf.0.len(), buf.1));
+    buf.1 = 7;
+    drop(buf);
+
+    let buf = frames.checkout();
+    assert_eq!((1, 0), (buf.0.len(), buf.1));
+    drop(buf);
+
+    let pool: Pool<Frame> = frames.into_inner();
+    assert_eq!(0, pool.in_use());
+    assert_eq!(3, Frames::with_capacity(3).capacity());
+
+    // Without a capacity, the pool grows instead of waiting forever
+    let mut frames = Frames::new();
+    assert_eq!(0, frames.capacity());
+
+    let bufs: Vec<_> = (0..3).map(|_| frames.checkout()).collect();
+    assert!(frames.capacity() >= 3);
+    drop(bufs);
+}
+
+#[test]
+pub fn test_checkout_future() {
+    use pool::Reset;
+    use std::future::{self, Future};
+    use std::marker::PhantomPinned;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    struct Countdown(u32);
+
+    impl Future for Countdown {
+        type Output = &'static str;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'static str> {
+            if self.0 == 0 {
+                return Poll::Ready("done");
+            }
+
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    impl Reset for Countdown {
+        fn reset(&mut self) {
+            self.0 = 2;
+        }
+    }
+
+    fn block_on<F: Future + Unpin>(mut fut: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(out) = Pin::new(&mut fut).poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    let mut pool = Pool::with_capacity(1, 0, || Countdown(0));
+
+    assert_eq!("done", block_on(pool.checkout()));
+    assert_eq!("done", block_on(pool.checkout()));
+
+    // Not `Unpin`, polled pinned
+    struct Pinned(Countdown, PhantomPinned);
+
+    impl Future for Pinned {
+        type Output = &'static str;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'static str> {
+            unsafe { self.map_unchecked_mut(|pinned| &mut pinned.0) }.poll(cx)
+        }
+    }
+
+    impl Reset for Pinned {
+        fn reset(&mut self) {}
+    }
+
+    let mut pool = Pool::with_capacity(1, 0, || Pinned(Countdown(3), PhantomPinned));
+    assert_eq!("done", block_on(pool.checkout().into_pin()));
+
+    // Through `Dirty`
+    let mut pool = Pool::with_capacity(1, 0, || Dirty(future::ready(7)));
+    assert_eq!(7, block_on(pool.checkout_dirty()));
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_stack_pool() {
+    use pool::stack::StackPool;
+
+    let stacks = StackPool::new(4, 10_000).unwrap();
+    let page = stacks.guard_size();
+
+    assert_eq!(4, stacks.capacity());
+    assert_eq!(0, stacks.stack_size() % page);
+    assert!(stacks.stack_size() >= 10_000);
+
+    let mut held: Vec<_> = (0..4).map(|_| stacks.try_checkout().unwrap()).collect();
+    assert!(stacks.try_checkout().is_none());
+    assert_eq!(4, stacks.in_use());
+
+    for stack in &held {
+        assert_eq!(0, stack.top() as usize % page);
+        assert_eq!(stacks.stack_size(), stack.size());
+
+        // The whole stack is writable
+        unsafe { std::ptr::write_bytes(stack.bottom(), 0xAB, stack.size()); }
+    }
+
+    // Stacks don't overlap, and are separated by guards
+    held.sort_by_key(|stack| stack.bottom() as usize);
+
+    for pair in held.windows(2) {
+        assert_eq!(pair[0].top() as usize + page, pair[1].bottom() as usize);
+    }
+
+    let idx = held[2].index();
+    held.remove(2);
+    assert_eq!(idx, stacks.try_checkout().unwrap().index());
+
+    drop(held);
+    assert_eq!(0, stacks.in_use());
+    assert!(stacks.release() <= 4 * stacks.stack_size());
+
+    assert!(StackPool::new(1, 0).is_err());
+    assert_eq!(0, StackPool::new(0, 4096).unwrap().capacity());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_stack_pool_guard() {
+    use pool::stack::StackPool;
+
+    let stacks = StackPool::new(1, 4096).unwrap();
+    let stack = stacks.try_checkout().unwrap();
+
+    unsafe {
+        match libc::fork() {
+            0 => {
+                // Overflow the stack by a byte
+                std::ptr::write_volatile(stack.bottom().sub(1), 1);
+                libc::_exit(0);
+            }
+            pid => {
+                let mut status = 0;
+                assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+                assert!(libc::WIFSIGNALED(status));
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_io_slices() {
+    use std::io::{Read, Write};
+
+    let mut pool = Pool::with_capacity(4, 4, || Dirty(()));
+
+    // Scatter a message over several entries
+    let mut bufs = pool.checkout_weighted(3).unwrap();
+    let size = bufs[0].extra().len();
+    let msg: Vec<u8> = (0..3 * size + 2).map(|i| i as u8).collect();
+
+    let mut src = &msg[..];
+    assert_eq!(3 * size, src.read_vectored(&mut bufs.io_slices_mut()).unwrap());
+    assert_eq!(2, src.len());
+
+    for (buf, chunk) in bufs.iter().zip(msg.chunks(size)) {
+        assert_eq!(chunk, buf.extra());
+    }
+
+    // And gather it back
+    let mut out = vec![];
+    assert_eq!(3 * size, out.write_vectored(&bufs.io_slices()).unwrap());
+    assert_eq!(&msg[..3 * size], &out[..]);
+
+    let slices = pool::io_slices(bufs.iter().rev());
+    assert_eq!(&msg[2 * size..3 * size], &slices[0][..]);
+    assert_eq!(&msg[..size], &slices[2][..]);
+
+    let mut one = pool.checkout();
+    one.io_slice_mut()[..4].copy_from_slice(b"solo");
+    assert_eq!(b"solo", &one.io_slice()[..4]);
+    assert!(pool::io_slices_mut(&mut Vec::<pool::Checkout<Dirty<()>>>::new()).is_empty());
+}
+
+#[test]
+pub fn test_extra_ring() {
+    use pool::ExtraRing;
+    use std::io::{Read, Write};
+
+    let mut pool = Pool::with_capacity(1, 10, || Dirty(7));
+    let mut ring = ExtraRing::new(pool.checkout());
+    let cap = ring.capacity();
+
+    assert!(cap >= 10);
+    assert_eq!(7, **ring.get_ref());
+    assert!(ring.is_empty());
+
+    // Fill it up
+    let data: Vec<u8> = (0..cap as u8).collect();
+    assert_eq!(cap, ring.write(&data).unwrap());
+    assert!(ring.is_full());
+    assert_eq!(0, ring.write(b"more").unwrap());
+    assert!(ring.write_all(b"more").is_err());
+
+    // Read some, and write across the end
+    let mut buf = [0; 4];
+    ring.read_exact(&mut buf).unwrap();
+    assert_eq!(&data[..4], &buf);
+    ring.write_all(b"wrap").unwrap();
+
+    {
+        let (first, second) = ring.as_slices();
+        assert_eq!(&data[4..], first);
+        assert_eq!(b"wrap", second);
+    }
+
+    let mut out = vec![];
+    ring.read_to_end(&mut out).unwrap();
+    assert_eq!([&data[4..], b"wrap"].concat(), out);
+    assert!(ring.is_empty());
+
+    // Free slices wrap around as well
+    {
+        let (first, second) = ring.free_slices();
+        assert_eq!(cap, first.len() + second.len());
+        assert_eq!(cap - 4, first.len());
+        first[0] = b'!';
+    }
+    ring.commit(1);
+    assert_eq!((&b"!"[..], &b""[..]), ring.as_slices());
+    ring.consume(1);
+
+    ring.clear();
+    assert_eq!(0, ring.len());
+    drop(ring.into_inner());
+    assert_eq!(0, pool.in_use());
+}
+
+#[test]
+pub fn test_extra_ring_split() {
+    use pool::ExtraRing;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    let mut pool = Pool::with_capacity(1, 16, || Dirty(()));
+    let mut ring = ExtraRing::new(pool.checkout());
+    let (mut producer, mut consumer) = ring.split();
+
+    let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+    let mut out = vec![];
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut rem = &data[..];
+
+            while !rem.is_empty() {
+                match producer.write(rem).unwrap() {
+                    0 => thread::yield_now(),
+                    n => rem = &rem[n..],
+                }
+            }
+        });
+
+        let mut buf = [0; 7];
+
+        while out.len() < data.len() {
+            match consumer.read(&mut buf).unwrap() {
+                0 => thread::yield_now(),
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+    });
+
+    assert_eq!(data, out);
+    assert!(ring.is_empty());
+}
+
+#[test]
+pub fn test_buffer_chain() {
+    use pool::BufferChain;
+    use std::io::Read;
+
+    let mut pool = Pool::with_capacity(4, 16, || Dirty(()));
+    let size = pool.checkout().extra().len();
+
+    let msg: Vec<u8> = (0..3 * size + 5).map(|i| i as u8).collect();
+    let mut chain = BufferChain::new();
+
+    // Runs out of entries
+    assert_eq!(4 * size, chain.append(&mut pool, &[&msg[..], &msg[..size - 5]].concat()));
+    chain.clear();
+    assert_eq!(0, pool.in_use());
+
+    // Appends carry on in the last segment
+    assert_eq!(size + 2, chain.append(&mut pool, &msg[..size + 2]));
+    assert_eq!(2 * size + 3, chain.append(&mut pool, &msg[size + 2..]));
+    assert_eq!(msg.len(), chain.len());
+    assert_eq!(4, chain.segments());
+    assert_eq!(4, pool.in_use());
+
+    let slices = chain.io_slices();
+    assert_eq!(msg, slices.iter().flat_map(|slice| slice.iter().cloned()).collect::<Vec<_>>());
+    drop(slices);
+
+    // Reads cross segments, checking in those read in full
+    let mut buf = vec![0; size + 3];
+    chain.read_exact(&mut buf).unwrap();
+    assert_eq!(&msg[..size + 3], &buf[..]);
+    assert_eq!(3, chain.segments());
+    assert_eq!(3, pool.in_use());
+    assert_eq!(&msg[size + 3..2 * size], chain.chunk());
+
+    // A segment pushed by hand
+    let mut seg = pool.checkout();
+    seg.extra_mut()[..3].copy_from_slice(b"end");
+    chain.push(seg, 3);
+
+    let mut rest = vec![];
+    chain.read_to_end(&mut rest).unwrap();
+    assert_eq!([&msg[size + 3..], b"end"].concat(), rest);
+    assert!(chain.is_empty());
+    assert_eq!(0, pool.in_use());
+
+    // Whatever is left is checked in on drop
+    chain.append(&mut pool, &msg);
+    drop(chain);
+    assert_eq!(0, pool.in_use());
+}
+
+#[test]
+#[cfg(feature = "bytes")]
+pub fn test_buffer_chain_buf() {
+    use bytes::Buf;
+    use pool::BufferChain;
+    use std::io::IoSlice;
+
+    let mut pool = Pool::with_capacity(8, 16, || Dirty(()));
+    let msg: Vec<u8> = (0..100).collect();
+
+    let mut chain = BufferChain::new();
+    assert_eq!(100, chain.append(&mut pool, &msg));
+
+    let mut slices = [IoSlice::new(&[]); 16];
+    let n = chain.chunks_vectored(&mut slices);
+    assert_eq!(chain.segments(), n);
+    assert_eq!(msg, slices[..n].iter().flat_map(|slice| slice.iter().cloned()).collect::<Vec<_>>());
+
+    assert_eq!(0, chain.get_u8());
+    assert_eq!(u32::from_be_bytes([1, 2, 3, 4]), chain.get_u32());
+
+    let rest = chain.copy_to_bytes(95);
+    assert_eq!(&msg[5..], &rest[..]);
+    assert!(!chain.has_remaining());
+    assert_eq!(0, pool.in_use());
+}
+
+#[cfg(feature = "proptest")]
+proptest! {
+    #[test]
+    fn prop_layout(config: pool::arbitrary::PoolConfig) {
+        let mut pool = config.builder().build(|| Dirty(0u8));
+        let mut held: Vec<pool::Checkout<Dirty<u8>>> = vec![];
+
+        while let Some(val) = pool.try_checkout() {
+            // Entries are only shifted off their alignment to align the
+            // extra bytes, all by as much
+            let offset = val.data_ptr() as usize % config.align;
+
+            if config.extra_align == 1 {
+                prop_assert_eq!(0, offset);
+            }
+
+            if let Some(first) = held.first() {
+                prop_assert_eq!(first.data_ptr() as usize % config.align, offset);
+            }
+
+            prop_assert_eq!(0, val.extra_ptr() as usize % config.extra_align);
+            prop_assert!(val.extra().len() >= config.extra);
+
+            held.push(val);
+
+            if held.len() > 256 {
+                break;
+            }
+        }
+
+        // No two entries overlap
+        let mut ranges: Vec<_> = held.iter()
+            .map(|val| (val.data_ptr() as usize, val.extra_ptr() as usize + val.extra().len()))
+            .collect();
+        ranges.sort();
+
+        for pair in ranges.windows(2) {
+            prop_assert!(pair[0].1 <= pair[1].0);
+        }
+    }
+
+    #[test]
+    fn prop_checkouts_match_model(config: pool::arbitrary::PoolConfig, ops: Vec<pool::arbitrary::Op>) {
+        use pool::arbitrary::{Model, Op};
+
+        let mut pool = config.builder().build(|| Dirty(0u8));
+        let mut model = Model::new(&config);
+        let mut held = vec![];
+
+        for op in ops {
+            match op {
+                Op::Checkout => {
+                    let checkout = pool.try_checkout();
+                    prop_assert_eq!(model.checkout(), checkout.is_some());
+                    held.extend(checkout);
+                }
+                Op::Checkin(idx) if !held.is_empty() => {
+                    held.swap_remove(idx % held.len());
+                    model.checkin();
+                }
+                Op::Checkin(_) => {}
+            }
+
+            prop_assert_eq!(model.held(), held.len());
+        }
+    }
+}